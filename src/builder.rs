@@ -0,0 +1,340 @@
+//! # Builder Module
+//!
+//! Configures a `Server` through chained method calls.
+//!
+//! `ServerBuilder` collects the settings first and checks them together in
+//! [`ServerBuilder::build`], before the address is bound, so a bad
+//! combination is reported as a `ServerError::Config` instead of surfacing
+//! while requests are served. `Server::build` remains the shortcut for a
+//! server with the defaults.
+//!
+//! ## Example
+//!
+//! ```rust
+//! use rwf::Server;
+//! use std::time::Duration;
+//!
+//! let server = Server::builder()
+//!     .timeout(Duration::from_secs(10))
+//!     .max_body(64 * 1024)
+//!     .quiet(true)
+//!     .build("127.0.0.1:0")
+//!     .unwrap();
+//! assert_ne!(server.address().unwrap().port(), 0);
+//!
+//! // Settings are checked before binding
+//! assert!(Server::builder().timeout(Duration::ZERO).build("127.0.0.1:0").is_err());
+//! ```
+
+use crate::{
+    config::{Config, MetricsEndpoint},
+    connection::metrics::Metrics,
+    Server, ServerError,
+};
+use std::{sync::Arc, time::Duration};
+
+/// Collects the configuration of a `Server` before it is bound.
+///
+/// Every setting starts from the same default as a server created with
+/// `Server::build`, listed on each method.
+///
+/// There is no `workers` setting: rather than handing connections to a
+/// fixed pool, the server serves each one on a thread of its own, started
+/// when it is accepted. To bound the number of threads serving requests,
+/// limit the connections served at once with
+/// [`ServerBuilder::max_connections`].
+#[derive(Debug, Clone, Default)]
+pub struct ServerBuilder {
+    config: Config,
+    quiet: Option<bool>,
+    case_insensitive: bool,
+    ignore_trailing_slash: bool,
+    strict_routes: bool,
+}
+
+impl ServerBuilder {
+    /// Creates a builder holding the default configuration.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets how long a connection may stay silent before it is closed,
+    /// whether idle between requests or in the middle of sending one.
+    ///
+    /// Defaults to 5 seconds. Must not be zero.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.config.read_timeout = timeout;
+        self
+    }
+
+    /// Sets how long a persistent connection may stay idle between two
+    /// requests, as by `Server::keepalive_timeout`.
+    ///
+    /// Defaults to the timeout. Must not be zero.
+    pub fn keepalive_timeout(mut self, timeout: Duration) -> Self {
+        self.config.keepalive_timeout = Some(timeout);
+        self
+    }
+
+    /// Sets the number of requests served on a connection before it is
+    /// closed, as by `Server::max_requests_per_conn`.
+    ///
+    /// Defaults to unlimited. Must not be zero.
+    pub fn max_requests_per_conn(mut self, count: usize) -> Self {
+        self.config.max_requests_per_conn = Some(count);
+        self
+    }
+
+    /// Sets how long busy connections may take to finish once the server
+    /// stops, as by `Server::shutdown_grace`.
+    ///
+    /// Defaults to not draining connections.
+    pub fn shutdown_grace(mut self, grace: Duration) -> Self {
+        self.config.shutdown_grace = Some(grace);
+        self
+    }
+
+    /// Sets the number of connections served at once, as by
+    /// `Server::max_connections`.
+    ///
+    /// Defaults to unlimited. Must not be zero.
+    pub fn max_connections(mut self, count: usize) -> Self {
+        self.config.max_connections = Some(count);
+        self
+    }
+
+    /// Sets the maximum size in bytes of a request body, as by
+    /// `Server::max_body_size`.
+    ///
+    /// Defaults to 1 MiB.
+    pub fn max_body(mut self, bytes: usize) -> Self {
+        self.config.limits.max_body_size = bytes;
+        self
+    }
+
+    /// Sets the size in bytes above which request bodies are spooled to a
+    /// temporary file, as by `Server::max_buffered_body`.
+    ///
+    /// Defaults to keeping every body in memory.
+    pub fn max_buffered_body(mut self, bytes: usize) -> Self {
+        self.config.limits.max_buffered_body = Some(bytes);
+        self
+    }
+
+    /// Sets the maximum size in bytes of a request's header block, as by
+    /// `Server::max_header_size`.
+    ///
+    /// Defaults to 8 KiB. Must not be zero.
+    pub fn max_header_size(mut self, bytes: usize) -> Self {
+        self.config.limits.max_header_size = bytes;
+        self
+    }
+
+    /// Sets the maximum length in bytes of a request target, as by
+    /// `Server::max_uri_length`.
+    ///
+    /// Defaults to 8 KiB. Must not be zero.
+    pub fn max_uri_length(mut self, bytes: usize) -> Self {
+        self.config.limits.max_uri_length = bytes;
+        self
+    }
+
+    /// Sets the maximum number of header lines a request may carry, as by
+    /// `Server::max_headers`.
+    ///
+    /// Defaults to 100.
+    pub fn max_headers(mut self, count: usize) -> Self {
+        self.config.limits.max_headers = count;
+        self
+    }
+
+    /// Sets whether the startup messages are suppressed, as by `Server::quiet`.
+    ///
+    /// Defaults to the `RWF_QUIET` environment variable.
+    pub fn quiet(mut self, quiet: bool) -> Self {
+        self.quiet = Some(quiet);
+        self
+    }
+
+    /// Sets the secret key session cookies are signed with, as by `Server::secret`.
+    ///
+    /// Defaults to a random key generated for the server. Must not be empty.
+    pub fn secret(mut self, key: &str) -> Self {
+        self.config.secret = key.as_bytes().to_vec();
+        self
+    }
+
+    /// Sets whether the client address is read from proxy headers, as by
+    /// `Server::trust_proxy`.
+    ///
+    /// Defaults to `false`.
+    pub fn trust_proxy(mut self, trust: bool) -> Self {
+        self.config.trust_proxy = trust;
+        self
+    }
+
+    /// Sets whether unsafe requests must carry the session's CSRF token, as
+    /// by `Server::csrf`.
+    ///
+    /// Defaults to `false`.
+    pub fn csrf(mut self, enabled: bool) -> Self {
+        self.config.csrf = enabled;
+        self
+    }
+
+    /// Sets the file name served for requests to a directory path, as by
+    /// `Server::index_file`.
+    ///
+    /// Defaults to `index.html`. Must not be empty.
+    pub fn index_file(mut self, name: &str) -> Self {
+        self.config.index_file = name.to_string();
+        self
+    }
+
+    /// Sets whether `Server::serve_dir` lists directories without an index
+    /// file, as by `Server::listing`.
+    ///
+    /// Defaults to `false`.
+    pub fn listing(mut self, enabled: bool) -> Self {
+        self.config.listing = enabled;
+        self
+    }
+
+    /// Sets the `Content-Type` of responses that have none, as by
+    /// `Server::default_content_type`.
+    ///
+    /// Defaults to `text/html; charset=utf-8`.
+    pub fn default_content_type(mut self, content_type: &str) -> Self {
+        self.config.default_content_type = content_type.to_string();
+        self
+    }
+
+    /// Sets where requests to protected routes without a session are
+    /// redirected, as by `Server::login_path`.
+    ///
+    /// Defaults to none, answering `401 Unauthorized`.
+    pub fn login_path(mut self, path: &str) -> Self {
+        self.config.login_path = Some(path.to_string());
+        self
+    }
+
+    /// Sets whether `TRACE` requests reach routes, as by `Server::allow_trace`.
+    ///
+    /// Defaults to `false`.
+    pub fn allow_trace(mut self, enabled: bool) -> Self {
+        self.config.allow_trace = enabled;
+        self
+    }
+
+    /// Sets the name of the threads serving connections, as by
+    /// `Server::thread_name`.
+    ///
+    /// Defaults to `rwf-worker`. Must not be empty.
+    pub fn thread_name(mut self, name: &str) -> Self {
+        self.config.thread_name = name.to_string();
+        self
+    }
+
+    /// Sets whether the server's own errors are sent as JSON to clients
+    /// preferring it, as by `Server::json_errors`.
+    ///
+    /// Defaults to `false`.
+    pub fn json_errors(mut self, enabled: bool) -> Self {
+        self.config.json_errors = enabled;
+        self
+    }
+
+    /// Collects request metrics and exposes them on the given path, as by
+    /// `Server::metrics`.
+    ///
+    /// Defaults to none, collecting no metrics.
+    pub fn metrics(mut self, path: &str) -> Self {
+        self.config.metrics = Some(MetricsEndpoint {
+            path: path.to_string(),
+            collector: Arc::new(Metrics::new()),
+        });
+        self
+    }
+
+    /// Sets whether routes match request paths regardless of case, as by
+    /// `Server::case_insensitive_routes`.
+    ///
+    /// Defaults to `false`.
+    pub fn case_insensitive_routes(mut self, enabled: bool) -> Self {
+        self.case_insensitive = enabled;
+        self
+    }
+
+    /// Sets whether a trailing `/` is ignored when matching routes, as by
+    /// `Server::ignore_trailing_slash`.
+    ///
+    /// Defaults to `false`.
+    pub fn ignore_trailing_slash(mut self, enabled: bool) -> Self {
+        self.ignore_trailing_slash = enabled;
+        self
+    }
+
+    /// Sets whether registering a duplicate route panics, as by
+    /// `Server::strict_routes`.
+    ///
+    /// Defaults to `false`.
+    pub fn strict_routes(mut self, enabled: bool) -> Self {
+        self.strict_routes = enabled;
+        self
+    }
+
+    /// Checks the settings and builds a server bound to the given address.
+    ///
+    /// # Arguments
+    ///
+    /// * `addr` - A string slice containing the address in format "host:port"
+    ///
+    /// # Returns
+    ///
+    /// * `Result<Server, ServerError>` - The configured server, a
+    ///   `ServerError::Config` describing the first invalid setting, or a
+    ///   `ServerError::Bind` if the address cannot be bound. Converts into an
+    ///   `io::Error` for functions returning `io::Result`.
+    pub fn build(self, addr: &str) -> Result<Server, ServerError> {
+        self.validate()?;
+
+        let mut server = Server::build(addr)?;
+        server.config = self.config;
+        if let Some(quiet) = self.quiet {
+            server.quiet(quiet);
+        }
+        server.case_insensitive_routes(self.case_insensitive);
+        server.ignore_trailing_slash(self.ignore_trailing_slash);
+        server.strict_routes(self.strict_routes);
+        Ok(server)
+    }
+
+    /// Rejects settings the server cannot run with.
+    fn validate(&self) -> Result<(), ServerError> {
+        let invalid = if self.config.read_timeout.is_zero() {
+            Some("the timeout must be greater than zero")
+        } else if self.config.keepalive_timeout.is_some_and(|t| t.is_zero()) {
+            Some("the keep-alive timeout must be greater than zero")
+        } else if self.config.max_requests_per_conn == Some(0) {
+            Some("the maximum requests per connection must be greater than zero")
+        } else if self.config.max_connections == Some(0) {
+            Some("the maximum number of connections must be greater than zero")
+        } else if self.config.limits.max_header_size == 0 {
+            Some("the maximum header size must be greater than zero")
+        } else if self.config.limits.max_uri_length == 0 {
+            Some("the maximum URI length must be greater than zero")
+        } else if self.config.secret.is_empty() {
+            Some("the session secret must not be empty")
+        } else if self.config.index_file.is_empty() {
+            Some("the index file name must not be empty")
+        } else if self.config.thread_name.is_empty() {
+            Some("the thread name must not be empty")
+        } else {
+            None
+        };
+        match invalid {
+            Some(message) => Err(ServerError::Config(message.to_string())),
+            None => Ok(()),
+        }
+    }
+}
@@ -0,0 +1,170 @@
+//! # Config Module
+//!
+//! Holds the server settings shared with every `RequestHandler`.
+
+use crate::connection::{
+    cache::ResponseCache,
+    clock::{Clock, SystemClock},
+    metrics::Metrics,
+    rate_limit::RateLimiter,
+    request::{Limits, Request},
+    response::Response,
+    secure_headers::SecureHeaders,
+    template::Templates,
+};
+use std::{collections::HashMap, sync::Arc, time::Duration};
+
+/// Settings applied while handling requests.
+///
+/// Built up through the `Server` configuration methods and shared with each
+/// connection's `RequestHandler` once the server starts running.
+#[derive(Debug, Clone)]
+pub struct Config {
+    /// Size limits applied while parsing requests
+    pub limits: Limits,
+    /// The built-in health check endpoint, if enabled
+    pub health_check: Option<HealthCheck>,
+    /// The metrics endpoint and the collector it renders, if enabled
+    pub metrics: Option<MetricsEndpoint>,
+    /// The key session cookies are signed with
+    pub secret: Vec<u8>,
+    /// How long a connection may stay silent before it is closed
+    pub read_timeout: Duration,
+    /// How long a persistent connection may wait for its next request, if
+    /// shorter than `read_timeout`
+    pub keepalive_timeout: Option<Duration>,
+    /// The number of requests served on a connection before it is closed, if limited
+    pub max_requests_per_conn: Option<usize>,
+    /// How long busy connections may take to finish once the server stops, if drained
+    pub shutdown_grace: Option<Duration>,
+    /// The number of connections served at once, if limited
+    pub max_connections: Option<usize>,
+    /// Whether client addresses are read from proxy headers
+    pub trust_proxy: bool,
+    /// The per-client rate limiter, if enabled
+    pub rate_limiter: Option<Arc<RateLimiter>>,
+    /// Whether requests with unsafe methods must carry the session's CSRF token
+    pub csrf: bool,
+    /// The file name served for requests to a directory path
+    pub index_file: String,
+    /// Whether `Server::serve_dir` lists directories that have no index file
+    pub listing: bool,
+    /// The page served for unmatched paths of a single-page app, if enabled
+    pub spa_fallback: Option<SpaFallback>,
+    /// The `Content-Type` given to responses with a body but no type of their own
+    pub default_content_type: String,
+    /// Where requests to protected routes without a session are redirected,
+    /// instead of getting `401 Unauthorized`
+    pub login_path: Option<String>,
+    /// Whether `TRACE` requests are routed instead of refused
+    pub allow_trace: bool,
+    /// The name of connection threads, numbered as in `rwf-worker-1`
+    pub thread_name: String,
+    /// Whether the server's own errors are JSON for clients preferring it
+    pub json_errors: bool,
+    /// Handlers running longer than this are logged, if set
+    pub slow_threshold: Option<Duration>,
+    /// The clock handler durations are measured with
+    pub clock: Arc<dyn Clock>,
+    /// Whether the raw bytes of each request are kept in `Request::raw`
+    pub capture_raw: bool,
+    /// The time-to-live of each cached route, keyed by path
+    pub cached_routes: HashMap<String, Duration>,
+    /// The responses stored for cached routes
+    pub response_cache: Arc<ResponseCache>,
+    /// The security headers added to every response, if enabled
+    pub secure_headers: Option<SecureHeaders>,
+    /// The directory `Request::render` reads templates from, if registered
+    pub templates: Option<Arc<Templates>>,
+    /// Callbacks run on every response before it is sent, in registration order
+    pub after_response: Vec<fn(&Request, &mut Response)>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            limits: Limits::default(),
+            health_check: Some(HealthCheck {
+                path: "/healthz".to_string(),
+                body: String::new(),
+            }),
+            metrics: None,
+            secret: random_secret(),
+            read_timeout: Duration::from_secs(5),
+            keepalive_timeout: None,
+            max_requests_per_conn: None,
+            shutdown_grace: None,
+            max_connections: None,
+            trust_proxy: false,
+            rate_limiter: None,
+            csrf: false,
+            index_file: "index.html".to_string(),
+            listing: false,
+            spa_fallback: None,
+            default_content_type: "text/html; charset=utf-8".to_string(),
+            json_errors: false,
+            thread_name: "rwf-worker".to_string(),
+            allow_trace: false,
+            login_path: None,
+            slow_threshold: None,
+            clock: Arc::new(SystemClock),
+            capture_raw: false,
+            cached_routes: HashMap::new(),
+            response_cache: Arc::new(ResponseCache::new()),
+            secure_headers: None,
+            templates: None,
+            after_response: Vec::new(),
+        }
+    }
+}
+
+/// Generates a random 256-bit key, used when no secret is configured.
+///
+/// # Panics
+///
+/// Panics if the operating system's random number generator is unavailable.
+fn random_secret() -> Vec<u8> {
+    let mut secret = vec![0u8; 32];
+    getrandom::fill(&mut secret).expect("failed to generate a session secret");
+    secret
+}
+
+/// A built-in endpoint answering `200 OK` without touching routes or sessions.
+#[derive(Debug, Clone)]
+pub struct HealthCheck {
+    /// The URL path the endpoint responds to
+    pub path: String,
+    /// The body sent with every response
+    pub body: String,
+}
+
+/// A built-in endpoint exposing request metrics in the Prometheus text format.
+#[derive(Debug, Clone)]
+pub struct MetricsEndpoint {
+    /// The URL path the endpoint responds to
+    pub path: String,
+    /// The collector recording every request
+    pub collector: Arc<Metrics>,
+}
+
+/// The page served in place of a `404 Not Found` for a single-page app.
+#[derive(Debug, Clone)]
+pub struct SpaFallback {
+    /// Path to the app's HTML entry point
+    pub index_path: String,
+    /// Path prefixes that keep answering `404 Not Found`, without a trailing `/`
+    pub exclude_prefixes: Vec<String>,
+}
+
+impl SpaFallback {
+    /// Checks whether an unmatched path is left to the app.
+    ///
+    /// Prefixes match whole segments, so `/api` covers `/api` and
+    /// `/api/users` but not `/apiary`.
+    pub fn covers(&self, path: &str) -> bool {
+        !self.exclude_prefixes.iter().any(|prefix| {
+            path.strip_prefix(prefix.as_str())
+                .is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+        })
+    }
+}
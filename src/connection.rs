@@ -6,20 +6,69 @@
 //!
 //! ## Submodules
 //!
+//! * `auth` - `Authorization` header parsing
+//! * `cache` - In-memory response cache
+//! * `clock` - Monotonic clock abstraction
+//! * `cookie` - `Set-Cookie` header values
+//! * `deferred` - Responses resolved later, for long polling
+//! * `headers` - Case-insensitive header multimap
 //! * `method` - HTTP method definitions
+//! * `metrics` - Request counts and latencies in the Prometheus format
+//! * `mime` - File extension to MIME type lookup
+//! * `rate_limit` - Per-client request rate limiting
 //! * `request` - Request parsing and handling
 //! * `response` - Response formatting and generation
+//! * `secure_headers` - Baseline security response headers
 //! * `session` - Session management
+//! * `sse` - Server-sent event streams
+//! * `template` - Runtime template rendering
+//! * `tracker` - Open connection tracking, for draining on shutdown
+//! * `version` - HTTP version definitions
 
+pub mod auth;
+pub mod cache;
+pub mod clock;
+pub mod cookie;
+pub mod deferred;
+pub mod headers;
 pub mod method;
+pub mod metrics;
+pub mod mime;
+pub mod rate_limit;
 pub mod request;
 pub mod response;
+pub mod secure_headers;
 pub mod session;
+pub mod sse;
+pub mod template;
+pub(crate) mod tracker;
+pub mod version;
 
-use crate::{contains_any, routing::Routes};
-use request::Request;
-use session::Sessions;
-use std::{cell::RefCell, io::Write, net::TcpStream, rc::Rc};
+use crate::{config::Config, routing::Routes};
+use cookie::Cookie;
+use method::Method;
+use request::{ParseError, Request};
+use response::Response;
+use session::{Session, Sessions};
+use std::{
+    any::Any,
+    fs,
+    io::{self, BufRead, BufReader, Read, Write},
+    net::{Shutdown, SocketAddr, TcpStream},
+    panic::{self, AssertUnwindSafe},
+    sync::{Arc, RwLock},
+    time::{Duration, Instant},
+};
+use tracker::Tracked;
+use version::Version;
+
+/// A bidirectional byte stream a request is read from and its response written to.
+///
+/// Implemented for every `Read + Write` type, so plain `TcpStream`s and TLS
+/// streams are handled the same way.
+pub trait Stream: Read + Write {}
+
+impl<T: Read + Write> Stream for T {}
 
 /// Handles incoming HTTP requests, manages sessions, and generates responses.
 ///
@@ -29,71 +78,880 @@ use std::{cell::RefCell, io::Write, net::TcpStream, rc::Rc};
 /// - Managing sessions
 /// - Routing requests to appropriate handlers
 /// - Generating and sending responses
+///
+/// Each connection gets its own `RequestHandler`, sharing the routes,
+/// sessions and configuration with the handlers of other connections.
 pub struct RequestHandler {
-    routes: Rc<RefCell<Routes>>,
-    sessions: Rc<RefCell<Sessions>>,
+    routes: Arc<RwLock<Routes>>,
+    sessions: Arc<Sessions>,
+    config: Arc<Config>,
+    tls: bool,
+    /// A handle to the connection's socket, to change its read timeout
+    /// while waiting for the next request and half-close it after a rejection
+    socket: Option<TcpStream>,
+    /// The connection's entry in the server's tracker, used to drain it on shutdown
+    tracked: Option<Tracked>,
+    /// The route serving the current request, labelling its metrics
+    route: Option<String>,
+    /// Whether that route is registered for the request's method, so an
+    /// extension method labels its metrics by name
+    method_routed: bool,
 }
 
 impl RequestHandler {
-    pub fn new(routes: Rc<RefCell<Routes>>, sessions: Rc<RefCell<Sessions>>) -> Self {
-        Self { routes, sessions }
+    pub fn new(routes: Arc<RwLock<Routes>>, sessions: Arc<Sessions>, config: Arc<Config>) -> Self {
+        Self {
+            routes,
+            sessions,
+            config,
+            tls: false,
+            socket: None,
+            tracked: None,
+            route: None,
+            method_routed: false,
+        }
+    }
+
+    /// Registers the handler's connection, so `Server::shutdown_grace` can
+    /// drain it.
+    pub(crate) fn set_tracked(&mut self, tracked: Tracked) {
+        self.tracked = Some(tracked);
+    }
+
+    /// Gives the handler a handle to the connection's socket, so idle
+    /// persistent connections are closed after `Server::keepalive_timeout`
+    /// and clients of rejected requests get the response before the close.
+    pub(crate) fn set_socket(&mut self, socket: TcpStream) {
+        self.socket = Some(socket);
+    }
+
+    /// Marks the connection as accepted over TLS, see `Request::is_secure`.
+    #[cfg(feature = "tls")]
+    pub(crate) fn set_tls(&mut self, tls: bool) {
+        self.tls = tls;
     }
 
-    /// Processes an incoming TCP stream and generates an appropriate response.
+    /// Processes an incoming stream and generates the appropriate responses.
+    ///
+    /// Requests are answered one after the other, including requests a client
+    /// pipelines without waiting for the previous response, until the
+    /// connection is to be closed: after a request asking for it with
+    /// `Connection: close`, an HTTP/1.0 request without `Connection:
+    /// keep-alive`, a response carrying `Connection: close`, the last request
+    /// allowed by `Server::max_requests_per_conn`, or once the client closes
+    /// the connection, stays idle longer than `Server::keepalive_timeout` or
+    /// the stream fails. Responses on a closing connection carry `Connection:
+    /// close`; kept-alive HTTP/1.0 connections get `Connection: keep-alive`.
     ///
-    /// This method:
-    /// 1. Parses the incoming HTTP request
-    /// 2. Logs the request details
-    /// 3. Attempts to find a matching route
-    /// 4. Manages session state
-    /// 5. Executes the route handler if found
-    /// 6. Generates and sends the response
+    /// For each request, this method:
+    /// 1. Parses the incoming HTTP request, answering `100 Continue` before
+    ///    reading the body if the client expects it. Requests that cannot be
+    ///    parsed are rejected and their connection closed: with `431 Request
+    ///    Header Fields Too Large` if the headers exceed the limits, `414 URI
+    ///    Too Long` if the request target does, `413
+    ///    Payload Too Large` (or `417 Expectation Failed` for an `Expect:
+    ///    100-continue` request) if the body does, `501 Not Implemented` if
+    ///    it has a `Transfer-Encoding`, or `400 Bad Request` if the request
+    ///    line is malformed, the `Content-Length` is not a number or its
+    ///    copies disagree, or the body is shorter than it. Empty lines before the request line are
+    ///    skipped, and a connection closed before a request line or in the
+    ///    middle of the headers is closed without a response
+    /// 2. Tags the request with an ID, reusing the client's `X-Request-Id` if it
+    ///    sent a valid one, and echoes it in the `X-Request-Id` response header
+    /// 3. Logs the request details. `HEAD` requests are sent back only the
+    ///    response headers
+    /// 4. Answers `OPTIONS *` with the supported methods in an `Allow`
+    ///    header, `TRACE` included when `Server::allow_trace` is enabled, and the health check and metrics endpoints directly, without
+    ///    creating a session. `CONNECT` requests get `501 Not Implemented`, and `TRACE`
+    ///    requests `405 Method Not Allowed` unless `Server::allow_trace` is
+    ///    enabled
+    /// 5. Rejects the request with `429 Too Many Requests` if its client exceeded
+    ///    the rate limit
+    /// 6. Attempts to find a matching route, serving the directory's index
+    ///    file for paths ending in `/` that have no route of their own. A
+    ///    path with routes for other methods only gets `405 Method Not
+    ///    Allowed` and an `Allow` header listing them; other unmatched paths
+    ///    get the single-page app's entry point if `Server::spa_fallback` is
+    ///    enabled, or `404 Not Found`. `HEAD` requests go to a route
+    ///    registered for `HEAD` if there is one, or else to the `GET` route,
+    ///    whose handler sees a `GET` request
+    /// 7. Serves `GET` requests to cached routes from the response cache,
+    ///    except for protected routes, which are never cached
+    /// 8. Manages session state: requests without a valid, correctly signed
+    ///    session cookie get a fresh session that is only stored, and its
+    ///    cookie only sent, if the handler adds data to it. Routes that never
+    ///    use the session, such as static files, skip this step and send no
+    ///    session cookie. Protected routes reject such requests instead, with
+    ///    `401 Unauthorized` or a redirect to `Server::login_path`
+    /// 9. Executes the route handler if found, caching its response for
+    ///    cached routes. A panicking handler is logged with the request's
+    ///    method and path and answered with `500 Internal Server Error`
+    /// 10. Adds the `Server::secure_headers` and runs the
+    ///     `Server::after_response` callbacks on the response, then sends it
     ///
     /// # Arguments
     ///
-    /// * `stream` - The TCP stream containing the incoming request
+    /// * `stream` - The stream containing the incoming request: a plain TCP
+    ///   connection, a TLS session or any other `Read + Write` type such as an
+    ///   in-memory `Cursor<Vec<u8>>`
+    /// * `remote_addr` - The client's address, taken from the TCP connection's
+    ///   `peer_addr()`, or `None` if it could not be determined
     ///
     /// # Side Effects
     ///
-    /// * Writes response data to the TCP stream
+    /// * Writes response data to the stream
     /// * Logs request information to stdout
     ///
-    /// # Panics
+    /// # Example
+    ///
+    /// A request sent with `Connection: close` on a persistent connection is
+    /// answered before the connection is closed, and so is the last request
+    /// of a client that half-closes the connection:
+    ///
+    /// ```rust
+    /// use rwf::Server;
+    /// use std::{io::{Read, Write}, net::{Shutdown, TcpStream}};
+    ///
+    /// let mut server = Server::build("127.0.0.1:0").unwrap();
+    /// server.route("/", |_req, _session| "hello".to_string());
+    /// server.route("/echo", |req, _session| req.body_str().unwrap_or_default().to_string());
+    /// # let addr = rwf::test_support::serve(server);
+    /// # let send = |request: &str| rwf::test_support::send(addr, request);
+    ///
+    /// let mut stream = TcpStream::connect(addr).unwrap();
+    /// stream.write_all(b"GET / HTTP/1.1\r\n\r\n").unwrap();
+    /// let mut first = Vec::new();
+    /// while !first.ends_with(b"hello") {
+    ///     let mut chunk = [0; 1024];
+    ///     let read = stream.read(&mut chunk).unwrap();
+    ///     first.extend_from_slice(&chunk[..read]);
+    /// }
+    /// assert!(!String::from_utf8(first).unwrap().contains("Connection: close"));
+    ///
+    /// // Reading to the end only returns once the server closed the connection
+    /// stream.write_all(b"GET / HTTP/1.1\r\nConnection: close\r\n\r\n").unwrap();
+    /// let mut second = String::new();
+    /// stream.read_to_string(&mut second).unwrap();
+    /// assert!(second.starts_with("HTTP/1.1 200 OK"));
+    /// assert!(second.contains("Connection: close"));
+    /// assert_eq!(second.matches("HTTP/1.1").count(), 1);
+    ///
+    /// let mut stream = TcpStream::connect(addr).unwrap();
+    /// stream.write_all(b"GET / HTTP/1.1\r\n\r\n").unwrap();
+    /// stream.shutdown(Shutdown::Write).unwrap();
+    /// let mut response = String::new();
+    /// stream.read_to_string(&mut response).unwrap();
+    /// assert!(response.starts_with("HTTP/1.1 200 OK") && response.ends_with("hello"));
+    ///
+    /// // Headers cut short by the close are not served as if complete
+    /// let mut stream = TcpStream::connect(addr).unwrap();
+    /// stream.write_all(b"GET / HTTP/1.1\r\nHost: x").unwrap();
+    /// stream.shutdown(Shutdown::Write).unwrap();
+    /// let mut response = String::new();
+    /// stream.read_to_string(&mut response).unwrap();
+    /// assert_eq!(response, "");
+    ///
+    /// // Pipelined requests sent in a single write are answered in order,
+    /// // without the body of one leaking into the next
+    /// let responses = send(
+    ///     "POST /echo HTTP/1.1\r\nContent-Length: 5\r\n\r\nfirst\
+    ///      POST /echo HTTP/1.1\r\nContent-Length: 6\r\nConnection: close\r\n\r\nsecond",
+    /// );
+    /// assert_eq!(responses.matches("HTTP/1.1 200 OK").count(), 2);
+    /// let (first, second) = responses.split_once("firstHTTP/1.1").unwrap();
+    /// assert!(first.starts_with("HTTP/1.1 200 OK") && second.ends_with("second"));
+    ///
+    /// // A client closing before the whole body arrived gets a clean error
+    /// let mut stream = TcpStream::connect(addr).unwrap();
+    /// stream
+    ///     .write_all(b"POST /echo HTTP/1.1\r\nContent-Length: 100\r\n\r\n0123456789")
+    ///     .unwrap();
+    /// stream.shutdown(Shutdown::Write).unwrap();
+    /// let mut response = String::new();
+    /// stream.read_to_string(&mut response).unwrap();
+    /// assert!(response.starts_with("HTTP/1.1 400 Bad Request"));
+    ///
+    /// // A body with an ambiguous end is refused and the connection closed,
+    /// // so a request smuggled inside it is never served
+    /// let smuggle = |framing: &str| {
+    ///     send(&format!("POST /echo HTTP/1.1\r\n{framing}\r\n\r\nGET / HTTP/1.1\r\n\r\n"))
+    /// };
+    /// let response = smuggle("Content-Length: 5x");
+    /// assert!(response.starts_with("HTTP/1.1 400 Bad Request") && !response.contains("hello"));
+    /// assert_eq!(response.matches("HTTP/1.1").count(), 1);
+    /// let response = smuggle("Content-Length: 0\r\nContent-Length: 18");
+    /// assert!(response.starts_with("HTTP/1.1 400") && !response.contains("hello"));
+    /// let response = smuggle("Transfer-Encoding: chunked");
+    /// assert!(response.starts_with("HTTP/1.1 501 Not Implemented") && !response.contains("hello"));
+    ///
+    /// // Malformed request lines and headers are answered, not dropped
+    /// assert!(send("GET /\r\nHost: x\r\n\r\n").starts_with("HTTP/1.1 400 Bad Request"));
+    /// let mut stream = TcpStream::connect(addr).unwrap();
+    /// stream.write_all(b"GET / HTTP/1.1\r\nX-Name: \xff\r\n\r\n").unwrap();
+    /// let mut response = String::new();
+    /// stream.read_to_string(&mut response).unwrap();
+    /// assert!(response.starts_with("HTTP/1.1 400 Bad Request"));
+    /// ```
+    pub fn resolve(&mut self, stream: &mut dyn Stream, remote_addr: Option<SocketAddr>) {
+        // Bytes read ahead of one request belong to the next one, so the
+        // buffer lives as long as the connection
+        let mut stream = BufferedStream(BufReader::new(stream));
+        let mut served = 0;
+        loop {
+            if !self.await_next(&mut stream, served == 0) {
+                break;
+            }
+            served += 1;
+            if let Some(tracked) = &self.tracked {
+                tracked.set_idle(false);
+            }
+            let last = self
+                .config
+                .max_requests_per_conn
+                .is_some_and(|max| served >= max);
+            if !self.serve_one(&mut stream, remote_addr, last) || last {
+                break;
+            }
+            if self
+                .tracked
+                .as_ref()
+                .is_some_and(|tracked| !tracked.set_idle(true))
+            {
+                break;
+            }
+        }
+    }
+
+    /// Waits for the first bytes of the next request, while the connection
+    /// counts as idle.
+    ///
+    /// With `Server::keepalive_timeout` set, the socket's read timeout is
+    /// shortened to it while waiting for any request but the `first`, then
+    /// restored for the rest of the request. Returns `false` if the client
+    /// stayed idle too long.
+    fn await_next(&self, stream: &mut BufferedStream, first: bool) -> bool {
+        // A pipelined request is already waiting
+        if !stream.0.buffer().is_empty() {
+            return true;
+        }
+        let keepalive = self.config.keepalive_timeout.filter(|_| !first);
+        match (keepalive, &self.socket) {
+            (Some(timeout), Some(socket)) => {
+                let _ = socket.set_read_timeout(Some(timeout));
+                let ready = stream.fill_buf().is_ok();
+                let _ = socket.set_read_timeout(Some(self.config.read_timeout));
+                ready
+            }
+            // Tracked connections only count as busy once a request arrives
+            _ if self.tracked.is_some() => stream.fill_buf().is_ok(),
+            _ => true,
+        }
+    }
+
+    /// Reads a single request from the stream and answers it, closing the
+    /// connection afterwards if it is the `last` one allowed.
+    ///
+    /// Returns whether the connection stays open for another request.
+    fn serve_one(
+        &mut self,
+        stream: &mut BufferedStream,
+        remote_addr: Option<SocketAddr>,
+        last: bool,
+    ) -> bool {
+        let (limits, secret) = (&self.config.limits, &self.config.secret);
+        let parsed = if self.config.capture_raw {
+            let mut recorder = Recorder {
+                inner: &mut *stream,
+                data: Vec::new(),
+            };
+            Request::parse_interactive(&mut recorder, limits, secret).map(|mut request| {
+                request.raw = Some(recorder.data);
+                request
+            })
+        } else {
+            Request::parse_interactive(&mut *stream, limits, secret)
+        };
+        let mut request = match parsed {
+            Ok(request) => request,
+            // The client closed or half-closed the connection between requests
+            Err(ParseError::ConnectionClosed | ParseError::Io(_)) => return false,
+            Err(error) => {
+                println!("Rejected request: {error}, closing connection");
+                // The client may already be gone, so a failed write is not an error
+                let _ = send(stream, &rejection(&error), false);
+                if let Some(socket) = &self.socket {
+                    drain(stream, socket);
+                }
+                return false;
+            }
+        };
+        request.remote_addr = remote_addr;
+        request.deadline = Some(Instant::now() + self.config.read_timeout);
+        request.trust_proxy = self.config.trust_proxy;
+        request.tls = self.tls;
+        request.templates.clone_from(&self.config.templates);
+
+        println!(
+            "[{}] {:?} request on '{}'.",
+            request.id, request.method, request.resource
+        );
+
+        let head_only = request.method == Method::HEAD;
+
+        let request_id = request.id.clone();
+        let (version, wants_keep_alive) = (request.version, request.keep_alive());
+        let (method, path) = (request.method.clone(), request.resource.clone());
+        let json_error = self.wants_json_error(&request);
+        let hooked = (!self.config.after_response.is_empty()).then(|| request.without_body());
+        self.route = None;
+        self.method_routed = false;
+        let start = self
+            .config
+            .metrics
+            .is_some()
+            .then(|| self.config.clock.now());
+        // A panicking handler fails its own request only: the connection and
+        // the server keep going
+        let response = match panic::catch_unwind(AssertUnwindSafe(|| self.respond(request))) {
+            Ok(response) => response,
+            Err(payload) => {
+                println!(
+                    "[{request_id}] Handler panicked on {method} '{path}': {}",
+                    panic_message(&*payload)
+                );
+                if json_error {
+                    Response::json_error(500, "Internal server error")
+                } else {
+                    Response::new(500, "Internal server error")
+                }
+            }
+        };
+        if let (Some(metrics), Some(start)) = (&self.config.metrics, start) {
+            let elapsed = self.config.clock.now().saturating_duration_since(start);
+            let route = self.route.as_deref().unwrap_or("unmatched");
+            // Clients may send any method token, so extension methods no
+            // route is registered for share one label
+            let method = match method {
+                Method::Other(_) if !self.method_routed => Method::Other("other".to_string()),
+                method => method,
+            };
+            metrics
+                .collector
+                .record(method, route, response.status, elapsed);
+        }
+        // Deferred responses are waited for here, once the session is released
+        let mut response = response.wait().with_header("X-Request-Id", &request_id);
+        if !response.body.is_empty() && response.header("content-type").is_none() {
+            response = response.with_header("Content-Type", &self.config.default_content_type);
+        }
+        if let Some(secure_headers) = &self.config.secure_headers {
+            secure_headers.apply(&mut response);
+        }
+        if let Some(request) = hooked {
+            for hook in &self.config.after_response {
+                hook(&request, &mut response);
+            }
+        }
+
+        let closes = response
+            .header("connection")
+            .is_some_and(|value| value.eq_ignore_ascii_case("close"));
+        // A streamed body ends with the connection, so it cannot be reused,
+        // and a server shutting down answers requests already started, then closes
+        let draining = self
+            .tracked
+            .as_ref()
+            .is_some_and(|tracked| tracked.is_draining());
+        let keep_alive =
+            wants_keep_alive && !closes && !response.is_streaming() && !last && !draining;
+        if !keep_alive && !closes {
+            response = response.with_header("Connection", "close");
+        } else if keep_alive && version == Version::Http10 {
+            response = response.with_header("Connection", "keep-alive");
+        }
+
+        send(stream, &response, head_only).is_ok() && keep_alive
+    }
+
+    /// Produces the response for a parsed request.
+    ///
+    /// Covers the health check and metrics endpoints, rate limiting, response
+    /// caching, session management, CSRF checks and dispatching to the
+    /// matching route, falling back to `404 Not Found`.
     ///
-    /// Panics if writing to the TCP stream fails
-    pub fn resolve(&mut self, mut stream: TcpStream) {
-        let request = Request::parse(&stream);
+    /// A request for a directory path ending in `/` with no route of its own
+    /// is served by the route of that directory's index file, if registered,
+    /// with `request.resource` rewritten to the index file's path.
+    fn respond(&mut self, mut request: Request) -> Response {
+        let allow = || {
+            let mut methods: Vec<String> = Method::ALL.iter().map(Method::to_string).collect();
+            if self.config.allow_trace {
+                methods.push(Method::TRACE.to_string());
+            }
+            methods.join(", ")
+        };
+        if request.is_asterisk_form() {
+            return Response::status(200).with_header("Allow", &allow());
+        }
+        match request.method {
+            Method::CONNECT => {
+                println!("Rejected request: CONNECT is not supported");
+                return self.error(&request, 501, "Not implemented");
+            }
+            Method::TRACE if !self.config.allow_trace => {
+                println!("Rejected request: TRACE is disabled");
+                return self
+                    .error(&request, 405, "Method not allowed")
+                    .with_header("Allow", &allow());
+            }
+            _ => {}
+        }
 
-        println!("{:?} request on '{}'.", request.method, request.resource);
+        if let Some(health) = &self.config.health_check {
+            if request.resource == health.path {
+                self.route = Some(health.path.clone());
+                return Response::new(200, health.body.as_str());
+            }
+        }
+        if let Some(metrics) = &self.config.metrics {
+            if request.resource == metrics.path {
+                self.route = Some(metrics.path.clone());
+                return Response::new(200, metrics.collector.render())
+                    .with_header("Content-Type", "text/plain; version=0.0.4");
+            }
+        }
+
+        if let (Some(limiter), Some(ip)) = (&self.config.rate_limiter, request.client_ip()) {
+            if let Err(retry_after) = limiter.check(ip) {
+                println!("Rate limit exceeded by {ip}");
+                let seconds = retry_after.as_secs_f64().ceil().max(1.0);
+                return Response::new(429, "Too many requests")
+                    .with_header("Retry-After", &seconds.to_string());
+            }
+        }
 
-        let mut routes = RefCell::borrow_mut(&self.routes);
-        let mut sessions = RefCell::borrow_mut(&self.sessions);
+        let all_routes = self.routes.read().unwrap_or_else(|e| e.into_inner());
+        let routes = all_routes.for_host(request.host.as_deref());
+        let sessions = &self.sessions;
 
-        if let Some(route) = routes.get_route(&request.resource) {
-            let f = route.get_fn();
+        // Hosts with routes of their own get their own cache entries
+        let host = request
+            .host
+            .as_deref()
+            .filter(|_| !std::ptr::eq(routes, &*all_routes));
+        let cache = matches!(request.method, Method::GET | Method::HEAD)
+            .then(|| self.config.cached_routes.get(&request.resource))
+            .flatten()
+            .map(|ttl| (cache_key(&request, host), *ttl));
 
-            let session_id = request
+        if request.resource.ends_with('/') && routes.get_route(&request.resource).is_none() {
+            let index = format!("{}{}", request.resource, self.config.index_file);
+            if routes.get_route(&index).is_some() {
+                request.resource = index;
+            }
+        }
+
+        let found = match request.method {
+            // Routes registered for HEAD answer it first, then the GET route
+            Method::HEAD => routes
+                .match_route(&request.resource, Method::HEAD)
+                .filter(|found| found.route.registered_method() == Some(&Method::HEAD))
+                .or_else(|| routes.match_route(&request.resource, Method::GET)),
+            _ => routes.match_route(&request.resource, request.method.clone()),
+        };
+        let Some(found) = found else {
+            let allowed = routes.allowed_methods(&request.resource);
+            if !allowed.is_empty() {
+                println!("Method {} not allowed, returned error", request.method);
+                let allowed: Vec<String> = allowed.iter().map(Method::to_string).collect();
+                return self
+                    .error(&request, 405, "Method not allowed")
+                    .with_header("Allow", &allowed.join(", "));
+            }
+            if let Some(response) = self.spa_fallback(&request) {
+                return response;
+            }
+            println!("No resource found, returned error");
+            if self.wants_json_error(&request) {
+                return Response::json_error(404, "Not found");
+            }
+            return response::error404();
+        };
+        request.params = found.params;
+        request.matched_route = Some(found.route.path().to_string());
+        self.route.clone_from(&request.matched_route);
+        self.method_routed = found.route.registered_method().is_some();
+        let handler = found.route.get_fn();
+
+        // Looked up once the route is known, so metrics count cache hits
+        // under it. A protected response belongs to one session, so it is
+        // never shared with other clients through the cache, and a HEAD
+        // route's response is no stand-in for the GET one
+        let head_route = found.route.registered_method() == Some(&Method::HEAD);
+        let cache = cache.filter(|_| !found.route.requires_session() && !head_route);
+        if let Some((key, _)) = &cache {
+            if let Some(response) = self.config.response_cache.get(key) {
+                return response;
+            }
+        }
+
+        let (method, path) = (request.method.clone(), request.resource.clone());
+        if request.method == Method::HEAD && !head_route {
+            request.method = Method::GET;
+        }
+        let (response, cookie) = if !found.route.uses_session() {
+            // Static content never needs a session, so none is looked up or
+            // created and no cookie keeps the response from being cached
+            let response = self.timed(method, &path, || handler(request, &mut Session::new()));
+            (response, None)
+        } else {
+            let loaded = request
                 .session
-                .filter(|id| sessions.contains(id))
-                .unwrap_or_else(|| sessions.add());
+                .clone()
+                .and_then(|id| Some((sessions.load(&id)?, id)));
+            if found.route.requires_session() && loaded.is_none() {
+                println!("Rejected request: no valid session");
+                return match &self.config.login_path {
+                    Some(login) => Response::redirect(login),
+                    None => self.error(&request, 401, "Unauthorized"),
+                };
+            }
+            if self.config.csrf
+                && !matches!(request.method, Method::GET | Method::HEAD | Method::OPTIONS)
+            {
+                let session = loaded.as_ref().map(|(session, _)| session);
+                if !has_csrf_token(&request, session) {
+                    if let Some((session, session_id)) = loaded {
+                        sessions.save(&session_id, session);
+                    }
+                    println!("Rejected request: missing or invalid CSRF token");
+                    return Response::new(403, "Invalid CSRF token");
+                }
+            }
 
-            let session = sessions.get(session_id);
+            match loaded {
+                Some((mut session, session_id)) => {
+                    let result = panic::catch_unwind(AssertUnwindSafe(|| {
+                        self.timed(method, &path, || handler(request, &mut session))
+                    }));
+                    let response = match result {
+                        Ok(response) => response,
+                        Err(payload) => {
+                            // Let other requests use the session instead of waiting forever
+                            sessions.release(&session_id);
+                            panic::resume_unwind(payload)
+                        }
+                    };
+                    if session.is_destroyed() {
+                        sessions.remove(&session_id);
+                        sessions.release(&session_id);
+                        (response, Some(expired_session_cookie()))
+                    } else {
+                        sessions.save(&session_id, session);
+                        (
+                            response,
+                            Some(session_cookie(&session_id, &self.config.secret)),
+                        )
+                    }
+                }
+                None => {
+                    let mut session = Session::new();
+                    let response = self.timed(method, &path, || handler(request, &mut session));
+                    let cookie = (!session.is_empty() && !session.is_destroyed())
+                        .then(|| session_cookie(&sessions.insert(session), &self.config.secret));
+                    (response, cookie)
+                }
+            }
+        };
+
+        if let Some((key, ttl)) = &cache {
+            if is_cacheable(&response) {
+                self.config
+                    .response_cache
+                    .store(key, response.clone(), *ttl);
+            }
+        }
+
+        match cookie {
+            Some(cookie) => response.with_cookie(cookie),
+            None => response,
+        }
+    }
+
+    /// Builds one of the server's own error responses, with a JSON body if
+    /// the client prefers it and `Server::json_errors` is enabled.
+    fn error(&self, request: &Request, status: u16, message: &str) -> Response {
+        if self.wants_json_error(request) {
+            Response::json_error(status, message)
+        } else {
+            Response::new(status, message)
+        }
+    }
 
-            let response = f(request, session);
+    /// Checks whether the server's errors are JSON for this request: the
+    /// option is enabled and the `Accept` header lists a JSON type first.
+    fn wants_json_error(&self, request: &Request) -> bool {
+        self.config.json_errors
+            && request.accepted_types().first().is_some_and(|preferred| {
+                preferred == "application/json" || preferred.ends_with("+json")
+            })
+    }
 
-            //response::redirect and response::error404 functions return
-            //the full HTTP response
-            if contains_any!(&response, "302", "404") {
-                stream.write_all(&response.as_bytes()).unwrap();
-                return;
+    /// Runs a handler, logging a warning if it takes longer than the
+    /// threshold set with `Server::slow_threshold`.
+    fn timed<R>(&self, method: Method, path: &str, handler: impl FnOnce() -> R) -> R {
+        let Some(threshold) = self.config.slow_threshold else {
+            return handler();
+        };
+        let start = self.config.clock.now();
+        let result = handler();
+        let elapsed = self.config.clock.now().saturating_duration_since(start);
+        if elapsed > threshold {
+            println!("Slow handler: {method} {path} took {elapsed:?} (threshold: {threshold:?})");
+        }
+        result
+    }
+
+    /// Serves the single-page app's entry point for a `GET` or `HEAD` request no route
+    /// matched, if the fallback is enabled and covers its path.
+    fn spa_fallback(&self, request: &Request) -> Option<Response> {
+        let fallback = self.config.spa_fallback.as_ref()?;
+        if !matches!(request.method, Method::GET | Method::HEAD)
+            || !fallback.covers(&request.resource)
+        {
+            return None;
+        }
+        match fs::read(&fallback.index_path) {
+            Ok(body) => Some(
+                Response::new(200, body).with_header("Content-Type", "text/html; charset=utf-8"),
+            ),
+            Err(e) => {
+                println!("Failed to read '{}': {e}", fallback.index_path);
+                None
             }
+        }
+    }
+}
 
-            let fmt_response = response::format_content(response.len(), &response, session_id);
-            stream.write_all(&fmt_response).unwrap();
-            return;
+/// The most bytes read from a rejected connection before it is closed
+/// anyway.
+const DRAIN_LIMIT: usize = 1024 * 1024;
+
+/// The longest a rejected connection is read from before it is closed
+/// anyway.
+const DRAIN_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Builds the key a request's response is cached under: the virtual host
+/// whose routes serve it, if any, its path, then its query parameters,
+/// sorted so their order does not matter.
+///
+/// Names and values are percent-encoded again, so a decoded `&` or `=` in
+/// a value cannot make two different queries share a key.
+fn cache_key(request: &Request, host: Option<&str>) -> String {
+    let mut params: Vec<_> = request.query.iter().collect();
+    params.sort();
+    let query: Vec<_> = params
+        .iter()
+        .map(|(k, v)| format!("{}={}", crate::percent_encode(k), crate::percent_encode(v)))
+        .collect();
+    format!(
+        "{}{}?{}",
+        host.unwrap_or_default(),
+        request.resource,
+        query.join("&")
+    )
+}
+
+/// Checks whether a response may be cached: it must be a `200 OK` response
+/// without `Cache-Control: no-store`.
+fn is_cacheable(response: &Response) -> bool {
+    let no_store = response.headers.iter().any(|(name, value)| {
+        name.eq_ignore_ascii_case("cache-control")
+            && value
+                .split(',')
+                .any(|directive| directive.trim().eq_ignore_ascii_case("no-store"))
+    });
+    response.status == 200 && !no_store && !response.is_streaming()
+}
+
+/// A connection whose reads are buffered for as long as it stays open.
+///
+/// Writes go straight to the underlying stream.
+struct BufferedStream<'a>(BufReader<&'a mut dyn Stream>);
+
+impl Read for BufferedStream<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.0.read(buf)
+    }
+}
+
+impl BufRead for BufferedStream<'_> {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        self.0.fill_buf()
+    }
+
+    fn consume(&mut self, amt: usize) {
+        self.0.consume(amt);
+    }
+}
+
+impl Write for BufferedStream<'_> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.get_mut().write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.0.get_mut().flush()
+    }
+}
+
+/// A stream wrapper keeping a copy of every byte read through it.
+///
+/// Used to fill `Request::raw` when the server captures raw requests. Only
+/// consumed bytes are recorded, so data buffered for a following request is
+/// left out.
+struct Recorder<'a, 'b> {
+    /// The wrapped stream
+    inner: &'a mut BufferedStream<'b>,
+    /// The bytes read so far
+    data: Vec<u8>,
+}
+
+impl Read for Recorder<'_, '_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let read = self.inner.read(buf)?;
+        self.data.extend_from_slice(&buf[..read]);
+        Ok(read)
+    }
+}
+
+impl BufRead for Recorder<'_, '_> {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        self.inner.fill_buf()
+    }
+
+    fn consume(&mut self, amt: usize) {
+        if let Ok(buffered) = self.inner.fill_buf() {
+            self.data
+                .extend_from_slice(&buffered[..amt.min(buffered.len())]);
         }
+        self.inner.consume(amt);
+    }
+}
+
+impl Write for Recorder<'_, '_> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.inner.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
 
-        println!("No resource found, returned error");
-        stream.write_all(response::error404().as_bytes()).unwrap()
+/// Extracts the message of a panic, as passed to `panic!`.
+fn panic_message(payload: &(dyn Any + Send)) -> &str {
+    payload
+        .downcast_ref::<&str>()
+        .copied()
+        .or_else(|| payload.downcast_ref::<String>().map(String::as_str))
+        .unwrap_or("unknown panic")
+}
+
+/// Builds the response rejecting a request that could not be parsed.
+///
+/// The connection is closed afterwards, since any unread part of the
+/// request is left on it.
+fn rejection(error: &ParseError) -> Response {
+    let (status, body) = match error {
+        ParseError::HeadersTooLarge => (431, "Request header fields too large"),
+        ParseError::MalformedRequestLine
+        | ParseError::MalformedHeader
+        | ParseError::ConnectionClosed
+        | ParseError::InvalidPath
+        | ParseError::TruncatedBody
+        | ParseError::InvalidContentLength
+        | ParseError::InvalidEncoding
+        | ParseError::Io(_) => (400, "Bad request"),
+        ParseError::BodyTooLarge => (413, "Payload too large"),
+        ParseError::UriTooLong => (414, "URI too long"),
+        ParseError::UnsupportedTransferEncoding => (501, "Not implemented"),
+        ParseError::ExpectationFailed => (417, "Expectation failed"),
+    };
+    Response::new(status, body).with_header("Connection", "close")
+}
+
+/// Checks whether a request carries its session's CSRF token, in the
+/// `X-CSRF-Token` header or the `csrf_token` form field.
+fn has_csrf_token(request: &Request, session: Option<&Session>) -> bool {
+    let Some(session) = session else {
+        return false;
+    };
+    match request.header("x-csrf-token") {
+        Some(token) => session.verify_csrf(token),
+        None => request
+            .form()
+            .and_then(|mut form| form.remove("csrf_token"))
+            .is_some_and(|token| session.verify_csrf(&token)),
+    }
+}
+
+/// Builds the cookie carrying a signed session ID.
+fn session_cookie(session_id: &str, secret: &[u8]) -> Cookie {
+    Cookie::new("session_id", &session::sign(session_id, secret)).http_only(true)
+}
+
+/// Builds the cookie telling the client to forget a destroyed session.
+fn expired_session_cookie() -> Cookie {
+    Cookie::new("session_id", "")
+        .max_age(Duration::ZERO)
+        .http_only(true)
+}
+
+/// Writes a response to the stream and flushes it.
+///
+/// When `head_only` is set, only the header section is written, as required
+/// for `HEAD` requests. Otherwise the events of a streaming response follow
+/// the header section.
+fn send(stream: &mut dyn Stream, response: &Response, head_only: bool) -> io::Result<()> {
+    let bytes = if head_only {
+        response.to_head_bytes()
+    } else {
+        response.to_bytes()
+    };
+    stream.write_all(&bytes)?;
+    stream.flush()?;
+    if !head_only {
+        response.stream_to(stream)?;
+    }
+    Ok(())
+}
+
+/// Half-closes a connection that was sent its last response, then reads and
+/// discards what the client still sends, as unread data would reset the
+/// connection before the client gets the response.
+///
+/// Stops after [`DRAIN_LIMIT`] bytes or [`DRAIN_TIMEOUT`], whichever comes
+/// first, so a client that keeps sending cannot hold the thread.
+///
+/// # Arguments
+///
+/// * `stream` - The connection to read from
+/// * `socket` - The socket underneath the connection
+pub(crate) fn drain(stream: &mut (impl Read + ?Sized), socket: &TcpStream) {
+    let _ = socket.shutdown(Shutdown::Write);
+    let deadline = Instant::now() + DRAIN_TIMEOUT;
+    let mut buffer = [0; 8192];
+    let mut remaining = DRAIN_LIMIT;
+    while remaining > 0 {
+        let left = deadline.saturating_duration_since(Instant::now());
+        if left.is_zero() || socket.set_read_timeout(Some(left)).is_err() {
+            return;
+        }
+        let len = remaining.min(buffer.len());
+        match stream.read(&mut buffer[..len]) {
+            Ok(0) | Err(_) => return,
+            Ok(read) => remaining -= read,
+        }
     }
 }
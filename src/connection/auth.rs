@@ -0,0 +1,106 @@
+//! # Auth Module
+//!
+//! Parses the credentials sent in an `Authorization` header.
+//!
+//! The `Basic` scheme carries a base64 encoded `user:password` pair, which is
+//! decoded here; `Bearer` carries an opaque token. Any other scheme is kept
+//! as sent, so handlers can still interpret it themselves.
+//!
+//! ## Example
+//!
+//! ```rust
+//! use rwf::connection::auth::Auth;
+//!
+//! assert_eq!(
+//!     Auth::parse("Basic YWxhZGRpbjpvcGVuc2VzYW1l"),
+//!     Some(Auth::Basic {
+//!         user: "aladdin".to_string(),
+//!         pass: "opensesame".to_string(),
+//!     })
+//! );
+//! assert_eq!(Auth::parse("Bearer abc.def"), Some(Auth::Bearer("abc.def".to_string())));
+//! assert_eq!(Auth::parse("Digest realm=\"x\""), Some(Auth::Other("Digest realm=\"x\"".to_string())));
+//!
+//! // Malformed credentials are rejected
+//! assert_eq!(Auth::parse("Basic not-base64!"), None);
+//! assert_eq!(Auth::parse("Bearer"), None);
+//! ```
+
+/// The credentials of an `Authorization` header.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Auth {
+    /// `Basic` credentials, decoded from base64
+    Basic {
+        /// The user name, everything before the first `:`
+        user: String,
+        /// The password, everything after the first `:`
+        pass: String,
+    },
+    /// A `Bearer` token
+    Bearer(String),
+    /// Any other scheme, holding the whole header value
+    Other(String),
+}
+
+impl Auth {
+    /// Parses the value of an `Authorization` header.
+    ///
+    /// The scheme is matched case-insensitively.
+    ///
+    /// # Arguments
+    ///
+    /// * `value` - The header value, e.g. `Bearer abc.def`
+    ///
+    /// # Returns
+    ///
+    /// * `Option<Auth>` - The parsed credentials, or `None` if the header is
+    ///   empty or a `Basic`/`Bearer` header is malformed
+    pub fn parse(value: &str) -> Option<Self> {
+        let value = value.trim();
+        let (scheme, credentials) = value.split_once(' ').unwrap_or((value, ""));
+        let credentials = credentials.trim();
+
+        if scheme.eq_ignore_ascii_case("basic") {
+            let decoded = String::from_utf8(base64_decode(credentials)?).ok()?;
+            let (user, pass) = decoded.split_once(':')?;
+            Some(Self::Basic {
+                user: user.to_string(),
+                pass: pass.to_string(),
+            })
+        } else if scheme.eq_ignore_ascii_case("bearer") {
+            (!credentials.is_empty()).then(|| Self::Bearer(credentials.to_string()))
+        } else if scheme.is_empty() {
+            None
+        } else {
+            Some(Self::Other(value.to_string()))
+        }
+    }
+}
+
+/// Decodes standard base64, with or without `=` padding.
+fn base64_decode(input: &str) -> Option<Vec<u8>> {
+    let input = input.trim_end_matches('=');
+    let mut bytes = Vec::with_capacity(input.len() * 3 / 4);
+    let mut buffer = 0u32;
+    let mut bits = 0;
+
+    for c in input.bytes() {
+        let sextet = match c {
+            b'A'..=b'Z' => c - b'A',
+            b'a'..=b'z' => c - b'a' + 26,
+            b'0'..=b'9' => c - b'0' + 52,
+            b'+' => 62,
+            b'/' => 63,
+            _ => return None,
+        };
+        buffer = (buffer << 6) | u32::from(sextet);
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            bytes.push((buffer >> bits) as u8);
+        }
+    }
+
+    // A single leftover character cannot encode a whole byte
+    (bits < 6).then_some(bytes)
+}
@@ -0,0 +1,139 @@
+//! # Cache Module
+//!
+//! Provides a thread-safe in-memory cache of rendered responses.
+//!
+//! Each entry is stored with a time-to-live and is only returned until it
+//! expires. The number of entries is capped, so clients requesting endless
+//! distinct keys cannot grow the cache without bound.
+//!
+//! ## Example
+//!
+//! ```rust
+//! use rwf::connection::{cache::ResponseCache, response::Response};
+//! use std::time::Duration;
+//!
+//! let cache = ResponseCache::new();
+//! cache.store("/report", Response::new(200, "expensive result"), Duration::from_secs(60));
+//!
+//! let hit = cache.get("/report").unwrap();
+//! assert_eq!(hit.body, b"expensive result");
+//!
+//! // An expired entry is gone
+//! cache.store("/report", Response::new(200, "expensive result"), Duration::ZERO);
+//! assert!(cache.get("/report").is_none());
+//! ```
+//!
+//! Once full, expired entries are swept out to make room, then the entry
+//! closest to expiring:
+//!
+//! ```rust
+//! use rwf::connection::{cache::ResponseCache, response::Response};
+//! use std::time::Duration;
+//!
+//! let cache = ResponseCache::with_capacity(2);
+//! cache.store("/a", Response::new(200, "a"), Duration::from_secs(10));
+//! cache.store("/b", Response::new(200, "b"), Duration::from_secs(60));
+//! cache.store("/c", Response::new(200, "c"), Duration::from_secs(60));
+//!
+//! assert_eq!(cache.len(), 2);
+//! assert!(cache.get("/a").is_none());
+//! assert!(cache.get("/b").is_some() && cache.get("/c").is_some());
+//! ```
+
+use super::response::Response;
+use std::{
+    collections::HashMap,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+/// The number of entries a cache created with [`ResponseCache::new`] holds.
+pub const DEFAULT_CAPACITY: usize = 1024;
+
+/// A thread-safe map of responses keyed by request path.
+#[derive(Debug)]
+pub struct ResponseCache {
+    /// Each cached response and the time it expires.
+    entries: Mutex<HashMap<String, (Response, Instant)>>,
+    /// The most entries held at once
+    capacity: usize,
+}
+
+impl Default for ResponseCache {
+    fn default() -> Self {
+        Self::with_capacity(DEFAULT_CAPACITY)
+    }
+}
+
+impl ResponseCache {
+    /// Creates an empty cache holding up to [`DEFAULT_CAPACITY`] entries.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates an empty cache holding up to `capacity` entries.
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            entries: Mutex::new(HashMap::new()),
+            capacity,
+        }
+    }
+
+    /// Returns the response cached under `key`, if it has not expired.
+    ///
+    /// Expired entries are removed.
+    ///
+    /// # Arguments
+    ///
+    /// * `key` - The cache key, usually the request path
+    pub fn get(&self, key: &str) -> Option<Response> {
+        let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        let (response, expires) = entries.get(key)?;
+        if Instant::now() < *expires {
+            return Some(response.clone());
+        }
+        entries.remove(key);
+        None
+    }
+
+    /// Stores a response under `key` for `ttl`, replacing any previous entry.
+    ///
+    /// When the cache is full, expired entries are removed first, then the
+    /// entry closest to expiring.
+    ///
+    /// # Arguments
+    ///
+    /// * `key` - The cache key, usually the request path
+    /// * `response` - The response to cache
+    /// * `ttl` - How long the entry stays valid
+    pub fn store(&self, key: &str, response: Response, ttl: Duration) {
+        let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        let now = Instant::now();
+        if self.capacity == 0 {
+            return;
+        }
+        if !entries.contains_key(key) && entries.len() >= self.capacity {
+            entries.retain(|_, (_, expires)| now < *expires);
+            if entries.len() >= self.capacity {
+                let soonest = entries
+                    .iter()
+                    .min_by_key(|(_, (_, expires))| *expires)
+                    .map(|(key, _)| key.clone());
+                if let Some(soonest) = soonest {
+                    entries.remove(&soonest);
+                }
+            }
+        }
+        entries.insert(key.to_string(), (response, now + ttl));
+    }
+
+    /// Returns the number of entries, including expired ones not yet removed.
+    pub fn len(&self) -> usize {
+        self.entries.lock().unwrap_or_else(|e| e.into_inner()).len()
+    }
+
+    /// Returns whether the cache holds no entries.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
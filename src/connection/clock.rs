@@ -0,0 +1,35 @@
+//! # Clock Module
+//!
+//! Abstracts the monotonic clock handler durations are measured with.
+//!
+//! The server reads the time through a [`Clock`], which is the system clock
+//! unless another one is set with `Server::clock`. Replacing it lets tests
+//! make a handler appear slow without sleeping.
+//!
+//! ## Example
+//!
+//! ```rust
+//! use rwf::connection::clock::{Clock, SystemClock};
+//!
+//! let clock = SystemClock;
+//! let start = clock.now();
+//! assert!(clock.now() >= start);
+//! ```
+
+use std::{fmt, time::Instant};
+
+/// A source of monotonic time.
+pub trait Clock: Send + Sync + fmt::Debug {
+    /// Returns the current time.
+    fn now(&self) -> Instant;
+}
+
+/// The operating system's monotonic clock, used by default.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+}
@@ -0,0 +1,150 @@
+//! # Cookie Module
+//!
+//! Builds the values of `Set-Cookie` response headers.
+//!
+//! A `Cookie` starts from a name and a value, and attributes are added with
+//! builder methods. Its `Display` implementation produces the header value,
+//! and [`Response::with_cookie`](super::response::Response::with_cookie)
+//! attaches it to a response. Every cookie becomes its own `Set-Cookie`
+//! header, so handler cookies and the session cookie are sent side by side.
+//!
+//! ## Example
+//!
+//! ```rust
+//! use rwf::connection::cookie::{Cookie, SameSite};
+//! use std::time::Duration;
+//!
+//! let cookie = Cookie::new("theme", "dark")
+//!     .path("/")
+//!     .max_age(Duration::from_secs(3600))
+//!     .secure(true)
+//!     .http_only(true)
+//!     .same_site(SameSite::Lax);
+//!
+//! assert_eq!(
+//!     cookie.to_string(),
+//!     "theme=dark; Path=/; Max-Age=3600; Secure; HttpOnly; SameSite=Lax"
+//! );
+//! ```
+
+use std::{fmt, time::Duration};
+
+/// The `SameSite` attribute of a cookie, controlling whether it is sent
+/// along with cross-site requests.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SameSite {
+    /// Only sent with requests from the same site
+    Strict,
+    /// Also sent when navigating to the site from another one
+    Lax,
+    /// Sent with every request; browsers require `Secure` alongside it
+    None,
+}
+
+impl fmt::Display for SameSite {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SameSite::Strict => write!(f, "Strict"),
+            SameSite::Lax => write!(f, "Lax"),
+            SameSite::None => write!(f, "None"),
+        }
+    }
+}
+
+/// A cookie to be set on the client through a `Set-Cookie` header.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cookie {
+    name: String,
+    value: String,
+    path: Option<String>,
+    max_age: Option<Duration>,
+    secure: bool,
+    http_only: bool,
+    same_site: Option<SameSite>,
+}
+
+impl Cookie {
+    /// Creates a cookie without any attributes.
+    ///
+    /// Such a cookie lasts until the browser is closed and is readable from
+    /// JavaScript.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The cookie name
+    /// * `value` - The cookie value, which must not contain `;`, `,` or whitespace
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use rwf::connection::cookie::Cookie;
+    ///
+    /// assert_eq!(Cookie::new("lang", "en").to_string(), "lang=en");
+    /// ```
+    pub fn new(name: &str, value: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            value: value.to_string(),
+            path: None,
+            max_age: None,
+            secure: false,
+            http_only: false,
+            same_site: None,
+        }
+    }
+
+    /// Limits the cookie to requests under the given path.
+    pub fn path(mut self, path: &str) -> Self {
+        self.path = Some(path.to_string());
+        self
+    }
+
+    /// Keeps the cookie for the given duration, rounded down to whole seconds.
+    ///
+    /// A zero duration asks the browser to delete the cookie.
+    pub fn max_age(mut self, max_age: Duration) -> Self {
+        self.max_age = Some(max_age);
+        self
+    }
+
+    /// Sets whether the cookie is only sent over HTTPS.
+    pub fn secure(mut self, secure: bool) -> Self {
+        self.secure = secure;
+        self
+    }
+
+    /// Sets whether the cookie is hidden from JavaScript.
+    pub fn http_only(mut self, http_only: bool) -> Self {
+        self.http_only = http_only;
+        self
+    }
+
+    /// Sets the cookie's `SameSite` attribute.
+    pub fn same_site(mut self, same_site: SameSite) -> Self {
+        self.same_site = Some(same_site);
+        self
+    }
+}
+
+impl fmt::Display for Cookie {
+    /// Formats the cookie as the value of a `Set-Cookie` header.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}={}", self.name, self.value)?;
+        if let Some(path) = &self.path {
+            write!(f, "; Path={path}")?;
+        }
+        if let Some(max_age) = self.max_age {
+            write!(f, "; Max-Age={}", max_age.as_secs())?;
+        }
+        if self.secure {
+            write!(f, "; Secure")?;
+        }
+        if self.http_only {
+            write!(f, "; HttpOnly")?;
+        }
+        if let Some(same_site) = self.same_site {
+            write!(f, "; SameSite={same_site}")?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,117 @@
+//! # Deferred Response Module
+//!
+//! Lets a handler answer a request later, from another thread, as long
+//! polling needs.
+//!
+//! A handler returns the placeholder built by
+//! [`Response::deferred`](super::response::Response::deferred) and hands the
+//! paired [`Resolver`] to whatever produces the data, e.g. by pushing it to a
+//! shared list of waiting clients. The connection keeps the stream open and
+//! blocks its own thread on a channel until the resolver sends the real
+//! response or the timeout elapses, in which case the client gets `204 No
+//! Content` and is expected to poll again.
+//!
+//! The server handles every connection on its own thread, so the wait is a
+//! plain blocking receive and other connections are unaffected. The handler
+//! has returned by then, so its session is already saved and released.
+//!
+//! ## Example
+//!
+//! ```rust,ignore
+//! let waiting: Arc<Mutex<Vec<Resolver>>> = Arc::default();
+//!
+//! let clients = Arc::clone(&waiting);
+//! server.route("/poll", move |_req, _session| {
+//!     let (response, resolver) = Response::deferred(Duration::from_secs(30));
+//!     clients.lock().unwrap().push(resolver);
+//!     response
+//! });
+//!
+//! // Elsewhere, once there is news
+//! for resolver in waiting.lock().unwrap().drain(..) {
+//!     resolver.resolve("news");
+//! }
+//! ```
+
+use super::response::{IntoResponse, Response};
+use std::{
+    fmt,
+    sync::{
+        mpsc::{self, Receiver, SyncSender},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+
+/// Sends the response of a deferred request, from any thread.
+///
+/// Dropping the resolver without calling [`Resolver::resolve`] ends the
+/// wait right away with the `204 No Content` placeholder.
+pub struct Resolver(SyncSender<Response>);
+
+impl Resolver {
+    /// Sends the response to the waiting client.
+    ///
+    /// # Returns
+    ///
+    /// * `true` - If the connection was still waiting for it
+    /// * `false` - If the request already timed out
+    pub fn resolve(self, response: impl IntoResponse) -> bool {
+        self.0.try_send(response.into_response()).is_ok()
+    }
+}
+
+impl fmt::Debug for Resolver {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("Resolver")
+    }
+}
+
+/// The receiving side of a deferred response, waited on by the connection.
+///
+/// Cloning a response shares the receiver, which only the first clone to be
+/// sent gets to wait on.
+#[derive(Clone)]
+pub struct Pending {
+    receiver: Arc<Mutex<Option<Receiver<Response>>>>,
+    timeout: Duration,
+}
+
+impl Pending {
+    /// Creates a pending response and the resolver completing it.
+    pub(crate) fn new(timeout: Duration) -> (Self, Resolver) {
+        let (sender, receiver) = mpsc::sync_channel(1);
+        let pending = Self {
+            receiver: Arc::new(Mutex::new(Some(receiver))),
+            timeout,
+        };
+        (pending, Resolver(sender))
+    }
+
+    /// Blocks until the response is resolved or the timeout elapses.
+    ///
+    /// Returns `None` on timeout, if the resolver was dropped, or if another
+    /// clone already waited.
+    pub(crate) fn wait(&self) -> Option<Response> {
+        let receiver = self
+            .receiver
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .take()?;
+        receiver.recv_timeout(self.timeout).ok()
+    }
+}
+
+impl fmt::Debug for Pending {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Pending")
+            .field("timeout", &self.timeout)
+            .finish()
+    }
+}
+
+impl PartialEq for Pending {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.receiver, &other.receiver)
+    }
+}
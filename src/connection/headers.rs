@@ -0,0 +1,136 @@
+//! # Headers Module
+//!
+//! Provides a case-insensitive multimap of HTTP header fields.
+//!
+//! When converting a raw header block:
+//! - Header names are matched case-insensitively and stored lowercased.
+//! - Values are trimmed of surrounding whitespace.
+//! - A header repeated on several lines keeps one value per line, in the
+//!   order they were received. Comma-separated values on a single line are
+//!   kept as one value, since some headers (like `Set-Cookie` or dates) use
+//!   commas inside a single value.
+//!
+//! ## Example
+//!
+//! ```rust
+//! use rwf::connection::headers::Headers;
+//!
+//! let headers = Headers::parse("GET / HTTP/1.1\nAccept: text/html\naccept: application/json");
+//!
+//! assert_eq!(headers.get("ACCEPT"), Some("text/html"));
+//! assert_eq!(headers.get_all("accept"), ["text/html", "application/json"]);
+//! ```
+
+use std::collections::HashMap;
+
+/// A collection of HTTP header fields where each name can hold several values.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Headers {
+    /// Header values keyed by lowercased name, in the order they were received.
+    fields: HashMap<String, Vec<String>>,
+}
+
+impl Headers {
+    /// Creates an empty header collection.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Parses the header block of a request.
+    ///
+    /// The first line is the request line and is skipped. Lines without a
+    /// `:` separator are ignored.
+    ///
+    /// # Arguments
+    ///
+    /// * `raw` - The raw header block, one field per line
+    ///
+    /// # Returns
+    ///
+    /// The parsed `Headers`
+    pub fn parse(raw: &str) -> Self {
+        let mut headers = Self::new();
+        for line in raw.lines().skip(1) {
+            if let Some((name, value)) = line.split_once(':') {
+                headers.append(name.trim(), value.trim());
+            }
+        }
+        headers
+    }
+
+    /// Adds a value for the given header name, keeping any existing values.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The header name, in any case
+    /// * `value` - The header value
+    pub fn append(&mut self, name: &str, value: &str) {
+        self.fields
+            .entry(name.to_ascii_lowercase())
+            .or_default()
+            .push(value.to_string());
+    }
+
+    /// Returns the first value of the given header, if present.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The header name, in any case
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use rwf::connection::headers::Headers;
+    ///
+    /// let headers = Headers::parse("GET / HTTP/1.1\nHost: localhost");
+    /// assert_eq!(headers.get("host"), Some("localhost"));
+    /// assert_eq!(headers.get("cookie"), None);
+    /// ```
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.get_all(name).first().map(String::as_str)
+    }
+
+    /// Returns every value of the given header, in the order they were received.
+    ///
+    /// Returns an empty slice if the header is absent.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The header name, in any case
+    pub fn get_all(&self, name: &str) -> &[String] {
+        self.fields
+            .get(&name.to_ascii_lowercase())
+            .map(Vec::as_slice)
+            .unwrap_or_default()
+    }
+
+    /// Checks whether the given header is present.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The header name, in any case
+    pub fn contains(&self, name: &str) -> bool {
+        self.fields.contains_key(&name.to_ascii_lowercase())
+    }
+
+    /// Returns every header name with its values, sorted by name.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use rwf::connection::headers::Headers;
+    ///
+    /// let headers = Headers::parse("GET / HTTP/1.1\nHost: localhost\nAccept: text/html");
+    /// let names: Vec<&str> = headers.iter().map(|(name, _)| name).collect();
+    /// assert_eq!(names, ["accept", "host"]);
+    /// ```
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &[String])> {
+        let mut fields: Vec<_> = self
+            .fields
+            .iter()
+            .map(|(name, values)| (name.as_str(), values.as_slice()))
+            .collect();
+        fields.sort_unstable_by_key(|(name, _)| *name);
+        fields.into_iter()
+    }
+}
@@ -2,81 +2,167 @@
 //!
 //! Defines HTTP methods supported by the server.
 //!
-//! Currently supports GET and POST methods with string conversion
-//! functionality in both directions.
+//! Currently supports GET, HEAD, POST, PUT, PATCH, DELETE and OPTIONS methods with string conversion
+//! functionality in both directions. TRACE and CONNECT are recognized so the
+//! server can refuse them: TRACE unless `Server::allow_trace` is enabled, and
+//! CONNECT, which only proxies serve, always. Any other method, such as
+//! WebDAV's PROPFIND, is kept as `Method::Other` and can be routed with
+//! `Server::route_with` like the named ones.
 //!
 //! ## Example
 //!
 //! ```rust
-//! use method::Method;
+//! use rwf::connection::method::Method;
 //!
 //! // Create from string
 //! let method = Method::from_string("GET");
 //!
 //! // Convert to string
 //! assert_eq!(method.to_string(), "GET");
+//!
+//! // Extension methods round-trip unchanged
+//! let method = Method::from_string("PROPFIND");
+//! assert_eq!(method, Method::Other("PROPFIND".to_string()));
+//! assert_eq!(method.to_string(), "PROPFIND");
 //! ```
 
-use core::panic;
+use std::fmt;
 
 /// Represents HTTP methods supported by the server.
 ///
 /// Currently supports:
 /// - GET
+/// - HEAD
 /// - POST
-#[derive(Debug)]
+/// - PUT
+/// - PATCH
+/// - DELETE
+/// - OPTIONS
+/// - TRACE
+/// - CONNECT
+/// - Any other method token, as `Method::Other`
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum Method {
     /// HTTP GET method
     GET,
+    /// HTTP HEAD method, answered without a body by a route registered for
+    /// it, or else by the GET handler
+    HEAD,
     /// HTTP POST method
     POST,
+    /// HTTP PUT method
+    PUT,
+    /// HTTP PATCH method, routed separately from PUT
+    PATCH,
+    /// HTTP DELETE method
+    DELETE,
+    /// HTTP OPTIONS method; `OPTIONS *` is answered by the server itself
+    OPTIONS,
+    /// HTTP TRACE method, refused with `405 Method Not Allowed` unless
+    /// `Server::allow_trace` is enabled, as echoing requests back can leak
+    /// credentials to scripts (cross-site tracing)
+    TRACE,
+    /// HTTP CONNECT method, for proxies, always refused with `501 Not Implemented`
+    CONNECT,
+    /// Any other method, such as WebDAV's PROPFIND, kept exactly as sent.
+    /// Methods are case-sensitive, so `get` is an `Other` method too
+    Other(String),
 }
 
 impl Method {
+    /// Every method served by default, in the order they are listed in
+    /// `Allow` headers. TRACE and CONNECT are left out.
+    pub const ALL: [Method; 7] = [
+        Method::GET,
+        Method::HEAD,
+        Method::POST,
+        Method::PUT,
+        Method::PATCH,
+        Method::DELETE,
+        Method::OPTIONS,
+    ];
+
     /// Converts a string to a Method enum variant.
     ///
     /// # Arguments
     ///
-    /// * `string` - The HTTP method as a string ("GET" or "POST")
+    /// * `string` - The HTTP method as a string ("GET", "HEAD", "POST", "PUT", "PATCH", "DELETE",
+    ///   "OPTIONS", "TRACE", "CONNECT" or any other method token)
     ///
     /// # Returns
     ///
-    /// The corresponding Method enum variant
+    /// The corresponding Method enum variant, `Method::Other` for methods
+    /// without a variant of their own
     ///
     /// # Panics
     ///
-    /// Panics if the string is not "GET" or "POST"
+    /// Panics if the string is not a valid method token: it is empty or
+    /// contains characters such as spaces, `/` or `@`. Requests with such a
+    /// method are rejected as `ParseError::MalformedRequestLine` instead.
     ///
     /// # Example
     ///
     /// ```rust
+    /// use rwf::connection::method::Method;
+    ///
     /// let method = Method::from_string("GET");
     /// assert!(matches!(method, Method::GET));
+    ///
+    /// let method = Method::from_string("PROPFIND");
+    /// assert!(matches!(method, Method::Other(ref name) if name == "PROPFIND"));
     /// ```
     pub fn from_string(string: &str) -> Self {
         match string {
             "GET" => Self::GET,
+            "HEAD" => Self::HEAD,
             "POST" => Self::POST,
+            "PUT" => Self::PUT,
+            "PATCH" => Self::PATCH,
+            "DELETE" => Self::DELETE,
+            "OPTIONS" => Self::OPTIONS,
+            "TRACE" => Self::TRACE,
+            "CONNECT" => Self::CONNECT,
+            other if Self::is_token(other) => Self::Other(other.to_string()),
             _ => panic!("Failed to parse string to http method"),
         }
     }
 
-    /// Converts the Method enum variant to its string representation.
-    ///
-    /// # Returns
-    ///
-    /// A String containing "GET" or "POST"
+    /// Checks whether a string is a valid method token: one or more letters,
+    /// digits or any of ``!#$%&'*+-.^_`|~``.
+    pub(crate) fn is_token(string: &str) -> bool {
+        !string.is_empty()
+            && string
+                .bytes()
+                .all(|b| b.is_ascii_alphanumeric() || b"!#$%&'*+-.^_`|~".contains(&b))
+    }
+}
+
+impl fmt::Display for Method {
+    /// Formats the Method enum variant as its string representation.
     ///
     /// # Example
     ///
     /// ```rust
+    /// use rwf::connection::method::Method;
+    ///
     /// let method = Method::GET;
     /// assert_eq!(method.to_string(), "GET");
+    ///
+    /// let method = Method::Other("PROPFIND".to_string());
+    /// assert_eq!(method.to_string(), "PROPFIND");
     /// ```
-    pub fn to_string(&self) -> String {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
-            Method::GET => "GET".to_string(),
-            Method::POST => "POST".to_string(),
+            Method::GET => write!(f, "GET"),
+            Method::HEAD => write!(f, "HEAD"),
+            Method::POST => write!(f, "POST"),
+            Method::PUT => write!(f, "PUT"),
+            Method::PATCH => write!(f, "PATCH"),
+            Method::DELETE => write!(f, "DELETE"),
+            Method::OPTIONS => write!(f, "OPTIONS"),
+            Method::TRACE => write!(f, "TRACE"),
+            Method::CONNECT => write!(f, "CONNECT"),
+            Method::Other(name) => write!(f, "{name}"),
         }
     }
 }
@@ -0,0 +1,150 @@
+//! # Metrics Module
+//!
+//! Collects request counts and latencies, rendered in the Prometheus text
+//! exposition format at the path given to `Server::metrics`.
+//!
+//! Requests are grouped by method and by the path of the route that served
+//! them as registered, e.g. `/users/:id`, so the number of series stays
+//! bounded however many different paths clients request. Requests no route
+//! matched are grouped under `unmatched`, and requests with an extension
+//! method no route is registered for, such as `PROPFIND`, under the method
+//! `other`. Two metric families are exposed:
+//!
+//! * `rwf_requests_total` - A counter of requests, labelled with `method`,
+//!   `route` and the response `status`
+//! * `rwf_request_duration_seconds` - A histogram of the time taken to
+//!   produce the response, labelled with `method` and `route`
+//!
+//! ## Example
+//!
+//! ```rust
+//! use rwf::connection::{method::Method, metrics::Metrics};
+//! use std::time::Duration;
+//!
+//! let metrics = Metrics::new();
+//! metrics.record(Method::GET, "/users/:id", 200, Duration::from_millis(3));
+//! metrics.record(Method::GET, "/users/:id", 404, Duration::from_millis(40));
+//!
+//! let text = metrics.render();
+//! assert!(text.contains(r#"rwf_requests_total{method="GET",route="/users/:id",status="200"} 1"#));
+//! assert!(text.contains(r#"rwf_request_duration_seconds_bucket{method="GET",route="/users/:id",le="0.005"} 1"#));
+//! assert!(text.contains(r#"rwf_request_duration_seconds_count{method="GET",route="/users/:id"} 2"#));
+//! ```
+
+use super::method::Method;
+use std::{collections::BTreeMap, fmt::Write, sync::Mutex, time::Duration};
+
+/// The upper bounds of the latency histogram buckets, in seconds.
+const BUCKETS: [f64; 11] = [
+    0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0,
+];
+
+/// A thread-safe collector of request counts and latencies.
+#[derive(Debug, Default)]
+pub struct Metrics {
+    /// The statistics of each method and route.
+    routes: Mutex<BTreeMap<(String, String), RouteStats>>,
+}
+
+/// The requests recorded for one method and route.
+#[derive(Debug, Default)]
+struct RouteStats {
+    /// The number of requests per response status
+    statuses: BTreeMap<u16, u64>,
+    /// The number of requests in each bucket of [`BUCKETS`], not cumulative
+    buckets: [u64; BUCKETS.len()],
+    /// The total number of requests
+    count: u64,
+    /// The total time taken by the requests
+    sum: Duration,
+}
+
+impl Metrics {
+    /// Creates a collector without any requests recorded.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Records a served request.
+    ///
+    /// # Arguments
+    ///
+    /// * `method` - The request method
+    /// * `route` - The route that served the request as registered, or
+    ///   `unmatched`
+    /// * `status` - The status code of the response
+    /// * `elapsed` - The time taken to produce the response
+    pub fn record(&self, method: Method, route: &str, status: u16, elapsed: Duration) {
+        let mut routes = self.routes.lock().unwrap_or_else(|e| e.into_inner());
+        let stats = routes
+            .entry((method.to_string(), route.to_string()))
+            .or_default();
+        *stats.statuses.entry(status).or_default() += 1;
+        let seconds = elapsed.as_secs_f64();
+        if let Some(bucket) = BUCKETS.iter().position(|bound| seconds <= *bound) {
+            stats.buckets[bucket] += 1;
+        }
+        stats.count += 1;
+        stats.sum += elapsed;
+    }
+
+    /// Renders the recorded requests in the Prometheus text format.
+    pub fn render(&self) -> String {
+        let routes = self.routes.lock().unwrap_or_else(|e| e.into_inner());
+        let mut text = String::new();
+
+        text.push_str("# HELP rwf_requests_total Requests served, by method, route and status.\n");
+        text.push_str("# TYPE rwf_requests_total counter\n");
+        for ((method, route), stats) in routes.iter() {
+            let labels = labels(method, route);
+            for (status, count) in &stats.statuses {
+                let _ = writeln!(
+                    text,
+                    "rwf_requests_total{{{labels},status=\"{status}\"}} {count}"
+                );
+            }
+        }
+
+        text.push_str(
+            "# HELP rwf_request_duration_seconds Time taken to produce responses, by method and route.\n",
+        );
+        text.push_str("# TYPE rwf_request_duration_seconds histogram\n");
+        for ((method, route), stats) in routes.iter() {
+            let labels = labels(method, route);
+            let mut cumulative = 0;
+            for (bound, count) in BUCKETS.iter().zip(stats.buckets) {
+                cumulative += count;
+                let _ = writeln!(
+                    text,
+                    "rwf_request_duration_seconds_bucket{{{labels},le=\"{bound}\"}} {cumulative}"
+                );
+            }
+            let _ = writeln!(
+                text,
+                "rwf_request_duration_seconds_bucket{{{labels},le=\"+Inf\"}} {}",
+                stats.count
+            );
+            let _ = writeln!(
+                text,
+                "rwf_request_duration_seconds_sum{{{labels}}} {}",
+                stats.sum.as_secs_f64()
+            );
+            let _ = writeln!(
+                text,
+                "rwf_request_duration_seconds_count{{{labels}}} {}",
+                stats.count
+            );
+        }
+        text
+    }
+}
+
+/// Formats the `method` and `route` labels, escaping the route as the
+/// format requires.
+fn labels(method: &str, route: &str) -> String {
+    let route = route
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n");
+    format!("method=\"{method}\",route=\"{route}\"")
+}
@@ -0,0 +1,65 @@
+//! # Mime Module
+//!
+//! Maps file extensions to the MIME types sent in the `Content-Type` header.
+//!
+//! ## Example
+//!
+//! ```rust
+//! use rwf::connection::mime;
+//!
+//! assert_eq!(mime::from_path("styles/main.css"), "text/css");
+//! assert_eq!(mime::from_path("unknown.xyz"), "application/octet-stream");
+//! ```
+
+/// Returns the MIME type for the given file path based on its extension.
+///
+/// The lookup is case-insensitive. Files without an extension, or with an
+/// unknown one, are reported as `application/octet-stream`.
+///
+/// # Arguments
+///
+/// * `path` - The path or name of the file
+///
+/// # Returns
+///
+/// The MIME type as a static string slice
+///
+/// # Example
+///
+/// ```rust
+/// use rwf::connection::mime;
+///
+/// assert_eq!(mime::from_path("index.HTML"), "text/html; charset=utf-8");
+/// assert_eq!(mime::from_path("logo.png"), "image/png");
+/// ```
+pub fn from_path(path: &str) -> &'static str {
+    let extension = match path.rsplit_once('.') {
+        Some((_, ext)) if !ext.contains('/') => ext.to_ascii_lowercase(),
+        _ => return "application/octet-stream",
+    };
+
+    match extension.as_str() {
+        "html" | "htm" => "text/html; charset=utf-8",
+        "css" => "text/css",
+        "js" | "mjs" => "text/javascript",
+        "json" => "application/json",
+        "txt" => "text/plain; charset=utf-8",
+        "csv" => "text/csv",
+        "xml" => "application/xml",
+        "pdf" => "application/pdf",
+        "zip" => "application/zip",
+        "wasm" => "application/wasm",
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "svg" => "image/svg+xml",
+        "ico" => "image/x-icon",
+        "webp" => "image/webp",
+        "woff" => "font/woff",
+        "woff2" => "font/woff2",
+        "ttf" => "font/ttf",
+        "mp3" => "audio/mpeg",
+        "mp4" => "video/mp4",
+        _ => "application/octet-stream",
+    }
+}
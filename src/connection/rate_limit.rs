@@ -0,0 +1,108 @@
+//! # Rate Limit Module
+//!
+//! Provides a per-client token bucket rate limiter.
+//!
+//! Each client IP owns a bucket holding up to `requests` tokens, refilled
+//! continuously at `requests` tokens per `per` duration. Every request takes
+//! one token; a request arriving at an empty bucket is rejected together with
+//! the time until the next token is available.
+//!
+//! ## Example
+//!
+//! ```rust
+//! use rwf::connection::rate_limit::RateLimiter;
+//! use std::time::Duration;
+//!
+//! let limiter = RateLimiter::new(2, Duration::from_secs(60));
+//! let ip = "203.0.113.7".parse().unwrap();
+//!
+//! assert!(limiter.check(ip).is_ok());
+//! assert!(limiter.check(ip).is_ok());
+//!
+//! // The third request within the minute exceeds the limit
+//! let retry_after = limiter.check(ip).unwrap_err();
+//! assert!(retry_after > Duration::from_secs(25));
+//!
+//! // Other clients have their own bucket
+//! assert!(limiter.check("203.0.113.8".parse().unwrap()).is_ok());
+//! ```
+
+use std::{
+    collections::HashMap,
+    net::IpAddr,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+/// Number of tracked clients above which full buckets are discarded.
+const PRUNE_THRESHOLD: usize = 10_000;
+
+/// A thread-safe token bucket rate limiter keyed by client IP.
+#[derive(Debug)]
+pub struct RateLimiter {
+    /// Maximum number of tokens a bucket holds.
+    capacity: f64,
+    /// Tokens added to a bucket per second.
+    refill_rate: f64,
+    /// Each client's remaining tokens and the time they were last updated.
+    buckets: Mutex<HashMap<IpAddr, (f64, Instant)>>,
+}
+
+impl RateLimiter {
+    /// Creates a limiter allowing `requests` requests per `per` duration for each client.
+    ///
+    /// # Arguments
+    ///
+    /// * `requests` - The number of requests allowed in a burst
+    /// * `per` - The time over which `requests` tokens are refilled
+    ///
+    /// # Panics
+    ///
+    /// Panics if `requests` is zero or `per` is zero.
+    pub fn new(requests: u32, per: Duration) -> Self {
+        assert!(requests > 0, "rate limit must allow at least one request");
+        assert!(!per.is_zero(), "rate limit duration must not be zero");
+        Self {
+            capacity: requests as f64,
+            refill_rate: requests as f64 / per.as_secs_f64(),
+            buckets: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Takes a token from the client's bucket.
+    ///
+    /// # Arguments
+    ///
+    /// * `ip` - The client's IP address
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(())` - If the request is allowed
+    /// * `Err(Duration)` - If the limit is exceeded, with the time until the
+    ///   client may retry
+    pub fn check(&self, ip: IpAddr) -> Result<(), Duration> {
+        let now = Instant::now();
+        let mut buckets = self.buckets.lock().unwrap_or_else(|e| e.into_inner());
+
+        if buckets.len() > PRUNE_THRESHOLD {
+            buckets.retain(|_, bucket| self.refill(bucket, now) < self.capacity);
+        }
+
+        let bucket = buckets.entry(ip).or_insert((self.capacity, now));
+        let tokens = self.refill(bucket, now);
+
+        if tokens >= 1.0 {
+            *bucket = (tokens - 1.0, now);
+            Ok(())
+        } else {
+            *bucket = (tokens, now);
+            Err(Duration::from_secs_f64((1.0 - tokens) / self.refill_rate))
+        }
+    }
+
+    /// Returns the tokens a bucket holds at `now`.
+    fn refill(&self, (tokens, updated): &(f64, Instant), now: Instant) -> f64 {
+        let elapsed = now.duration_since(*updated).as_secs_f64();
+        (tokens + elapsed * self.refill_rate).min(self.capacity)
+    }
+}
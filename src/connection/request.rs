@@ -4,7 +4,7 @@
 //! body extraction, and form data processing.
 //!
 //! This module provides functionality to:
-//! - Parse raw TCP streams into structured HTTP requests
+//! - Parse raw byte streams into structured HTTP requests
 //! - Extract HTTP methods and paths
 //! - Process request headers and bodies
 //! - Handle session management
@@ -13,181 +13,2112 @@
 //! ## Example
 //!
 //! ```rust
-//! use request::Request;
+//! use rwf::connection::request::{Limits, Request};
+//! use std::io::Cursor;
 //!
-//! // Assuming we have a TcpStream...
-//! let request = Request::parse(&stream);
+//! // Any `Read` source works: a TcpStream, a TLS stream or an in-memory buffer
+//! let mut stream = Cursor::new(b"GET /hello HTTP/1.1\r\nHost: localhost\r\n\r\n".to_vec());
+//! let request = Request::parse(&mut stream, &Limits::default(), b"secret").unwrap();
 //!
 //! // Access request components
 //! println!("Method: {:?}", request.method);
 //! println!("Path: {}", request.resource);
 //! ```
 
-use super::method::Method;
+mod body;
+mod builder;
+mod content_type;
+
+pub use body::BodyReader;
+pub use builder::RequestBuilder;
+pub use content_type::ContentType;
+
+use super::{
+    auth::Auth,
+    headers::Headers,
+    method::Method,
+    session,
+    template::{TemplateError, Templates},
+    version::Version,
+};
 use std::collections::HashMap;
-use std::io::{BufRead, BufReader, Read};
-use std::net::TcpStream;
-use std::usize;
+use std::fmt;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{IpAddr, SocketAddr};
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// Limits applied while reading a request from a stream.
+///
+/// Requests exceeding them are rejected before their data is fully buffered,
+/// so a client cannot make the server allocate unbounded memory.
+#[derive(Debug, Clone, Copy)]
+pub struct Limits {
+    /// Maximum size in bytes of the header lines together, excluding the
+    /// request line, which is limited by `max_uri_length`. Defaults to 8 KiB.
+    pub max_header_size: usize,
+    /// Maximum number of header lines, excluding the request line. Defaults to 100.
+    pub max_headers: usize,
+    /// Maximum length in bytes of the request target, path and query
+    /// string together. Defaults to 8 KiB.
+    pub max_uri_length: usize,
+    /// Maximum size in bytes of the body, as announced by `Content-Length`. Defaults to 1 MiB.
+    pub max_body_size: usize,
+    /// Bodies larger than this many bytes are written to a temporary file
+    /// instead of `Request::body`, and read through `Request::body_reader`.
+    /// Defaults to `None`, keeping every body in memory.
+    pub max_buffered_body: Option<usize>,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Self {
+            max_header_size: 8 * 1024,
+            max_headers: 100,
+            max_uri_length: 8 * 1024,
+            max_body_size: 1024 * 1024,
+            max_buffered_body: None,
+        }
+    }
+}
+
+/// Bytes of the request line allowed besides the target, for the method and
+/// version, when reading it to check `Limits::max_uri_length`.
+const REQUEST_LINE_SLACK: usize = 64;
+
+/// The header section, buffered body and spooled body read by
+/// `Request::get_data`.
+type RequestData = (String, Option<Vec<u8>>, Option<BodyReader>);
+
+/// Errors that can occur while parsing a request.
+#[derive(Debug, PartialEq)]
+pub enum ParseError {
+    /// The header block exceeded `Limits::max_header_size` or `Limits::max_headers`.
+    HeadersTooLarge,
+    /// The request line was not a method, a target and a version separated
+    /// by single spaces, or was not valid UTF-8.
+    MalformedRequestLine,
+    /// A header line was not valid UTF-8.
+    MalformedHeader,
+    /// The request target exceeded `Limits::max_uri_length`.
+    UriTooLong,
+    /// The client closed the connection before sending a request line, e.g.
+    /// a persistent connection closed between two requests, or before the
+    /// blank line ending the headers.
+    ConnectionClosed,
+    /// The `Content-Length` exceeded `Limits::max_body_size`.
+    BodyTooLarge,
+    /// The client sent `Expect: 100-continue` for a body exceeding
+    /// `Limits::max_body_size`, so it is told not to send it.
+    ExpectationFailed,
+    /// The path climbs above the root with `..` segments, e.g. `/../etc/passwd`.
+    InvalidPath,
+    /// The connection ended before the whole body announced by
+    /// `Content-Length` was received.
+    TruncatedBody,
+    /// A `Content-Length` header was not a plain decimal number, or several
+    /// of them disagreed, so the end of the body cannot be trusted.
+    InvalidContentLength,
+    /// The request carried a `Transfer-Encoding` header. Chunked bodies are
+    /// not supported, and reading one as a plain body would let its data
+    /// pass for the next request.
+    UnsupportedTransferEncoding,
+    /// A `gzip` or `deflate` encoded body could not be decompressed. Only
+    /// raised with the `compression` feature.
+    InvalidEncoding,
+    /// Reading the request failed, e.g. because the connection timed out or
+    /// was reset. No response can be sent.
+    Io(io::ErrorKind),
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ParseError::HeadersTooLarge => write!(f, "request header fields too large"),
+            ParseError::MalformedRequestLine => write!(f, "malformed request line"),
+            ParseError::MalformedHeader => write!(f, "malformed header line"),
+            ParseError::UriTooLong => write!(f, "request target too long"),
+            ParseError::ConnectionClosed => write!(f, "connection closed by the client"),
+            ParseError::BodyTooLarge => write!(f, "request body too large"),
+            ParseError::ExpectationFailed => write!(f, "expectation failed"),
+            ParseError::InvalidPath => write!(f, "path escapes the root directory"),
+            ParseError::TruncatedBody => write!(f, "request body shorter than its length"),
+            ParseError::InvalidContentLength => write!(f, "invalid Content-Length header"),
+            ParseError::UnsupportedTransferEncoding => {
+                write!(f, "Transfer-Encoding is not supported")
+            }
+            ParseError::InvalidEncoding => write!(f, "invalid content encoding"),
+            ParseError::Io(kind) => write!(f, "failed to read request: {kind}"),
+        }
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+/// Errors that can occur while reading a JSON request body.
+///
+/// Converts into a `415 Unsupported Media Type` or `400 Bad Request`
+/// [`Response`](super::response::Response), so handlers can answer invalid
+/// bodies directly.
+///
+/// Available with the `serde` feature.
+#[cfg(feature = "serde")]
+#[derive(Debug)]
+pub enum BodyError {
+    /// The `Content-Type` header is missing or not a JSON media type.
+    WrongContentType,
+    /// The request has no body.
+    EmptyBody,
+    /// The body is not valid JSON for the requested type.
+    Deserialize(serde_json::Error),
+}
+
+#[cfg(feature = "serde")]
+impl BodyError {
+    /// Returns the HTTP status code fitting the error.
+    ///
+    /// # Returns
+    ///
+    /// * `415` - For [`BodyError::WrongContentType`]
+    /// * `400` - For an empty or undeserializable body
+    pub fn status(&self) -> u16 {
+        match self {
+            BodyError::WrongContentType => 415,
+            BodyError::EmptyBody | BodyError::Deserialize(_) => 400,
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl fmt::Display for BodyError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            BodyError::WrongContentType => write!(f, "expected a JSON content type"),
+            BodyError::EmptyBody => write!(f, "request body is empty"),
+            BodyError::Deserialize(e) => write!(f, "invalid JSON body: {e}"),
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl std::error::Error for BodyError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            BodyError::Deserialize(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl From<BodyError> for super::response::Response {
+    fn from(error: BodyError) -> Self {
+        Self::new(error.status(), error.to_string())
+    }
+}
+
+#[cfg(feature = "serde")]
+impl From<BodyError> for super::response::HttpError {
+    /// Lets handlers returning `Result<_, HttpError>` use `?` on `Request::json`.
+    fn from(error: BodyError) -> Self {
+        Self::new(error.status(), &error.to_string())
+    }
+}
+
+/// Represents an HTTP request with all its components.
+///
+/// Stores parsed information from an HTTP request including the method,
+/// resource path, headers, body, and session information.
+///
+/// Its `Debug` output leaves out the body and raw bytes and redacts
+/// credential headers, so requests can be logged safely. See also
+/// [`Request::summary`].
+pub struct Request {
+    pub resource: String,
+    pub query: HashMap<String, String>,
+    pub method: Method,
+    pub version: Version,
+    pub header: String,
+    pub headers: Headers,
+    /// The raw body bytes, see [`Request::body_str`] for the body as text
+    pub body: Option<Vec<u8>>,
+    pub session: Option<String>,
+    /// Identifies the request in logs and the `X-Request-Id` response header
+    pub id: String,
+    /// The exact bytes received for the request, headers and body, kept
+    /// only when `Server::capture_raw` is enabled
+    pub raw: Option<Vec<u8>>,
+    /// The host name from the `Host` header, lowercased and without the
+    /// port, e.g. `a.example.com`
+    pub host: Option<String>,
+    pub remote_addr: Option<SocketAddr>,
+    /// Whether `client_ip` may read proxy headers, see `Server::trust_proxy`
+    pub(crate) trust_proxy: bool,
+    /// Whether the connection was accepted on a TLS server
+    pub(crate) tls: bool,
+    /// The values of the matched route's `:name` path segments, keyed by name
+    pub params: HashMap<String, String>,
+    /// The path of the route handling the request as registered, e.g.
+    /// `/users/:id`, set once the request is routed
+    pub matched_route: Option<String>,
+    /// The cookies sent in `Cookie` headers, read through [`Request::cookie`]
+    cookies: HashMap<String, String>,
+    /// When the response is due, derived from the server's timeout once the
+    /// request has been read, see [`Request::time_remaining`]
+    pub deadline: Option<Instant>,
+    /// The body, when it exceeded `Limits::max_buffered_body` and was
+    /// written to a temporary file instead of `body`
+    body_reader: Option<BodyReader>,
+    /// The templates registered with `Server::templates`, see [`Request::render`]
+    pub(crate) templates: Option<Arc<Templates>>,
+}
+
+/// Headers whose values are replaced by `[redacted]` in `Debug` output.
+const REDACTED_HEADERS: [&str; 4] = [
+    "authorization",
+    "proxy-authorization",
+    "cookie",
+    "x-csrf-token",
+];
+
+impl fmt::Debug for Request {
+    /// Formats the request without exposing credentials.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use rwf::connection::request::{Limits, Request};
+    ///
+    /// let raw = b"POST /login HTTP/1.1\r\n\
+    ///             Authorization: Bearer s3cr3t\r\n\
+    ///             Cookie: theme=dark\r\n\
+    ///             Content-Length: 13\r\n\
+    ///             \r\n\
+    ///             password=hunt";
+    /// let request = Request::parse(&raw[..], &Limits::default(), b"secret").unwrap();
+    /// let debug = format!("{request:?}");
+    ///
+    /// assert!(debug.contains("\"authorization\": [\"[redacted]\"]"));
+    /// assert!(!debug.contains("s3cr3t") && !debug.contains("dark") && !debug.contains("hunt"));
+    /// assert!(debug.contains("body: Some(\"<13 bytes>\")"));
+    /// ```
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let headers: Vec<(&str, Vec<&str>)> = self
+            .headers
+            .iter()
+            .map(|(name, values)| {
+                let values = if REDACTED_HEADERS.contains(&name) {
+                    vec!["[redacted]"; values.len()]
+                } else {
+                    values.iter().map(String::as_str).collect()
+                };
+                (name, values)
+            })
+            .collect();
+
+        f.debug_struct("Request")
+            .field("id", &self.id)
+            .field("method", &self.method)
+            .field("resource", &self.resource)
+            .field("matched_route", &self.matched_route)
+            .field("params", &self.params)
+            .field("query", &self.query)
+            .field("version", &self.version)
+            .field("headers", &DebugMap(&headers))
+            .field(
+                "body",
+                &self
+                    .body
+                    .as_ref()
+                    .map(|body| format!("<{} bytes>", body.len())),
+            )
+            .field("session", &self.session)
+            .field("host", &self.host)
+            .field("remote_addr", &self.remote_addr)
+            .field("deadline", &self.deadline)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Formats name and value pairs as a map, keeping their order.
+struct DebugMap<'a>(&'a [(&'a str, Vec<&'a str>)]);
+
+impl fmt::Debug for DebugMap<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_map()
+            .entries(self.0.iter().map(|(name, values)| (name, values)))
+            .finish()
+    }
+}
+
+impl Request {
+    /// Returns the value of a path parameter captured by the matched route.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The parameter name, without the leading `:`
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use rwf::Server;
+    ///
+    /// let mut server = Server::build("127.0.0.1:0").unwrap();
+    /// server.route("/users/:id", |req, _session| {
+    ///     format!("{} {}", req.matched_route.as_deref().unwrap(), req.param("id").unwrap())
+    /// });
+    /// # let addr = rwf::test_support::serve(server);
+    /// # let send = |request: &str| rwf::test_support::send(addr, request);
+    ///
+    /// let response = send("GET /users/42 HTTP/1.1\r\nConnection: close\r\n\r\n");
+    /// assert!(response.ends_with("/users/:id 42"));
+    /// ```
+    pub fn param(&self, name: &str) -> Option<&str> {
+        self.params.get(name).map(String::as_str)
+    }
+
+    /// Returns the non-empty segments of the request path, in order.
+    ///
+    /// Leading, trailing and repeated slashes yield no empty segments, and
+    /// the root path `/` has none. Segments are returned as sent, without
+    /// percent-decoding.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use rwf::connection::{method::Method, request::Request};
+    ///
+    /// let request = Request::builder(Method::GET, "/a/b/c/?page=2").build();
+    /// assert_eq!(request.path_segments().collect::<Vec<_>>(), ["a", "b", "c"]);
+    /// assert_eq!(request.path_segments().last(), Some("c"));
+    ///
+    /// let root = Request::builder(Method::GET, "/").build();
+    /// assert_eq!(root.path_segments().next(), None);
+    /// ```
+    pub fn path_segments(&self) -> impl Iterator<Item = &str> {
+        self.resource
+            .split('/')
+            .filter(|segment| !segment.is_empty())
+    }
+
+    /// Returns a path parameter captured by the matched route, parsed as `T`.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The parameter name, without the leading `:`
+    ///
+    /// # Returns
+    ///
+    /// * `Some(T)` - The parsed value
+    /// * `None` - If the parameter is missing or cannot be parsed as `T`
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use rwf::Server;
+    ///
+    /// let mut server = Server::build("127.0.0.1:0").unwrap();
+    /// server.route("/users/:id", |req, _session| {
+    ///     let id: u32 = req.param_as("id").unwrap_or(0);
+    ///     let missing = req.param_as::<u32>("page");
+    ///     format!("user {id}, page {missing:?}")
+    /// });
+    /// # let addr = rwf::test_support::serve(server);
+    /// # let get = |path: &str| rwf::test_support::get(addr, path);
+    ///
+    /// assert!(get("/users/42").ends_with("user 42, page None"));
+    /// // A value that does not parse falls back like a missing one
+    /// assert!(get("/users/ferris").ends_with("user 0, page None"));
+    /// ```
+    pub fn param_as<T: FromStr>(&self, name: &str) -> Option<T> {
+        self.param(name)?.parse().ok()
+    }
+
+    /// Returns a one-line description of the request, safe to log.
+    ///
+    /// Holds the method, the path and the session ID, if any. Headers, the
+    /// query string and the body are left out, so no credentials leak.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use rwf::connection::request::{Limits, Request};
+    ///
+    /// let raw = b"GET /profile?token=abc HTTP/1.1\r\nAuthorization: Basic dXNlcjpwYXNz\r\n\r\n";
+    /// let request = Request::parse(&raw[..], &Limits::default(), b"secret").unwrap();
+    /// assert_eq!(request.summary(), "GET /profile (no session)");
+    /// ```
+    pub fn summary(&self) -> String {
+        match &self.session {
+            Some(session) => format!("{} {} (session: {session})", self.method, self.resource),
+            None => format!("{} {} (no session)", self.method, self.resource),
+        }
+    }
+
+    /// Starts building a request in memory, e.g. to call a handler in a test.
+    ///
+    /// # Arguments
+    ///
+    /// * `method` - The request method
+    /// * `target` - The path, optionally followed by a query string
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use rwf::connection::{method::Method, request::Request};
+    ///
+    /// let request = Request::builder(Method::POST, "/login")
+    ///     .header("Content-Type", "application/x-www-form-urlencoded")
+    ///     .body("user=john")
+    ///     .build();
+    /// assert_eq!(request.form().unwrap()["user"], "john");
+    /// ```
+    pub fn builder(method: Method, target: &str) -> RequestBuilder {
+        RequestBuilder::new(method, target)
+    }
+
+    /// Parses a stream into a structured HTTP request.
+    ///
+    /// # Arguments
+    ///
+    /// * `stream` - Any reader containing the raw HTTP request, such as a
+    ///   `TcpStream`, a TLS stream or an in-memory `Cursor`
+    /// * `limits` - The size limits the request must respect
+    /// * `secret` - The server secret session cookies are signed with
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(Request)` - A new `Request` instance containing the parsed data
+    /// * `Err(ParseError::HeadersTooLarge)` - If the header block exceeds the limits
+    /// * `Err(ParseError::UriTooLong)` - If the request target exceeds
+    ///   `Limits::max_uri_length`
+    /// * `Err(ParseError::ConnectionClosed)` - If the stream ends before a
+    ///   request line, or before the blank line ending the headers
+    /// * `Err(ParseError::MalformedRequestLine)` - If the request line is not
+    ///   exactly a method, a target and a version separated by single spaces
+    /// * `Err(ParseError::MalformedHeader)` - If a header line is not valid UTF-8
+    /// * `Err(ParseError::BodyTooLarge)` - If the announced body exceeds the limits
+    /// * `Err(ParseError::ExpectationFailed)` - If the announced body exceeds the
+    ///   limits and the client sent `Expect: 100-continue`
+    ///
+    /// A stream that is only readable cannot be answered with `100 Continue`,
+    /// so the body of an `Expect: 100-continue` request is read without one.
+    /// The server answers it before reading the body.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use rwf::connection::{method::Method, request::{Limits, Request}};
+    /// use std::io::Cursor;
+    ///
+    /// let raw = "POST /login HTTP/1.1\r\n\
+    ///            Host: localhost\r\n\
+    ///            Content-Type: application/x-www-form-urlencoded\r\n\
+    ///            Content-Length: 13\r\n\
+    ///            \r\n\
+    ///            user=john&a=1";
+    /// let request = Request::parse(Cursor::new(raw.as_bytes().to_vec()), &Limits::default(), b"secret").unwrap();
+    ///
+    /// assert!(matches!(request.method, Method::POST));
+    /// assert_eq!(request.resource, "/login");
+    /// assert!(request.header.contains("Host: localhost"));
+    /// assert!(request.header.contains("Content-Length: 13"));
+    /// assert_eq!(request.body_str(), Some("user=john&a=1"));
+    /// assert_eq!(request.id.len(), 16);
+    ///
+    /// // An ID sent by the client is kept
+    /// let raw = b"GET / HTTP/1.1\r\nX-Request-Id: abc-123\r\n\r\n";
+    /// let request = Request::parse(&raw[..], &Limits::default(), b"secret").unwrap();
+    /// assert_eq!(request.id, "abc-123");
+    /// ```
+    ///
+    /// An endless header line is rejected once the limit is reached instead of
+    /// being buffered:
+    ///
+    /// ```rust
+    /// use rwf::connection::request::{Limits, ParseError, Request};
+    /// use std::io::{repeat, Read};
+    ///
+    /// let endless = b"GET / HTTP/1.1\r\nX-Filler: ".chain(repeat(b'a'));
+    /// let result = Request::parse(endless, &Limits::default(), b"secret");
+    /// assert_eq!(result.unwrap_err(), ParseError::HeadersTooLarge);
+    ///
+    /// let limits = Limits { max_headers: 2, ..Limits::default() };
+    /// let raw = b"GET / HTTP/1.1\r\nA: 1\r\nB: 2\r\nC: 3\r\n\r\n";
+    /// let result = Request::parse(&raw[..], &limits, b"secret");
+    /// assert_eq!(result.unwrap_err(), ParseError::HeadersTooLarge);
+    /// ```
+    ///
+    /// An overlong request target is rejected from the request line alone,
+    /// reading little more than the limit however much the client sends:
+    ///
+    /// ```rust
+    /// use rwf::connection::request::{Limits, ParseError, Request};
+    /// use std::io::{repeat, Read};
+    ///
+    /// let limits = Limits { max_uri_length: 16, ..Limits::default() };
+    /// let raw = b"GET /a/reasonable/path HTTP/1.1\r\n\r\n";
+    /// let result = Request::parse(&raw[..], &limits, b"secret");
+    /// assert_eq!(result.unwrap_err(), ParseError::UriTooLong);
+    ///
+    /// // A target of exactly the limit is fine, as the request line does not
+    /// // count toward `max_header_size`
+    /// let raw = format!("GET /{} HTTP/1.1\r\n\r\n", "a".repeat(8191));
+    /// assert!(Request::parse(raw.as_bytes(), &Limits::default(), b"secret").is_ok());
+    ///
+    /// // A never-ending target
+    /// let stream = (&b"GET /"[..]).chain(repeat(b'a'));
+    /// let result = Request::parse(stream, &Limits::default(), b"secret");
+    /// assert_eq!(result.unwrap_err(), ParseError::UriTooLong);
+    /// ```
+    ///
+    /// A connection closed before sending a request line is an error rather
+    /// than a panic:
+    ///
+    /// ```rust
+    /// use rwf::connection::request::{Limits, ParseError, Request};
+    ///
+    /// let result = Request::parse(&b""[..], &Limits::default(), b"secret");
+    /// assert_eq!(result.unwrap_err(), ParseError::ConnectionClosed);
+    ///
+    /// // Empty lines before the request line are skipped
+    /// let result = Request::parse(&b"\r\n\r\n"[..], &Limits::default(), b"secret");
+    /// assert_eq!(result.unwrap_err(), ParseError::ConnectionClosed);
+    ///
+    /// // So is one closed before the headers are complete
+    /// let result = Request::parse(&b"GET /page HTTP/1.1\r\nHost: x"[..], &Limits::default(), b"secret");
+    /// assert_eq!(result.unwrap_err(), ParseError::ConnectionClosed);
+    ///
+    /// let result = Request::parse(&b"GET\r\n\r\n"[..], &Limits::default(), b"secret");
+    /// assert_eq!(result.unwrap_err(), ParseError::MalformedRequestLine);
+    ///
+    /// // So is a method that is not a valid token
+    /// let result = Request::parse(&b"G(E)T / HTTP/1.1\r\n\r\n"[..], &Limits::default(), b"secret");
+    /// assert_eq!(result.unwrap_err(), ParseError::MalformedRequestLine);
+    ///
+    /// // A missing version or a doubled space is not guessed at
+    /// let result = Request::parse(&b"GET /page\r\nHost: x\r\n\r\n"[..], &Limits::default(), b"secret");
+    /// assert_eq!(result.unwrap_err(), ParseError::MalformedRequestLine);
+    /// let result = Request::parse(&b"GET  /page HTTP/1.1\r\n\r\n"[..], &Limits::default(), b"secret");
+    /// assert_eq!(result.unwrap_err(), ParseError::MalformedRequestLine);
+    ///
+    /// // Neither is a line that is not UTF-8
+    /// let result = Request::parse(&b"GET / HTTP/1.1\r\nX-Name: \xff\r\n\r\n"[..], &Limits::default(), b"secret");
+    /// assert_eq!(result.unwrap_err(), ParseError::MalformedHeader);
+    /// ```
+    ///
+    /// Oversized bodies are rejected from their `Content-Length`, before
+    /// anything is read:
+    ///
+    /// ```rust
+    /// use rwf::connection::request::{Limits, ParseError, Request};
+    ///
+    /// let limits = Limits { max_body_size: 4, ..Limits::default() };
+    /// let raw = b"POST / HTTP/1.1\r\nContent-Length: 5\r\n\r\nhello";
+    /// let result = Request::parse(&raw[..], &limits, b"secret");
+    /// assert_eq!(result.unwrap_err(), ParseError::BodyTooLarge);
+    ///
+    /// let raw = b"POST / HTTP/1.1\r\nContent-Length: 5\r\nExpect: 100-continue\r\n\r\n";
+    /// let result = Request::parse(&raw[..], &limits, b"secret");
+    /// assert_eq!(result.unwrap_err(), ParseError::ExpectationFailed);
+    /// ```
+    ///
+    /// A body cut short of its `Content-Length` is an error too:
+    ///
+    /// ```rust
+    /// use rwf::connection::request::{Limits, ParseError, Request};
+    ///
+    /// let raw = b"POST / HTTP/1.1\r\nContent-Length: 100\r\n\r\nonly 10 b.";
+    /// let result = Request::parse(&raw[..], &Limits::default(), b"secret");
+    /// assert_eq!(result.unwrap_err(), ParseError::TruncatedBody);
+    /// ```
+    ///
+    /// Bodies whose end is ambiguous are rejected rather than guessed, as a
+    /// wrong guess would run part of the body as the next request:
+    ///
+    /// ```rust
+    /// use rwf::connection::request::{Limits, ParseError, Request};
+    ///
+    /// let raw = b"POST / HTTP/1.1\r\nContent-Length: 5x\r\n\r\nhello";
+    /// let result = Request::parse(&raw[..], &Limits::default(), b"secret");
+    /// assert_eq!(result.unwrap_err(), ParseError::InvalidContentLength);
+    ///
+    /// let raw = b"POST / HTTP/1.1\r\nContent-Length: 5\r\nContent-Length: 6\r\n\r\nhello!";
+    /// let result = Request::parse(&raw[..], &Limits::default(), b"secret");
+    /// assert_eq!(result.unwrap_err(), ParseError::InvalidContentLength);
+    ///
+    /// // Repeating the same length is fine
+    /// let raw = b"POST / HTTP/1.1\r\nContent-Length: 5\r\nContent-Length: 5\r\n\r\nhello";
+    /// let request = Request::parse(&raw[..], &Limits::default(), b"secret").unwrap();
+    /// assert_eq!(request.body_str(), Some("hello"));
+    ///
+    /// let raw = b"POST / HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n5\r\nhello\r\n0\r\n\r\n";
+    /// let result = Request::parse(&raw[..], &Limits::default(), b"secret");
+    /// assert_eq!(result.unwrap_err(), ParseError::UnsupportedTransferEncoding);
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// Panics if the operating system's random number generator is
+    /// unavailable while generating the request ID, see [`Request::id`].
+    pub fn parse(stream: impl Read, limits: &Limits, secret: &[u8]) -> Result<Self, ParseError> {
+        Self::parse_with(BufReader::new(stream), limits, secret, |_| Ok(()))
+    }
+
+    /// Parses a request from a connection, answering `Expect: 100-continue`.
+    ///
+    /// Behaves like [`Request::parse`], but writes `HTTP/1.1 100 Continue`
+    /// to the stream before reading the body of a request that expects it, so
+    /// the client goes on to send the body.
+    ///
+    /// Only the bytes of this request are consumed from `stream`, so the
+    /// next request on a persistent connection can be read from it.
+    pub(crate) fn parse_interactive(
+        stream: &mut (impl BufRead + Write),
+        limits: &Limits,
+        secret: &[u8],
+    ) -> Result<Self, ParseError> {
+        Self::parse_with(stream, limits, secret, |stream| {
+            stream.write_all(b"HTTP/1.1 100 Continue\r\n\r\n")?;
+            stream.flush()
+        })
+    }
+
+    /// Parses a request, calling `send_continue` with the stream before
+    /// reading the body of an `Expect: 100-continue` request.
+    fn parse_with<R: BufRead>(
+        stream: R,
+        limits: &Limits,
+        secret: &[u8],
+        send_continue: impl FnOnce(&mut R) -> io::Result<()>,
+    ) -> Result<Self, ParseError> {
+        let (header, body, body_reader) = Self::get_data(stream, limits, send_continue)?;
+        let request_line = header.lines().next().unwrap_or_default();
+        let tokens: Vec<&str> = request_line.split(' ').collect();
+        if tokens.len() != 3 || tokens.contains(&"") || !Method::is_token(tokens[0]) {
+            return Err(ParseError::MalformedRequestLine);
+        }
+        let method = Self::http_method(request_line);
+        let version = Self::http_version(request_line);
+        let target = Self::http_path(request_line);
+        // The asterisk-form target addresses the server itself, not a
+        // resource, and is only defined for OPTIONS
+        if target == "*" && method != Method::OPTIONS {
+            return Err(ParseError::MalformedRequestLine);
+        }
+        let (path, query) = match target.split_once('?') {
+            Some((path, query)) => (path.to_string(), from_forms(query)),
+            None => (target, HashMap::new()),
+        };
+        let path = Self::normalize_path(&path).ok_or(ParseError::InvalidPath)?;
+        let session = Self::get_session(&header, secret);
+        let headers = Headers::parse(&header);
+        let id = Self::request_id(&headers);
+        let host = headers.get("host").and_then(Self::host_name);
+        let cookies = Self::parse_cookies(&headers);
+        let mut request = Self {
+            resource: path,
+            query,
+            method,
+            version,
+            header,
+            headers,
+            body,
+            session,
+            id,
+            raw: None,
+            host,
+            remote_addr: None,
+            trust_proxy: false,
+            tls: false,
+            params: HashMap::new(),
+            matched_route: None,
+            cookies,
+            deadline: None,
+            body_reader,
+            templates: None,
+        };
+        request.apply_method_override();
+        Ok(request)
+    }
+
+    /// Copies the request without its body, for callbacks running once the
+    /// handler has consumed the request.
+    pub(crate) fn without_body(&self) -> Self {
+        Self {
+            resource: self.resource.clone(),
+            query: self.query.clone(),
+            method: self.method.clone(),
+            version: self.version,
+            header: self.header.clone(),
+            headers: self.headers.clone(),
+            body: None,
+            session: self.session.clone(),
+            id: self.id.clone(),
+            raw: None,
+            host: self.host.clone(),
+            remote_addr: self.remote_addr,
+            trust_proxy: self.trust_proxy,
+            tls: self.tls,
+            params: self.params.clone(),
+            matched_route: self.matched_route.clone(),
+            cookies: self.cookies.clone(),
+            deadline: self.deadline,
+            body_reader: None,
+            templates: self.templates.clone(),
+        }
+    }
+
+    /// Replaces the method of a `POST` request with the one it asks for.
+    ///
+    /// HTML forms can only send `GET` and `POST`, so a `POST` request may carry
+    /// the method it stands for in an `X-HTTP-Method-Override` header or, for
+    /// form submissions, a `_method` field. The header takes precedence. Only
+    /// `PUT`, `PATCH` and `DELETE` may be requested, case-insensitively; other values
+    /// and other request methods are left alone.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use rwf::connection::{method::Method, request::{Limits, Request}};
+    ///
+    /// let raw = b"POST /posts/7 HTTP/1.1\r\n\
+    ///             Content-Type: application/x-www-form-urlencoded\r\n\
+    ///             Content-Length: 14\r\n\
+    ///             \r\n\
+    ///             _method=delete";
+    /// let request = Request::parse(&raw[..], &Limits::default(), b"secret").unwrap();
+    /// assert_eq!(request.method, Method::DELETE);
+    ///
+    /// let raw = b"POST /posts/7 HTTP/1.1\r\nX-HTTP-Method-Override: PUT\r\n\r\n";
+    /// let request = Request::parse(&raw[..], &Limits::default(), b"secret").unwrap();
+    /// assert_eq!(request.method, Method::PUT);
+    ///
+    /// let raw = b"POST /posts/7 HTTP/1.1\r\nX-HTTP-Method-Override: patch\r\n\r\n";
+    /// let request = Request::parse(&raw[..], &Limits::default(), b"secret").unwrap();
+    /// assert_eq!(request.method, Method::PATCH);
+    ///
+    /// // Only POST can be overridden, and only to PUT, PATCH or DELETE
+    /// let raw = b"GET /posts/7 HTTP/1.1\r\nX-HTTP-Method-Override: DELETE\r\n\r\n";
+    /// let request = Request::parse(&raw[..], &Limits::default(), b"secret").unwrap();
+    /// assert_eq!(request.method, Method::GET);
+    ///
+    /// let raw = b"POST /posts/7 HTTP/1.1\r\nX-HTTP-Method-Override: HEAD\r\n\r\n";
+    /// let request = Request::parse(&raw[..], &Limits::default(), b"secret").unwrap();
+    /// assert_eq!(request.method, Method::POST);
+    /// ```
+    fn apply_method_override(&mut self) {
+        if self.method != Method::POST {
+            return;
+        }
+        let requested = self
+            .header("x-http-method-override")
+            .map(str::to_string)
+            .or_else(|| self.form()?.remove("_method"));
+        self.method = match requested
+            .map(|method| method.to_ascii_uppercase())
+            .as_deref()
+        {
+            Some("PUT") => Method::PUT,
+            Some("PATCH") => Method::PATCH,
+            Some("DELETE") => Method::DELETE,
+            _ => return,
+        };
+    }
+
+    /// Extracts the host name from a `Host` header value.
+    ///
+    /// The name is lowercased and the port dropped, keeping the brackets of
+    /// an IPv6 address. Empty values give `None`.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use rwf::connection::request::{Limits, Request};
+    ///
+    /// let host = |value: &str| {
+    ///     let raw = format!("GET / HTTP/1.1\r\nHost: {value}\r\n\r\n");
+    ///     Request::parse(raw.as_bytes(), &Limits::default(), b"secret").unwrap().host
+    /// };
+    ///
+    /// assert_eq!(host("A.Example.com").as_deref(), Some("a.example.com"));
+    /// assert_eq!(host("example.com:8080").as_deref(), Some("example.com"));
+    /// assert_eq!(host("[::1]:8080").as_deref(), Some("[::1]"));
+    ///
+    /// let raw = b"GET / HTTP/1.0\r\n\r\n";
+    /// assert_eq!(Request::parse(&raw[..], &Limits::default(), b"secret").unwrap().host, None);
+    /// ```
+    pub(crate) fn host_name(value: &str) -> Option<String> {
+        let value = value.trim();
+        let name = match value.strip_prefix('[') {
+            Some(rest) => format!("[{}]", rest.split(']').next()?),
+            None => value.split(':').next()?.to_string(),
+        };
+        (!name.is_empty() && name != "[]").then(|| name.to_ascii_lowercase())
+    }
+
+    /// Picks the ID of a request.
+    ///
+    /// The client's `X-Request-Id` is reused when it is at most 128 visible
+    /// ASCII characters long, so IDs assigned by a proxy carry through to the
+    /// logs. Otherwise a random 64-bit ID rendered as hex is generated.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the operating system's random number generator is unavailable.
+    fn request_id(headers: &Headers) -> String {
+        if let Some(id) = headers.get("x-request-id") {
+            if !id.is_empty() && id.len() <= 128 && id.bytes().all(|b| b.is_ascii_graphic()) {
+                return id.to_string();
+            }
+        }
+        let mut bytes = [0u8; 8];
+        getrandom::fill(&mut bytes).expect("failed to generate a request ID");
+        session::to_hex(&bytes)
+    }
+
+    /// Looks up a query string parameter and parses it into `T`.
+    ///
+    /// Query parameters are split from the path while parsing, so
+    /// `/items?page=2` is routed as `/items` with `query["page"] == "2"`.
+    ///
+    /// # Arguments
+    ///
+    /// * `key` - The query parameter name
+    ///
+    /// # Returns
+    ///
+    /// * `Some(T)` - The parsed value
+    /// * `None` - If the parameter is missing or cannot be parsed as `T`
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use rwf::connection::request::{Limits, Request};
+    ///
+    /// let raw = b"GET /items?page=2&sort=name HTTP/1.1\r\n\r\n";
+    /// let request = Request::parse(&raw[..], &Limits::default(), b"secret").unwrap();
+    ///
+    /// assert_eq!(request.resource, "/items");
+    /// assert_eq!(request.query_as::<u32>("page"), Some(2));
+    /// assert_eq!(request.query_as::<u32>("sort"), None);
+    /// assert_eq!(request.query_as::<u32>("limit").unwrap_or(20), 20);
+    /// ```
+    pub fn query_as<T: FromStr>(&self, key: &str) -> Option<T> {
+        self.query.get(key)?.parse().ok()
+    }
+
+    /// Returns the first value of the given header, if present.
+    ///
+    /// Header names are matched case-insensitively. Use `headers.get_all` to
+    /// read every value of a repeated header.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The header name
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use rwf::connection::request::{Limits, Request};
+    ///
+    /// let raw = b"GET / HTTP/1.1\r\nAccept: text/html\r\nAccept: application/json\r\n\r\n";
+    /// let request = Request::parse(&raw[..], &Limits::default(), b"secret").unwrap();
+    ///
+    /// assert_eq!(request.header("accept"), Some("text/html"));
+    /// assert_eq!(request.headers.get_all("Accept"), ["text/html", "application/json"]);
+    /// ```
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers.get(name)
+    }
+
+    /// Returns the value of the given cookie, if the client sent it.
+    ///
+    /// Cookies from every `Cookie` header are considered. Names are matched
+    /// exactly, as cookie names are case-sensitive, and the first cookie of
+    /// a repeated name wins. Values are returned as sent, without the double
+    /// quotes they may be wrapped in. The session cookie is read like any
+    /// other, still signed; the server checks it on its own.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The cookie name
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use rwf::connection::request::{Limits, Request};
+    ///
+    /// let raw = b"GET / HTTP/1.1\r\nCookie: theme=dark; lang=\"en\"\r\nCookie: theme=light\r\n\r\n";
+    /// let request = Request::parse(&raw[..], &Limits::default(), b"secret").unwrap();
+    ///
+    /// assert_eq!(request.cookie("theme"), Some("dark"));
+    /// assert_eq!(request.cookie("lang"), Some("en"));
+    /// assert_eq!(request.cookie("Theme"), None);
+    /// assert_eq!(request.cookie("missing"), None);
+    /// ```
+    pub fn cookie(&self, name: &str) -> Option<&str> {
+        self.cookies.get(name).map(String::as_str)
+    }
+
+    /// Collects the cookies of every `Cookie` header, keeping the first
+    /// value of a repeated name.
+    fn parse_cookies(headers: &Headers) -> HashMap<String, String> {
+        let mut cookies = HashMap::new();
+        let pairs = headers
+            .get_all("cookie")
+            .iter()
+            .flat_map(|line| line.split(';'))
+            .filter_map(|cookie| cookie.trim().split_once('='));
+        for (name, value) in pairs {
+            let value = value.trim();
+            let value = value
+                .strip_prefix('"')
+                .and_then(|value| value.strip_suffix('"'))
+                .unwrap_or(value);
+            cookies
+                .entry(name.trim().to_string())
+                .or_insert_with(|| value.to_string());
+        }
+        cookies
+    }
+
+    /// Returns whether the request is `OPTIONS *`, asking about the server
+    /// as a whole rather than one of its resources.
+    ///
+    /// The server answers these requests itself, with `200 OK` and an `Allow`
+    /// header listing every supported method, `TRACE` included when
+    /// `Server::allow_trace` is enabled, without matching any route.
+    /// Any other method with a `*` target is rejected as
+    /// `ParseError::MalformedRequestLine`.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use rwf::connection::request::{Limits, ParseError, Request};
+    ///
+    /// let raw = b"OPTIONS * HTTP/1.1\r\nHost: localhost\r\n\r\n";
+    /// let request = Request::parse(&raw[..], &Limits::default(), b"secret").unwrap();
+    /// assert!(request.is_asterisk_form());
+    /// assert_eq!(request.resource, "*");
+    ///
+    /// let raw = b"GET * HTTP/1.1\r\n\r\n";
+    /// let result = Request::parse(&raw[..], &Limits::default(), b"secret");
+    /// assert_eq!(result.unwrap_err(), ParseError::MalformedRequestLine);
+    /// ```
+    ///
+    /// A running server answers the probe without a route:
+    ///
+    /// ```rust
+    /// use rwf::Server;
+    ///
+    /// let server = Server::build("127.0.0.1:0").unwrap();
+    /// # let addr = rwf::test_support::serve(server);
+    /// # let send = |request: &str| rwf::test_support::send(addr, request);
+    ///
+    /// let response = send("OPTIONS * HTTP/1.1\r\nConnection: close\r\n\r\n");
+    /// assert!(response.starts_with("HTTP/1.1 200 OK"));
+    /// assert!(response.contains("Allow: GET, HEAD, POST, PUT, PATCH, DELETE, OPTIONS\r\n"));
+    /// assert!(!response.contains("Set-Cookie"));
+    /// ```
+    pub fn is_asterisk_form(&self) -> bool {
+        self.method == Method::OPTIONS && self.resource == "*"
+    }
+
+    /// Returns whether the client wants the connection kept open after the response.
+    ///
+    /// A `Connection: close` or `Connection: keep-alive` header decides;
+    /// otherwise HTTP/1.1 connections stay open and HTTP/1.0 ones are closed.
+    /// HTTP/1.0 requests need no `Host` header.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use rwf::connection::request::{Limits, Request};
+    ///
+    /// let parse = |raw: &[u8]| Request::parse(raw, &Limits::default(), b"secret").unwrap();
+    ///
+    /// assert!(parse(b"GET / HTTP/1.1\r\nHost: localhost\r\n\r\n").keep_alive());
+    /// assert!(!parse(b"GET / HTTP/1.1\r\nConnection: close\r\n\r\n").keep_alive());
+    /// assert!(!parse(b"GET / HTTP/1.0\r\n\r\n").keep_alive());
+    /// assert!(parse(b"GET / HTTP/1.0\r\nConnection: Keep-Alive\r\n\r\n").keep_alive());
+    /// ```
+    pub fn keep_alive(&self) -> bool {
+        let has_token = |token: &str| {
+            self.headers
+                .get_all("connection")
+                .iter()
+                .flat_map(|value| value.split(','))
+                .any(|option| option.trim().eq_ignore_ascii_case(token))
+        };
+        if has_token("close") {
+            false
+        } else {
+            has_token("keep-alive") || self.version.keeps_alive()
+        }
+    }
+
+    /// Returns the address of the client that sent the request.
+    ///
+    /// Set by the server from the connection's peer address. `None` if the
+    /// request was parsed outside the server or the peer address could not be
+    /// determined.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use rwf::connection::request::{Limits, Request};
+    ///
+    /// let raw = b"GET / HTTP/1.1\r\n\r\n";
+    /// let mut request = Request::parse(&raw[..], &Limits::default(), b"secret").unwrap();
+    /// assert_eq!(request.remote_addr(), None);
+    ///
+    /// request.remote_addr = Some("203.0.113.7:51234".parse().unwrap());
+    /// assert_eq!(request.remote_addr().unwrap().ip().to_string(), "203.0.113.7");
+    /// ```
+    pub fn remote_addr(&self) -> Option<SocketAddr> {
+        self.remote_addr
+    }
+
+    /// Returns how much time is left before the request's deadline.
+    ///
+    /// The server sets the deadline once the request has been read, as long
+    /// after that as the configured timeout (`ServerBuilder::timeout`, 5
+    /// seconds by default), the time a connection may stay silent before it
+    /// is dropped. Handlers doing slow work, such as calling other services,
+    /// can check it to give up early instead of answering a client that has
+    /// likely given up already.
+    ///
+    /// # Returns
+    ///
+    /// * `Some(Duration)` - The time left, zero once the deadline has passed
+    /// * `None` - If the request has no deadline, e.g. because it was parsed
+    ///   outside the server
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use rwf::Server;
+    /// use std::time::Duration;
+    ///
+    /// let mut server = Server::builder()
+    ///     .timeout(Duration::from_secs(30))
+    ///     .build("127.0.0.1:0")
+    ///     .unwrap();
+    /// server.route("/report", |req, _session| {
+    ///     assert!(req.deadline.is_some());
+    ///     match req.time_remaining() {
+    ///         Some(left) if left > Duration::from_secs(10) => "Full report".to_string(),
+    ///         _ => "Summary".to_string(),
+    ///     }
+    /// });
+    /// # let addr = rwf::test_support::serve(server);
+    /// # let send = |request: &str| rwf::test_support::send(addr, request);
+    ///
+    /// let response = send("GET /report HTTP/1.1\r\nConnection: close\r\n\r\n");
+    /// assert!(response.ends_with("Full report"));
+    ///
+    /// // A request parsed outside the server has no deadline
+    /// use rwf::connection::request::{Limits, Request};
+    /// let request = Request::parse(&b"GET / HTTP/1.1\r\n\r\n"[..], &Limits::default(), b"").unwrap();
+    /// assert_eq!(request.time_remaining(), None);
+    /// ```
+    pub fn time_remaining(&self) -> Option<Duration> {
+        self.deadline
+            .map(|deadline| deadline.saturating_duration_since(Instant::now()))
+    }
+
+    /// Renders a template from the directory registered with
+    /// `Server::templates`, HTML-escaping the values.
+    ///
+    /// See [`Templates::render`] for the placeholders and errors.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(String)` - The rendered template
+    /// * `Err(TemplateError::NoDirectory)` - If the server has no template
+    ///   directory, or the request was parsed outside a server
+    /// * `Err(TemplateError)` - If the template is missing or unreadable
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use rwf::{connection::response::HttpError, Server};
+    /// use std::fs;
+    ///
+    /// let dir = std::env::temp_dir().join(format!("rwf-request-templates-{}", std::process::id()));
+    /// fs::create_dir_all(&dir).unwrap();
+    /// fs::write(dir.join("hello.html"), "<p>Hello $name</p>").unwrap();
+    ///
+    /// let mut server = Server::build("127.0.0.1:0").unwrap();
+    /// server.templates(dir.to_str().unwrap()).unwrap();
+    /// server.route("/hello", |req, _session| -> Result<String, HttpError> {
+    ///     Ok(req.render("hello.html", &[("name", req.query["name"].as_str())])?)
+    /// });
+    /// server.route("/missing", |req, _session| -> Result<String, HttpError> {
+    ///     Ok(req.render("missing.html", &[])?)
+    /// });
+    /// # let addr = rwf::test_support::serve(server);
+    /// # let get = |path: &str| rwf::test_support::get(addr, path);
+    ///
+    /// assert!(get("/hello?name=Ferris").ends_with("<p>Hello Ferris</p>"));
+    /// let response = get("/hello?name=%3Cscript%3E");
+    /// assert!(response.ends_with("<p>Hello &lt;script&gt;</p>"));
+    /// assert!(get("/missing").starts_with("HTTP/1.1 500"));
+    /// ```
+    pub fn render(&self, name: &str, context: &[(&str, &str)]) -> Result<String, TemplateError> {
+        self.templates
+            .as_deref()
+            .ok_or(TemplateError::NoDirectory)?
+            .render(name, context)
+    }
+
+    /// Renders a template like [`Request::render`], inserting the values as
+    /// given.
+    ///
+    /// Only for values known to be safe HTML, see [`Templates::render_raw`].
+    pub fn render_raw(
+        &self,
+        name: &str,
+        context: &[(&str, &str)],
+    ) -> Result<String, TemplateError> {
+        self.templates
+            .as_deref()
+            .ok_or(TemplateError::NoDirectory)?
+            .render_raw(name, context)
+    }
+
+    /// Returns the IP address of the client that originated the request.
+    ///
+    /// When the server trusts a proxy (see `Server::trust_proxy`), the address
+    /// is read from the last entry of the last `X-Forwarded-For` header, or
+    /// else from the `for=` parameter of the last element of the last
+    /// `Forwarded` header. Otherwise, or when that entry is not a valid
+    /// address, the socket peer address is used.
+    ///
+    /// The last entry is the one added by the proxy in front of the server,
+    /// e.g. nginx's `proxy_add_x_forwarded_for`: proxies append the address
+    /// they received the request from to whatever the client sent, so the
+    /// entries before it are client-controlled. Only trust proxy headers when
+    /// every request reaches the server through such a proxy: otherwise any
+    /// client can send these headers and claim an arbitrary address.
+    ///
+    /// # Returns
+    ///
+    /// * `Option<IpAddr>` - The client's IP address, if known
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use rwf::Server;
+    ///
+    /// let mut server = Server::build("127.0.0.1:0").unwrap();
+    /// server.trust_proxy(true);
+    /// server.route("/", |req, _session| req.client_ip().unwrap().to_string());
+    /// # let addr = rwf::test_support::serve(server);
+    /// # let get = |headers: &str| {
+    /// #     rwf::test_support::send(addr, &format!("GET / HTTP/1.1\r\n{headers}Connection: close\r\n\r\n"))
+    /// # };
+    ///
+    /// assert!(get("").ends_with("127.0.0.1"));
+    /// assert!(get("X-Forwarded-For: 203.0.113.7\r\n").ends_with("203.0.113.7"));
+    /// // A spoofed entry sent by the client is followed by the proxy's own
+    /// assert!(get("X-Forwarded-For: 10.0.0.1, 203.0.113.7\r\n").ends_with("203.0.113.7"));
+    /// assert!(get("X-Forwarded-For: 10.0.0.1\r\nX-Forwarded-For: 203.0.113.7\r\n").ends_with("203.0.113.7"));
+    /// assert!(get("Forwarded: for=10.0.0.1, for=\"[2001:db8::1]:443\"\r\n").ends_with("2001:db8::1"));
+    /// ```
+    pub fn client_ip(&self) -> Option<IpAddr> {
+        let last = |name: &str| {
+            self.headers
+                .get_all(name)
+                .last()
+                .and_then(|value| value.rsplit(',').next())
+                .map(str::to_string)
+        };
+        let forwarded = || {
+            last("x-forwarded-for")
+                .and_then(|entry| parse_forwarded_ip(&entry))
+                .or_else(|| {
+                    last("forwarded")?
+                        .split(';')
+                        .filter_map(|pair| pair.trim().split_once('='))
+                        .find(|(name, _)| name.eq_ignore_ascii_case("for"))
+                        .and_then(|(_, value)| parse_forwarded_ip(value))
+                })
+        };
+
+        self.trust_proxy
+            .then(forwarded)
+            .flatten()
+            .or_else(|| self.remote_addr.map(|addr| addr.ip()))
+    }
+
+    /// Returns whether the request reached the client-facing server over HTTPS.
+    ///
+    /// True for connections accepted by a server built with `Server::build_tls`.
+    /// When the server trusts a proxy (see `Server::trust_proxy`), the scheme
+    /// the proxy received is read from the first entry of the
+    /// `X-Forwarded-Proto` header, or else from the `proto=` parameter of the
+    /// `Forwarded` header. Plain connections without such headers are not
+    /// secure.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use rwf::Server;
+    ///
+    /// let mut server = Server::build("127.0.0.1:0").unwrap();
+    /// server.trust_proxy(true);
+    /// server.route("/", |req, _session| req.scheme().to_string());
+    /// # let addr = rwf::test_support::serve(server);
+    /// # let get = |headers: &str| {
+    /// #     rwf::test_support::send(addr, &format!("GET / HTTP/1.1\r\n{headers}Connection: close\r\n\r\n"))
+    /// # };
+    ///
+    /// assert!(get("").ends_with("http"));
+    /// assert!(get("X-Forwarded-Proto: https\r\n").ends_with("https"));
+    /// assert!(get("X-Forwarded-Proto: HTTPS, http\r\n").ends_with("https"));
+    /// assert!(get("Forwarded: for=203.0.113.7;proto=https\r\n").ends_with("https"));
+    /// assert!(get("X-Forwarded-Proto: http\r\n").ends_with("http"));
+    /// ```
+    pub fn is_secure(&self) -> bool {
+        let forwarded = || {
+            self.header("x-forwarded-proto")
+                .and_then(|value| value.split(',').next())
+                .map(str::to_string)
+                .or_else(|| {
+                    self.header("forwarded")?
+                        .split([',', ';'])
+                        .filter_map(|pair| pair.trim().split_once('='))
+                        .find(|(name, _)| name.eq_ignore_ascii_case("proto"))
+                        .map(|(_, value)| value.to_string())
+                })
+        };
+
+        self.tls
+            || self.trust_proxy
+                && forwarded().is_some_and(|proto| {
+                    proto.trim().trim_matches('"').eq_ignore_ascii_case("https")
+                })
+    }
+
+    /// Returns the scheme the request was made with, `https` or `http`.
+    ///
+    /// See [`Request::is_secure`] for how it is determined.
+    pub fn scheme(&self) -> &'static str {
+        if self.is_secure() {
+            "https"
+        } else {
+            "http"
+        }
+    }
 
-/// Represents an HTTP request with all its components.
-///
-/// Stores parsed information from an HTTP request including the method,
-/// resource path, headers, body, and session information.
-#[derive(Debug)]
-pub struct Request {
-    pub resource: String,
-    pub method: Method,
-    pub header: String,
-    pub body: Option<String>,
-    pub session: Option<usize>,
-}
+    /// Returns the media types listed in the `Accept` header, most preferred first.
+    ///
+    /// Types are ordered by their `q` weight (defaulting to `1`), keeping the
+    /// header order between equal weights. Types with `q=0` are excluded.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use rwf::connection::request::{Limits, Request};
+    ///
+    /// let raw = b"GET / HTTP/1.1\r\nAccept: application/json;q=0.9, text/html\r\n\r\n";
+    /// let request = Request::parse(&raw[..], &Limits::default(), b"secret").unwrap();
+    ///
+    /// assert_eq!(request.accepted_types(), ["text/html", "application/json"]);
+    /// ```
+    pub fn accepted_types(&self) -> Vec<String> {
+        let mut types: Vec<(String, f32)> = self
+            .headers
+            .get_all("accept")
+            .iter()
+            .flat_map(|value| value.split(','))
+            .filter_map(|entry| {
+                let mut parts = entry.split(';');
+                let media_type = parts.next()?.trim().to_ascii_lowercase();
+                let quality = parts
+                    .filter_map(|param| param.trim().split_once('='))
+                    .find(|(name, _)| name.trim() == "q")
+                    .map_or(1.0, |(_, q)| q.trim().parse().unwrap_or(0.0));
+                (!media_type.is_empty() && quality > 0.0).then_some((media_type, quality))
+            })
+            .collect();
 
-impl Request {
-    /// Parses a TCP stream into a structured HTTP request.
+        types.sort_by(|(_, a), (_, b)| b.total_cmp(a));
+        types
+            .into_iter()
+            .map(|(media_type, _)| media_type)
+            .collect()
+    }
+
+    /// Parses the request's `Authorization` header.
+    ///
+    /// # Returns
+    ///
+    /// * `Option<Auth>` - The credentials the client sent, or `None` if the
+    ///   header is missing or malformed. Unknown schemes are returned as
+    ///   `Auth::Other`.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use rwf::connection::{auth::Auth, request::{Limits, Request}};
+    ///
+    /// let raw = b"GET / HTTP/1.1\r\nAuthorization: Basic dXNlcjpwYXNz\r\n\r\n";
+    /// let request = Request::parse(&raw[..], &Limits::default(), b"secret").unwrap();
+    /// assert_eq!(
+    ///     request.authorization(),
+    ///     Some(Auth::Basic { user: "user".to_string(), pass: "pass".to_string() })
+    /// );
+    ///
+    /// let raw = b"GET / HTTP/1.1\r\nAuthorization: Bearer t0k3n\r\n\r\n";
+    /// let request = Request::parse(&raw[..], &Limits::default(), b"secret").unwrap();
+    /// assert_eq!(request.authorization(), Some(Auth::Bearer("t0k3n".to_string())));
+    /// ```
+    pub fn authorization(&self) -> Option<Auth> {
+        Auth::parse(self.header("authorization")?)
+    }
+
+    /// Checks whether the client accepts the given media type.
+    ///
+    /// Wildcards such as `*/*` and `text/*` in the `Accept` header match any
+    /// type they cover. A request without an `Accept` header accepts everything.
     ///
     /// # Arguments
     ///
-    /// * `stream` - The TCP stream containing the raw HTTP request
+    /// * `mime` - The media type to check, e.g. `application/json`
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use rwf::connection::request::{Limits, Request};
+    ///
+    /// let raw = b"GET / HTTP/1.1\r\nAccept: text/*, application/json;q=0\r\n\r\n";
+    /// let request = Request::parse(&raw[..], &Limits::default(), b"secret").unwrap();
+    ///
+    /// assert!(request.accepts("text/html"));
+    /// assert!(!request.accepts("application/json"));
+    /// ```
+    pub fn accepts(&self, mime: &str) -> bool {
+        if !self.headers.contains("accept") {
+            return true;
+        }
+
+        let mime = mime.to_ascii_lowercase();
+        let (kind, _) = mime.split_once('/').unwrap_or((&mime, ""));
+        self.accepted_types()
+            .iter()
+            .any(|accepted| match accepted.split_once('/') {
+                Some(("*", "*")) => true,
+                Some((accepted_kind, "*")) => accepted_kind == kind,
+                _ => *accepted == mime,
+            })
+    }
+
+    /// Checks whether the request was made by a script rather than a page load.
+    ///
+    /// A request counts as AJAX if it carries `X-Requested-With:
+    /// XMLHttpRequest`, as jQuery and similar libraries send, or if JSON is
+    /// the most preferred type of its `Accept` header, as `fetch` callers
+    /// usually ask for. Browsers loading a page prefer `text/html`, so
+    /// handlers can answer the same route with JSON or a full page.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use rwf::connection::request::{Limits, Request};
+    ///
+    /// let is_ajax = |headers: &str| {
+    ///     let raw = format!("GET /items HTTP/1.1\r\n{headers}\r\n");
+    ///     Request::parse(raw.as_bytes(), &Limits::default(), b"secret").unwrap().is_ajax()
+    /// };
+    ///
+    /// assert!(is_ajax("X-Requested-With: XMLHttpRequest\r\n"));
+    /// assert!(is_ajax("Accept: application/json\r\n"));
+    /// assert!(is_ajax("Accept: text/html;q=0.5, application/json\r\n"));
+    /// assert!(!is_ajax(""));
+    /// assert!(!is_ajax("Accept: text/html,application/xhtml+xml,*/*;q=0.8\r\n"));
+    /// ```
+    pub fn is_ajax(&self) -> bool {
+        let requested_with = self
+            .header("x-requested-with")
+            .is_some_and(|value| value.trim().eq_ignore_ascii_case("XMLHttpRequest"));
+        requested_with
+            || self
+                .accepted_types()
+                .first()
+                .is_some_and(|preferred| preferred == "application/json")
+    }
+
+    /// Returns the body as a borrowed string, if the request has one and it
+    /// is valid UTF-8.
+    ///
+    /// Saves matching on [`Request::body`] and converting it, e.g. to pass it
+    /// to [`from_forms`].
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use rwf::connection::request::{from_forms, Limits, Request};
+    ///
+    /// let raw = b"POST /search HTTP/1.1\r\nContent-Length: 11\r\n\r\nq=rust&n=10";
+    /// let request = Request::parse(&raw[..], &Limits::default(), b"secret").unwrap();
+    /// assert_eq!(request.body_str(), Some("q=rust&n=10"));
+    /// assert_eq!(from_forms(request.body_str().unwrap())["q"], "rust");
+    /// assert_eq!(request.body_bytes(), Some(&b"q=rust&n=10"[..]));
+    ///
+    /// let raw = b"GET / HTTP/1.1\r\n\r\n";
+    /// let request = Request::parse(&raw[..], &Limits::default(), b"secret").unwrap();
+    /// assert_eq!(request.body_str(), None);
+    ///
+    /// // Binary bodies are kept as sent, but are no string
+    /// let raw = b"POST /upload HTTP/1.1\r\nContent-Length: 4\r\n\r\n\xff\xfe\x00\x80";
+    /// let request = Request::parse(&raw[..], &Limits::default(), b"secret").unwrap();
+    /// assert_eq!(request.body_bytes(), Some(&[0xff, 0xfe, 0x00, 0x80][..]));
+    /// assert_eq!(request.body_str(), None);
+    /// ```
+    pub fn body_str(&self) -> Option<&str> {
+        self.body_bytes()
+            .and_then(|body| std::str::from_utf8(body).ok())
+    }
+
+    /// Returns the body as borrowed bytes, exactly as sent, if the request
+    /// has one.
+    pub fn body_bytes(&self) -> Option<&[u8]> {
+        self.body.as_deref()
+    }
+
+    /// Parses the body as URL-encoded form data.
     ///
     /// # Returns
     ///
-    /// A new `Request` instance containing the parsed data
+    /// * `Some(HashMap)` - The decoded form fields, see [`from_forms`]
+    /// * `None` - If there is no body or the `Content-Type` is not
+    ///   `application/x-www-form-urlencoded`
     ///
     /// # Example
     ///
     /// ```rust
-    /// let request = Request::parse(&tcp_stream);
+    /// use rwf::connection::request::{Limits, Request};
+    ///
+    /// let raw = b"POST /login HTTP/1.1\r\n\
+    ///             Content-Type: application/x-www-form-urlencoded\r\n\
+    ///             Content-Length: 24\r\n\
+    ///             \r\n\
+    ///             user=john+doe&pass=a%26b";
+    /// let request = Request::parse(&raw[..], &Limits::default(), b"secret").unwrap();
+    ///
+    /// let form = request.form().unwrap();
+    /// assert_eq!(form["user"], "john doe");
+    /// assert_eq!(form["pass"], "a&b");
     /// ```
+    pub fn form(&self) -> Option<HashMap<String, String>> {
+        if !self
+            .content_type()
+            .is_some_and(|content_type| content_type.is_form())
+        {
+            return None;
+        }
+        self.body_str().map(from_forms)
+    }
+
+    /// Parses the body as URL-encoded form data, keeping every value of
+    /// repeated fields.
     ///
-    /// # Panics
+    /// # Returns
     ///
-    /// Panics if:
-    /// - Header parsing fails
-    /// - Content-Length parsing fails
-    /// - UTF-8 conversion fails
-    pub fn parse(stream: &TcpStream) -> Self {
-        let (header, body) = Self::get_data(stream);
-        let method = Self::http_method(&header);
-        let path = Self::http_path(&header);
-        let session = Self::get_session(&header);
-        Self {
-            resource: path,
-            method,
-            header,
-            body,
-            session,
+    /// * `Some(HashMap)` - The decoded form fields, see [`from_forms_multi`]
+    /// * `None` - If there is no body or the `Content-Type` is not
+    ///   `application/x-www-form-urlencoded`
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use rwf::connection::request::{Limits, Request};
+    ///
+    /// let raw = b"POST /filter HTTP/1.1\r\n\
+    ///             Content-Type: application/x-www-form-urlencoded\r\n\
+    ///             Content-Length: 15\r\n\
+    ///             \r\n\
+    ///             color=red&color";
+    /// let request = Request::parse(&raw[..], &Limits::default(), b"secret").unwrap();
+    /// assert_eq!(request.form_multi().unwrap()["color"], ["red", ""]);
+    /// ```
+    pub fn form_multi(&self) -> Option<HashMap<String, Vec<String>>> {
+        if !self
+            .content_type()
+            .is_some_and(|content_type| content_type.is_form())
+        {
+            return None;
         }
+        self.body_str().map(from_forms_multi)
     }
 
-    /// Extracts header and body data from a TCP stream.
+    /// Returns the query string parameters and form fields as one map.
     ///
-    /// # Arguments
+    /// Lets a handler read its input the same way whether the form was sent
+    /// with `GET`, in the query string, or with `POST`, in a URL-encoded
+    /// body. Both are decoded by [`from_forms`]. A field present in both
+    /// takes its value from the body, which the client submitted
+    /// deliberately, while the query string may be left over from the URL.
+    /// If the body is not form data, only the query string is returned.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use rwf::connection::request::{Limits, Request};
+    ///
+    /// let parse = |raw: &str| Request::parse(raw.as_bytes(), &Limits::default(), b"secret").unwrap();
+    /// let post = |target: &str, body: &str| {
+    ///     parse(&format!(
+    ///         "POST {target} HTTP/1.1\r\n\
+    ///          Content-Type: application/x-www-form-urlencoded\r\n\
+    ///          Content-Length: {}\r\n\r\n{body}",
+    ///         body.len()
+    ///     ))
+    /// };
+    ///
+    /// // Query only
+    /// let params = parse("GET /search?q=rust&page=2 HTTP/1.1\r\n\r\n").params_merged();
+    /// assert_eq!((params["q"].as_str(), params["page"].as_str()), ("rust", "2"));
+    ///
+    /// // Body only
+    /// let params = post("/search", "q=rust+lang").params_merged();
+    /// assert_eq!(params["q"], "rust lang");
+    ///
+    /// // Both, the body taking precedence
+    /// let params = post("/search?q=old&page=2", "q=new").params_merged();
+    /// assert_eq!((params["q"].as_str(), params["page"].as_str()), ("new", "2"));
+    /// ```
+    pub fn params_merged(&self) -> HashMap<String, String> {
+        let mut params = self.query.clone();
+        params.extend(self.form().unwrap_or_default());
+        params
+    }
+
+    /// Deserializes the body as JSON into `T`.
+    ///
+    /// The `Content-Type` must be `application/json` or another JSON media
+    /// type such as `application/problem+json`.
     ///
-    /// * `stream` - The TCP stream to read from
+    /// Available with the `serde` feature.
     ///
     /// # Returns
     ///
-    /// A tuple containing:
-    /// - The header as a String
-    /// - The body as an Option<String>
+    /// * `Ok(T)` - The deserialized body
+    /// * `Err(BodyError)` - If the content type is wrong, the body is empty,
+    ///   or it cannot be deserialized into `T`
     ///
-    /// # Panics
+    /// # Example
+    ///
+    /// ```rust
+    /// use rwf::connection::request::{BodyError, Limits, Request};
+    /// use rwf::connection::response::Response;
+    /// use serde::Deserialize;
+    ///
+    /// #[derive(Debug, Deserialize, PartialEq)]
+    /// struct Login {
+    ///     user: String,
+    ///     remember: bool,
+    /// }
+    ///
+    /// let raw = b"POST /login HTTP/1.1\r\n\
+    ///             Content-Type: application/json\r\n\
+    ///             Content-Length: 31\r\n\
+    ///             \r\n\
+    ///             {\"user\":\"john\",\"remember\":true}";
+    /// let request = Request::parse(&raw[..], &Limits::default(), b"secret").unwrap();
+    /// assert_eq!(
+    ///     request.json::<Login>().unwrap(),
+    ///     Login { user: "john".to_string(), remember: true }
+    /// );
+    ///
+    /// // Errors convert into a ready-made response
+    /// let raw = b"POST /login HTTP/1.1\r\nContent-Type: text/plain\r\nContent-Length: 2\r\n\r\n{}";
+    /// let request = Request::parse(&raw[..], &Limits::default(), b"secret").unwrap();
+    /// let error = request.json::<Login>().unwrap_err();
+    /// assert!(matches!(error, BodyError::WrongContentType));
+    /// assert_eq!(Response::from(error).status, 415);
+    /// ```
+    #[cfg(feature = "serde")]
+    pub fn json<T: serde::de::DeserializeOwned>(&self) -> Result<T, BodyError> {
+        if !self
+            .content_type()
+            .is_some_and(|content_type| content_type.is_json())
+        {
+            return Err(BodyError::WrongContentType);
+        }
+        match self.body.as_deref() {
+            None | Some([]) => Err(BodyError::EmptyBody),
+            Some(body) => serde_json::from_slice(body).map_err(BodyError::Deserialize),
+        }
+    }
+
+    /// Takes the reader of a body too large to be kept in memory.
+    ///
+    /// Bodies larger than `Limits::max_buffered_body`, set with
+    /// `Server::max_buffered_body`, are written to a temporary file while the
+    /// request is read and leave [`Request::body`] as `None`, so `form` and
+    /// `json` do not see them either. Smaller bodies stay in `body` as usual.
+    ///
+    /// # Returns
+    ///
+    /// * `Some(BodyReader)` - The spooled body, on the first call
+    /// * `None` - If the body was kept in memory, or was already taken
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use rwf::connection::request::{Limits, Request};
+    /// use std::io::Read;
+    ///
+    /// let limits = Limits { max_buffered_body: Some(4), ..Limits::default() };
+    /// let raw = b"POST /upload HTTP/1.1\r\nContent-Length: 11\r\n\r\nhello world";
+    /// let mut request = Request::parse(&raw[..], &limits, b"secret").unwrap();
+    ///
+    /// let mut body = String::new();
+    /// request.body_reader().unwrap().read_to_string(&mut body).unwrap();
+    /// assert_eq!(body, "hello world");
+    /// assert!(request.body.is_none() && request.body_reader().is_none());
+    /// ```
+    pub fn body_reader(&mut self) -> Option<BodyReader> {
+        self.body_reader.take()
+    }
+
+    /// Parses the `Content-Type` header into its media type and parameters.
+    ///
+    /// `form`, `form_multi` and `json` check the media type through it.
+    ///
+    /// # Returns
     ///
-    /// Panics if:
-    /// - Reading from the stream fails
-    /// - Content-Length parsing fails
-    /// - UTF-8 conversion fails
-    fn get_data(stream: &TcpStream) -> (String, Option<String>) {
-        let mut buffer = BufReader::new(stream);
+    /// * `Some(ContentType)` - The parsed header, see [`ContentType::parse`]
+    /// * `None` - If the header is missing or empty
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use rwf::connection::request::{Limits, Request};
+    ///
+    /// let parse = |content_type: &str| {
+    ///     let raw = format!("POST /upload HTTP/1.1\r\nContent-Type: {content_type}\r\n\r\n");
+    ///     Request::parse(raw.as_bytes(), &Limits::default(), b"secret").unwrap()
+    /// };
+    ///
+    /// let json = parse("application/json; charset=utf-8").content_type().unwrap();
+    /// assert_eq!(json.media_type, "application/json");
+    /// assert_eq!(json.charset(), Some("utf-8"));
+    /// assert!(json.is_json());
+    ///
+    /// let multipart = parse("multipart/form-data; boundary=\"----rwf; boundary\"").content_type().unwrap();
+    /// assert!(multipart.is_multipart());
+    /// assert_eq!(multipart.boundary(), Some("----rwf; boundary"));
+    ///
+    /// let raw = b"GET / HTTP/1.1\r\n\r\n";
+    /// assert!(Request::parse(&raw[..], &Limits::default(), b"secret").unwrap().content_type().is_none());
+    /// ```
+    pub fn content_type(&self) -> Option<ContentType> {
+        self.header("content-type").and_then(ContentType::parse)
+    }
+
+    /// Extracts header and body data from a stream.
+    ///
+    /// # Arguments
+    ///
+    /// * `stream` - The stream to read from
+    /// * `limits` - The size limits the request must respect
+    /// * `send_continue` - Called with the stream before reading the body when
+    ///   the client sent `Expect: 100-continue`
+    ///
+    /// # Returns
+    ///
+    /// * `Ok((String, Option<Vec<u8>>, Option<BodyReader>))` - The header
+    ///   section with its lines joined by `\n`, the body if one was sent and
+    ///   buffered, and the spooled body instead if it exceeds
+    ///   `Limits::max_buffered_body`
+    /// * `Err(ParseError)` - If the stream ends early, a limit is exceeded or
+    ///   the end of the body is ambiguous, as listed on [`Request::parse`]
+    fn get_data<R: BufRead>(
+        mut buffer: R,
+        limits: &Limits,
+        send_continue: impl FnOnce(&mut R) -> io::Result<()>,
+    ) -> Result<RequestData, ParseError> {
         let mut header = Vec::new();
-        let mut content_length = 0;
+        let mut content_length: Option<usize> = None;
+        let mut invalid_length = false;
+        let mut transfer_encoding = false;
+        let mut expect_continue = false;
+        let mut content_encoding = String::new();
+        let mut header_size = 0;
 
         loop {
             let mut line = String::new();
-            buffer.read_line(&mut line).unwrap();
+            let request_line = header.is_empty();
+            let remaining = if request_line {
+                // Enough of the request line to tell an overlong target apart
+                limits.max_uri_length.saturating_add(REQUEST_LINE_SLACK)
+            } else {
+                limits.max_header_size.saturating_sub(header_size)
+            };
+            let read = (&mut buffer)
+                .take(remaining as u64 + 1)
+                .read_line(&mut line)
+                .map_err(|e| match e.kind() {
+                    io::ErrorKind::InvalidData if request_line => ParseError::MalformedRequestLine,
+                    io::ErrorKind::InvalidData => ParseError::MalformedHeader,
+                    kind => ParseError::Io(kind),
+                })?;
+            // A header block cut short is never served as if it were whole
+            if read == 0 {
+                return Err(ParseError::ConnectionClosed);
+            }
             let line = line.trim_end();
+            if request_line && !line.is_empty() {
+                // The request line only answers to the target length limit
+                let target_too_long = line
+                    .split_whitespace()
+                    .nth(1)
+                    .is_some_and(|target| target.len() > limits.max_uri_length);
+                if target_too_long || read > remaining {
+                    return Err(ParseError::UriTooLong);
+                }
+            } else {
+                header_size += read;
+                if header_size > limits.max_header_size {
+                    return Err(ParseError::HeadersTooLarge);
+                }
+            }
 
+            // Clients may send empty lines between two requests
+            if line.is_empty() && header.is_empty() {
+                continue;
+            }
             if line.is_empty() {
                 break;
             }
 
-            if line.starts_with("Content-Length: ") {
-                content_length = line.split(": ").nth(1).unwrap_or("0").parse().unwrap_or(0);
+            if let Some((name, value)) = line.split_once(':') {
+                let (name, value) = (name.trim(), value.trim());
+                if name.eq_ignore_ascii_case("content-length") {
+                    // Only plain digits: `+5`, `5x` or `5, 5` do not frame a body
+                    let parsed = Some(value)
+                        .filter(|value| {
+                            !value.is_empty() && value.bytes().all(|b| b.is_ascii_digit())
+                        })
+                        .and_then(|value| value.parse::<usize>().ok());
+                    match (parsed, content_length) {
+                        (None, _) => invalid_length = true,
+                        (Some(length), Some(previous)) if length != previous => {
+                            invalid_length = true
+                        }
+                        (Some(length), _) => content_length = Some(length),
+                    }
+                } else if name.eq_ignore_ascii_case("transfer-encoding") {
+                    transfer_encoding = true;
+                } else if name.eq_ignore_ascii_case("expect") {
+                    expect_continue = value.eq_ignore_ascii_case("100-continue");
+                } else if name.eq_ignore_ascii_case("content-encoding") {
+                    content_encoding = value.to_ascii_lowercase();
+                }
             }
 
             header.push(line.to_string());
+            if header.len() > limits.max_headers + 1 {
+                return Err(ParseError::HeadersTooLarge);
+            }
+        }
+
+        if transfer_encoding {
+            return Err(ParseError::UnsupportedTransferEncoding);
+        }
+        if invalid_length {
+            return Err(ParseError::InvalidContentLength);
+        }
+        let content_length = content_length.unwrap_or(0);
+        if content_length > limits.max_body_size {
+            return Err(if expect_continue {
+                ParseError::ExpectationFailed
+            } else {
+                ParseError::BodyTooLarge
+            });
         }
 
         let header_str = header.join("\n");
         if content_length == 0 {
-            return (header_str, None);
+            return Ok((header_str, None, None));
+        }
+
+        if expect_continue {
+            // A client that misses the interim response sends the body after
+            // a timeout anyway, so a failed write is not fatal
+            let _ = send_continue(&mut buffer);
+        }
+
+        if limits
+            .max_buffered_body
+            .is_some_and(|max| content_length > max)
+        {
+            let reader =
+                BodyReader::spool(&mut buffer, content_length as u64).map_err(body_error)?;
+            return Ok((header_str, None, Some(reader)));
         }
 
         let mut body = vec![0u8; content_length];
-        buffer.read_exact(&mut body).unwrap();
-        let body_str = String::from_utf8_lossy(&body).to_string();
-        (header_str, Some(body_str))
+        buffer.read_exact(&mut body).map_err(body_error)?;
+        #[cfg(feature = "compression")]
+        let body = Self::decode_body(body, &content_encoding, limits.max_body_size)?;
+        #[cfg(not(feature = "compression"))]
+        let _ = content_encoding;
+        Ok((header_str, Some(body), None))
     }
 
-    /// Extracts the HTTP method from the header string.
+    /// Decompresses a body sent with `Content-Encoding: gzip` or `deflate`.
     ///
-    /// # Arguments
+    /// Bodies with any other encoding are returned unchanged. The
+    /// decompressed size is limited to `max_body_size`, so a small compressed
+    /// body cannot expand into an unbounded allocation.
     ///
-    /// * `header_str` - The raw header string
+    /// Available with the `compression` feature.
     ///
     /// # Returns
     ///
-    /// The parsed HTTP method
+    /// * `Ok(Vec<u8>)` - The decoded body
+    /// * `Err(ParseError::BodyTooLarge)` - If the decompressed body exceeds `max_body_size`
+    /// * `Err(ParseError::InvalidEncoding)` - If the body is not valid compressed data
     ///
-    /// # Panics
+    /// # Example
+    ///
+    /// ```rust
+    /// use flate2::{write::GzEncoder, Compression};
+    /// use rwf::connection::request::{Limits, ParseError, Request};
+    /// use std::io::Write;
+    ///
+    /// let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+    /// encoder.write_all(b"name=ferris&lang=rust").unwrap();
+    /// let compressed = encoder.finish().unwrap();
+    ///
+    /// let mut raw = format!(
+    ///     "POST /users HTTP/1.1\r\n\
+    ///      Content-Type: application/x-www-form-urlencoded\r\n\
+    ///      Content-Encoding: gzip\r\n\
+    ///      Content-Length: {}\r\n\r\n",
+    ///     compressed.len()
+    /// )
+    /// .into_bytes();
+    /// raw.extend_from_slice(&compressed);
+    ///
+    /// let request = Request::parse(&raw[..], &Limits::default(), b"secret").unwrap();
+    /// assert_eq!(request.body_str(), Some("name=ferris&lang=rust"));
+    /// assert_eq!(request.form().unwrap()["name"], "ferris");
+    ///
+    /// // The limit applies to the decompressed size
+    /// let mut encoder = GzEncoder::new(Vec::new(), Compression::best());
+    /// encoder.write_all(&[b'a'; 10_000]).unwrap();
+    /// let bomb = encoder.finish().unwrap();
+    /// assert!(bomb.len() < 100);
     ///
-    /// Panics if the method string is invalid or missing
-    fn http_method(header_str: &str) -> Method {
-        Method::from_string(header_str.split(" ").next().unwrap())
+    /// let mut raw = format!("POST / HTTP/1.1\r\nContent-Encoding: gzip\r\nContent-Length: {}\r\n\r\n", bomb.len())
+    ///     .into_bytes();
+    /// raw.extend_from_slice(&bomb);
+    /// let limits = Limits { max_body_size: 100, ..Limits::default() };
+    /// let result = Request::parse(&raw[..], &limits, b"secret");
+    /// assert_eq!(result.unwrap_err(), ParseError::BodyTooLarge);
+    /// ```
+    #[cfg(feature = "compression")]
+    fn decode_body(
+        body: Vec<u8>,
+        encoding: &str,
+        max_body_size: usize,
+    ) -> Result<Vec<u8>, ParseError> {
+        use flate2::read::{DeflateDecoder, GzDecoder, ZlibDecoder};
+
+        let limit = max_body_size as u64 + 1;
+        let mut decoded = Vec::new();
+        let result = match encoding {
+            "gzip" | "x-gzip" => GzDecoder::new(&body[..])
+                .take(limit)
+                .read_to_end(&mut decoded),
+            // `deflate` is meant to be zlib wrapped, but some clients send raw deflate data
+            "deflate" => ZlibDecoder::new(&body[..])
+                .take(limit)
+                .read_to_end(&mut decoded)
+                .or_else(|_| {
+                    decoded.clear();
+                    DeflateDecoder::new(&body[..])
+                        .take(limit)
+                        .read_to_end(&mut decoded)
+                }),
+            _ => return Ok(body),
+        };
+
+        result.map_err(|_| ParseError::InvalidEncoding)?;
+        if decoded.len() > max_body_size {
+            return Err(ParseError::BodyTooLarge);
+        }
+        Ok(decoded)
     }
 
-    /// Extracts the request path from the header string.
+    /// Extracts the HTTP method from the request line.
     ///
     /// # Arguments
     ///
-    /// * `header_str` - The raw header string
+    /// * `request_line` - The first line of the request
     ///
     /// # Returns
     ///
-    /// The request path as a String
+    /// The parsed HTTP method. The request line is expected to have been
+    /// checked by `parse_with` already.
+    fn http_method(request_line: &str) -> Method {
+        Method::from_string(request_line.split(' ').next().unwrap_or_default())
+    }
+
+    /// Extracts the request target from the request line.
     ///
-    /// # Panics
+    /// # Arguments
+    ///
+    /// * `request_line` - The first line of the request
+    ///
+    /// # Returns
+    ///
+    /// The request target, path and query string, as a String. The request
+    /// line is expected to have been checked by `parse_with` already.
+    fn http_path(request_line: &str) -> String {
+        request_line
+            .split(' ')
+            .nth(1)
+            .unwrap_or_default()
+            .to_string()
+    }
+
+    /// Brings a request path into its canonical form before routing.
+    ///
+    /// Repeated slashes are collapsed, `.` segments are dropped and `..`
+    /// segments remove the segment before them. A trailing slash is kept, so
+    /// directory paths still end in `/`. Targets not starting with `/`, such
+    /// as `*`, are returned unchanged.
+    ///
+    /// # Returns
+    ///
+    /// * `Some(String)` - The normalized path
+    /// * `None` - If a `..` segment would climb above the root
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use rwf::connection::request::{Limits, ParseError, Request};
+    ///
+    /// let resource = |target: &str| {
+    ///     let raw = format!("GET {target} HTTP/1.1\r\n\r\n");
+    ///     Request::parse(raw.as_bytes(), &Limits::default(), b"secret").map(|r| r.resource)
+    /// };
     ///
-    /// Panics if the path is missing from the header
-    fn http_path(header_str: &str) -> String {
-        header_str.split(" ").skip(1).next().unwrap().to_string()
+    /// assert_eq!(resource("//a///b"), Ok("/a/b".to_string()));
+    /// assert_eq!(resource("/a/./b"), Ok("/a/b".to_string()));
+    /// assert_eq!(resource("/a/../b"), Ok("/b".to_string()));
+    /// assert_eq!(resource("/docs//?page=2"), Ok("/docs/".to_string()));
+    /// assert_eq!(resource("/a/../../etc/passwd"), Err(ParseError::InvalidPath));
+    /// ```
+    fn normalize_path(path: &str) -> Option<String> {
+        if !path.starts_with('/') {
+            return Some(path.to_string());
+        }
+
+        let mut segments: Vec<&str> = Vec::new();
+        let mut directory = path.ends_with('/');
+        for segment in path.split('/').filter(|segment| !segment.is_empty()) {
+            directory = matches!(segment, "." | "..");
+            match segment {
+                "." => {}
+                ".." => {
+                    segments.pop()?;
+                }
+                _ => segments.push(segment),
+            }
+        }
+        directory |= path.ends_with('/');
+
+        let mut normalized = format!("/{}", segments.join("/"));
+        if directory && !segments.is_empty() {
+            normalized.push('/');
+        }
+        Some(normalized)
+    }
+
+    /// Extracts the HTTP version from the request line.
+    ///
+    /// The request line is expected to have been checked by `parse_with`
+    /// already.
+    fn http_version(request_line: &str) -> Version {
+        Version::from_string(request_line.split(' ').nth(2).unwrap_or_default())
     }
 
-    /// Extracts the session ID from the header string.
+    /// Extracts the session ID from the `session_id` cookie in the header string.
+    ///
+    /// The cookie must carry a valid signature for `secret`, as produced by
+    /// [`session::sign`]; tampered or unsigned cookies are ignored.
     ///
     /// # Arguments
     ///
     /// * `header_str` - The raw header string
+    /// * `secret` - The server secret session cookies are signed with
     ///
     /// # Returns
     ///
-    /// * `Option<usize>` - The session ID if present
-    pub fn get_session(header_str: &str) -> Option<usize> {
-        for line in header_str.lines() {
-            if line.contains("session_id") {
-                return line
-                    .split("=")
-                    .nth(1)
-                    .map(|id| id.parse().unwrap_or(100000000000));
-            }
-        }
-        None
+    /// * `Option<String>` - The session ID if present and correctly signed
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use rwf::connection::{request::Request, session};
+    ///
+    /// let header = format!("GET / HTTP/1.1\nCookie: theme=dark; session_id={}", session::sign("a1b2", b"key"));
+    /// assert_eq!(Request::get_session(&header, b"key"), Some("a1b2".to_string()));
+    /// assert_eq!(Request::get_session("GET / HTTP/1.1\nCookie: session_id=a1b2", b"key"), None);
+    /// ```
+    pub fn get_session(header_str: &str, secret: &[u8]) -> Option<String> {
+        let (_, value) = header_str
+            .lines()
+            .skip(1)
+            .filter_map(|line| line.split_once(':'))
+            .filter(|(name, _)| name.trim().eq_ignore_ascii_case("cookie"))
+            .flat_map(|(_, cookies)| cookies.split(';'))
+            .filter_map(|cookie| cookie.trim().split_once('='))
+            .find(|(name, _)| *name == "session_id")?;
+
+        session::verify(value, secret)
+    }
+}
+
+/// Converts an error raised while reading a body, telling a connection
+/// closed too early apart from other failures.
+fn body_error(error: io::Error) -> ParseError {
+    match error.kind() {
+        io::ErrorKind::UnexpectedEof => ParseError::TruncatedBody,
+        kind => ParseError::Io(kind),
     }
 }
 
+/// Parses a client address from a proxy header value.
+///
+/// Accepts bare IPv4/IPv6 addresses, addresses with a port, bracketed IPv6
+/// addresses and quoted values, as found in `X-Forwarded-For` and `Forwarded`.
+fn parse_forwarded_ip(value: &str) -> Option<IpAddr> {
+    let value = value.trim().trim_matches('"');
+    if let Some(rest) = value.strip_prefix('[') {
+        return rest.split(']').next()?.parse().ok();
+    }
+    value
+        .parse()
+        .ok()
+        .or_else(|| value.parse::<SocketAddr>().ok().map(|addr| addr.ip()))
+}
+
 /// Parses form data from a request body string.
 ///
-/// Converts URL-encoded form data into a key-value HashMap.
+/// Converts URL-encoded form data into a key-value HashMap, decoding `+` and
+/// `%XX` escapes in both keys and values. Fields without a `=` get an empty
+/// value, and empty fields are skipped.
 ///
 /// # Arguments
 ///
@@ -200,19 +2131,107 @@ impl Request {
 /// # Example
 ///
 /// ```rust
+/// use rwf::connection::request::from_forms;
+///
 /// let body = "username=john&password=secret".to_string();
 /// let form_data = from_forms(&body);
 /// assert_eq!(form_data.get("username"), Some(&"john".to_string()));
+///
+/// let form_data = from_forms("name=John+Doe&city=S%C3%A3o%20Paulo");
+/// assert_eq!(form_data.get("name"), Some(&"John Doe".to_string()));
+/// assert_eq!(form_data.get("city"), Some(&"São Paulo".to_string()));
+/// ```
+pub fn from_forms(body: &str) -> HashMap<String, String> {
+    form_pairs(body).collect()
+}
+
+/// Parses form data from a request body string, keeping every value of
+/// repeated fields.
+///
+/// Decodes fields like [`from_forms`], but collects the values of a field
+/// sent several times, as multi-select controls and checkboxes do, in the
+/// order they appear. A trailing `[]` is stripped from field names, so
+/// `items[]=x&items[]=y` is read as `items` with two values.
+///
+/// # Arguments
+///
+/// * `body` - The form data string (e.g., "tag=a&tag=b")
+///
+/// # Returns
+///
+/// A HashMap containing every value of each field
+///
+/// # Example
+///
+/// ```rust
+/// use rwf::connection::request::from_forms_multi;
+///
+/// let form_data = from_forms_multi("tag=a&tag=b&items[]=x&items[]=y&name=John");
+/// assert_eq!(form_data["tag"], ["a", "b"]);
+/// assert_eq!(form_data["items"], ["x", "y"]);
+/// assert_eq!(form_data["name"], ["John"]);
 /// ```
+pub fn from_forms_multi(body: &str) -> HashMap<String, Vec<String>> {
+    let mut fields: HashMap<String, Vec<String>> = HashMap::new();
+    for (key, value) in form_pairs(body) {
+        let key = match key.strip_suffix("[]") {
+            Some(stripped) => stripped.to_string(),
+            None => key,
+        };
+        fields.entry(key).or_default().push(value);
+    }
+    fields
+}
+
+/// Splits form data into decoded key and value pairs, in the order they appear.
+fn form_pairs(body: &str) -> impl Iterator<Item = (String, String)> + '_ {
+    body.split("&").filter(|pair| !pair.is_empty()).map(|pair| {
+        let (key, value) = pair.split_once("=").unwrap_or((pair, ""));
+        (url_decode(key), url_decode(value))
+    })
+}
+
+/// Decodes a URL-encoded string.
 ///
-/// # Panics
+/// `+` becomes a space and `%XX` escapes become the byte they encode. Invalid
+/// escapes are kept as-is, and invalid UTF-8 is replaced with `U+FFFD`.
 ///
-/// Panics if the form data is malformed
-pub fn from_forms(body: &String) -> HashMap<String, String> {
-    body.split("&")
-        .map(|pair| {
-            let parts: Vec<_> = pair.split("=").collect();
-            (parts[0].to_string(), parts[1].to_string())
-        })
-        .collect()
+/// # Arguments
+///
+/// * `encoded` - The URL-encoded string
+///
+/// # Example
+///
+/// ```rust
+/// use rwf::connection::request::url_decode;
+///
+/// assert_eq!(url_decode("a+b%21"), "a b!");
+/// assert_eq!(url_decode("100%"), "100%");
+/// ```
+pub fn url_decode(encoded: &str) -> String {
+    let bytes = encoded.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+
+    while i < bytes.len() {
+        match bytes[i] {
+            b'+' => decoded.push(b' '),
+            b'%' => {
+                let byte = encoded
+                    .get(i + 1..i + 3)
+                    .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+                match byte {
+                    Some(byte) => {
+                        decoded.push(byte);
+                        i += 2;
+                    }
+                    None => decoded.push(b'%'),
+                }
+            }
+            byte => decoded.push(byte),
+        }
+        i += 1;
+    }
+
+    String::from_utf8_lossy(&decoded).to_string()
 }
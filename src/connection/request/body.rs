@@ -0,0 +1,133 @@
+//! # Request Body Module
+//!
+//! Provides `BodyReader`, giving handlers incremental access to large
+//! request bodies.
+//!
+//! Bodies larger than `Limits::max_buffered_body` are not collected into
+//! `Request::body`. They are copied in small chunks to a temporary file
+//! while the request is parsed, so at most one chunk is held in memory, and
+//! the handler reads them back through [`Request::body_reader`](super::Request::body_reader).
+//! The file is deleted once the reader, or a request whose reader was never
+//! taken, is dropped.
+//!
+//! ## Example
+//!
+//! ```rust
+//! use rwf::connection::request::{Limits, Request};
+//! use std::io::{repeat, Read};
+//!
+//! let size = 4 * 1024 * 1024;
+//! let head = format!("POST /upload HTTP/1.1\r\nContent-Length: {size}\r\n\r\n");
+//! let stream = head.as_bytes().chain(repeat(b'a').take(size as u64));
+//! let limits = Limits {
+//!     max_body_size: 16 * 1024 * 1024,
+//!     max_buffered_body: Some(64 * 1024),
+//!     ..Limits::default()
+//! };
+//!
+//! let mut request = Request::parse(stream, &limits, b"secret").unwrap();
+//! assert_eq!(request.body, None);
+//!
+//! let mut reader = request.body_reader().unwrap();
+//! assert_eq!(reader.len(), size as u64);
+//!
+//! // Read the upload in chunks, never holding it in memory as a whole
+//! let (mut chunk, mut total) = ([0u8; 8192], 0);
+//! loop {
+//!     let read = reader.read(&mut chunk).unwrap();
+//!     if read == 0 {
+//!         break;
+//!     }
+//!     assert!(chunk[..read].iter().all(|&byte| byte == b'a'));
+//!     total += read;
+//! }
+//! assert_eq!(total, size);
+//! ```
+
+use std::{
+    fmt,
+    fs::{self, File},
+    io::{self, BufReader, Read},
+    path::PathBuf,
+};
+
+/// A request body kept in a temporary file, read incrementally.
+///
+/// The bytes are the body as sent by the client: a `Content-Encoding` is
+/// not decoded.
+pub struct BodyReader {
+    /// The temporary file holding the body
+    file: BufReader<File>,
+    /// Where the temporary file lives, to delete it on drop
+    path: PathBuf,
+    /// The size of the body in bytes
+    len: u64,
+}
+
+impl BodyReader {
+    /// Copies exactly `len` bytes from `source` to a new temporary file.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(BodyReader)` - A reader positioned at the start of the body
+    /// * `Err(io::Error)` - If the file cannot be written, or the source ends
+    ///   before `len` bytes, with `io::ErrorKind::UnexpectedEof`
+    pub(crate) fn spool(source: &mut impl Read, len: u64) -> io::Result<Self> {
+        let mut id = [0u8; 16];
+        getrandom::fill(&mut id).map_err(|e| io::Error::other(e.to_string()))?;
+        let path = std::env::temp_dir().join(format!(
+            "rwf-body-{}",
+            crate::connection::session::to_hex(&id)
+        ));
+
+        let mut file = File::create_new(&path)?;
+        let copied = io::copy(&mut source.take(len), &mut file).and_then(|copied| {
+            if copied < len {
+                Err(io::ErrorKind::UnexpectedEof.into())
+            } else {
+                Ok(copied)
+            }
+        });
+        if let Err(e) = copied {
+            let _ = fs::remove_file(&path);
+            return Err(e);
+        }
+
+        let file = File::open(&path)?;
+        Ok(Self {
+            file: BufReader::new(file),
+            path,
+            len,
+        })
+    }
+
+    /// Returns the size of the body in bytes.
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
+    /// Returns `true` if the body is empty.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+}
+
+impl Read for BodyReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.file.read(buf)
+    }
+}
+
+impl Drop for BodyReader {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+impl fmt::Debug for BodyReader {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("BodyReader")
+            .field("len", &self.len)
+            .finish()
+    }
+}
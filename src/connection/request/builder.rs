@@ -0,0 +1,130 @@
+//! # Request Builder Module
+//!
+//! Builds requests in memory, so handlers can be tested as plain functions
+//! without a server or a socket.
+//!
+//! [`Request::builder`](super::Request::builder) starts from a method and a
+//! target, headers and a body are added with chained calls, and
+//! [`RequestBuilder::build`] parses the result exactly like a request read
+//! from a connection, so the query string, `Host` and `Content-Type` are
+//! available as usual. Path parameters, normally captured by the route, can
+//! be set with [`RequestBuilder::param`].
+//!
+//! ## Example
+//!
+//! ```rust
+//! use rwf::connection::{method::Method, request::Request, session::Session};
+//!
+//! fn greet(req: Request, session: &mut Session) -> String {
+//!     let visits = session.get::<u32>("visits").copied().unwrap_or(0) + 1;
+//!     session.add("visits".to_string(), visits);
+//!     format!("Hello, {}! Visit {visits}", req.param("name").unwrap_or("stranger"))
+//! }
+//!
+//! let request = Request::builder(Method::GET, "/hello/ferris?lang=en")
+//!     .header("Accept", "text/html")
+//!     .param("name", "ferris")
+//!     .build();
+//! assert_eq!(request.query["lang"], "en");
+//!
+//! let mut session = Session::new();
+//! assert_eq!(greet(request, &mut session), "Hello, ferris! Visit 1");
+//! assert_eq!(session.get::<u32>("visits"), Some(&1));
+//! ```
+
+use super::{Limits, Request};
+use crate::connection::method::Method;
+use std::{collections::HashMap, net::SocketAddr, time::Instant};
+
+/// Collects the parts of a request built in memory, see [`Request::builder`].
+#[derive(Debug, Clone)]
+pub struct RequestBuilder {
+    method: Method,
+    target: String,
+    headers: Vec<(String, String)>,
+    body: Vec<u8>,
+    params: HashMap<String, String>,
+    remote_addr: Option<SocketAddr>,
+    deadline: Option<Instant>,
+}
+
+impl RequestBuilder {
+    /// Starts a request with the given method and target, path and query string.
+    pub(crate) fn new(method: Method, target: &str) -> Self {
+        Self {
+            method,
+            target: target.to_string(),
+            headers: Vec::new(),
+            body: Vec::new(),
+            params: HashMap::new(),
+            remote_addr: None,
+            deadline: None,
+        }
+    }
+
+    /// Adds a header. Calling it again with the same name adds another value.
+    pub fn header(mut self, name: &str, value: &str) -> Self {
+        self.headers.push((name.to_string(), value.to_string()));
+        self
+    }
+
+    /// Sets the body, sent with a matching `Content-Length`.
+    pub fn body(mut self, body: impl Into<Vec<u8>>) -> Self {
+        self.body = body.into();
+        self
+    }
+
+    /// Sets a path parameter, as a route such as `/users/:id` would capture it.
+    pub fn param(mut self, name: &str, value: &str) -> Self {
+        self.params.insert(name.to_string(), value.to_string());
+        self
+    }
+
+    /// Sets the address of the client, as returned by `Request::remote_addr`.
+    pub fn remote_addr(mut self, addr: SocketAddr) -> Self {
+        self.remote_addr = Some(addr);
+        self
+    }
+
+    /// Sets the deadline, as returned by `Request::time_remaining`.
+    pub fn deadline(mut self, deadline: Instant) -> Self {
+        self.deadline = Some(deadline);
+        self
+    }
+
+    /// Builds the request.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the parts do not form a valid request, e.g. if the target
+    /// or a header contains a line break.
+    pub fn build(self) -> Request {
+        let mut raw = format!("{} {} HTTP/1.1\r\n", self.method, self.target).into_bytes();
+        for (name, value) in &self.headers {
+            raw.extend_from_slice(format!("{name}: {value}\r\n").as_bytes());
+        }
+        if !self.body.is_empty() {
+            raw.extend_from_slice(format!("Content-Length: {}\r\n", self.body.len()).as_bytes());
+        }
+        raw.extend_from_slice(b"\r\n");
+        raw.extend_from_slice(&self.body);
+
+        // Whatever the test builds is accepted, without the server's limits
+        let limits = Limits {
+            max_header_size: raw.len(),
+            max_headers: self.headers.len() + 1,
+            max_uri_length: self.target.len(),
+            max_body_size: self.body.len(),
+            max_buffered_body: None,
+        };
+        // No secret is known, so a session cookie never verifies
+        let mut request = match Request::parse(&raw[..], &limits, b"") {
+            Ok(request) => request,
+            Err(e) => panic!("Invalid request {} {}: {e:?}", self.method, self.target),
+        };
+        request.params = self.params;
+        request.remote_addr = self.remote_addr;
+        request.deadline = self.deadline;
+        request
+    }
+}
@@ -0,0 +1,116 @@
+//! # Content Type Module
+//!
+//! Parses `Content-Type` header values into their media type and parameters.
+//!
+//! A value such as `multipart/form-data; boundary="----b"` is split into
+//! the media type, lowercased, and its parameters, whose names are
+//! lowercased and whose quoted values are unquoted. `Request::content_type`
+//! parses the header of a request; [`ContentType::parse`] works on any value.
+//!
+//! ## Example
+//!
+//! ```rust
+//! use rwf::connection::request::ContentType;
+//!
+//! let content_type = ContentType::parse("Text/HTML; Charset=\"UTF-8\"").unwrap();
+//! assert_eq!(content_type.media_type, "text/html");
+//! assert_eq!(content_type.param("charset"), Some("UTF-8"));
+//! ```
+
+use std::collections::HashMap;
+
+/// A parsed `Content-Type` header value.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ContentType {
+    /// The lowercased media type, e.g. `application/json`
+    pub media_type: String,
+    /// The parameters keyed by lowercased name, e.g. `charset` or `boundary`
+    pub params: HashMap<String, String>,
+}
+
+impl ContentType {
+    /// Parses a `Content-Type` header value.
+    ///
+    /// Parameters without a `=` are ignored, and a `;` inside a quoted
+    /// value does not end it.
+    ///
+    /// # Arguments
+    ///
+    /// * `value` - The header value, e.g. `text/plain; charset=utf-8`
+    ///
+    /// # Returns
+    ///
+    /// * `Some(ContentType)` - The media type and its parameters
+    /// * `None` - If the value has no media type
+    pub fn parse(value: &str) -> Option<Self> {
+        let mut parts = split_unquoted(value).into_iter();
+        let media_type = parts.next()?.trim().to_ascii_lowercase();
+        if media_type.is_empty() {
+            return None;
+        }
+
+        let params = parts
+            .filter_map(|part| {
+                let (name, value) = part.split_once('=')?;
+                let value = value.trim();
+                let value = value
+                    .strip_prefix('"')
+                    .and_then(|value| value.strip_suffix('"'))
+                    .map_or_else(|| value.to_string(), |quoted| quoted.replace("\\\"", "\""));
+                Some((name.trim().to_ascii_lowercase(), value))
+            })
+            .collect();
+        Some(Self { media_type, params })
+    }
+
+    /// Returns the value of a parameter, matching its name case-insensitively.
+    pub fn param(&self, name: &str) -> Option<&str> {
+        self.params
+            .get(&name.to_ascii_lowercase())
+            .map(String::as_str)
+    }
+
+    /// Returns the `charset` parameter, if present.
+    pub fn charset(&self) -> Option<&str> {
+        self.param("charset")
+    }
+
+    /// Returns the `boundary` parameter of a multipart body, if present.
+    pub fn boundary(&self) -> Option<&str> {
+        self.param("boundary")
+    }
+
+    /// Returns whether the media type is `application/json` or ends in `+json`.
+    pub fn is_json(&self) -> bool {
+        self.media_type == "application/json" || self.media_type.ends_with("+json")
+    }
+
+    /// Returns whether the media type is `application/x-www-form-urlencoded`.
+    pub fn is_form(&self) -> bool {
+        self.media_type == "application/x-www-form-urlencoded"
+    }
+
+    /// Returns whether the media type is `multipart/form-data`.
+    pub fn is_multipart(&self) -> bool {
+        self.media_type == "multipart/form-data"
+    }
+}
+
+/// Splits a header value on the `;` separators outside quoted strings.
+fn split_unquoted(value: &str) -> Vec<&str> {
+    let (mut parts, mut start, mut quoted, mut escaped) = (Vec::new(), 0, false, false);
+    for (index, character) in value.char_indices() {
+        match character {
+            _ if escaped => escaped = false,
+            '\\' if quoted => escaped = true,
+            '"' => quoted = !quoted,
+            ';' if !quoted => {
+                parts.push(&value[start..index]);
+                start = index + 1;
+            }
+            _ => {}
+        }
+    }
+    parts.push(&value[start..]);
+    parts
+}
@@ -1,24 +1,1257 @@
 //! # Response Module
 //!
 //! Provides utilities for generating HTTP responses, including:
+//! - Building structured responses with status, headers and body
+//! - Serving files with the correct content type
+//! - Setting cookies
+//! - Streaming server-sent events
 //! - Formatting response content with headers
-//! - Generating error responses
-//! - Serving HTML content
+//! - Generating error responses, or ending handlers early with an `HttpError`
 //! - Handling redirects
 //!
 //! ## Example
 //!
 //! ```rust
-//! use response::{format_content, html};
+//! use rwf::connection::response::{format_content, Response};
 //!
 //! // Format a response with session
 //! let response = format_content(11, "Hello World", 12345);
 //!
-//! // Serve HTML content
-//! let html_content = html("templates/index.html");
+//! // Build a response with a custom header
+//! let response = Response::new(200, "Hello World").with_header("X-Powered-By", "rwf");
 //! ```
 //!
-use std::usize;
+use super::{
+    cookie::Cookie,
+    deferred::{Pending, Resolver},
+    mime,
+    request::Request,
+    sse::{EventStream, StreamBody},
+    Stream,
+};
+use std::{
+    borrow::Cow,
+    fmt, fs, io,
+    time::{Duration, UNIX_EPOCH},
+};
+
+/// Represents an HTTP response with a status code, headers and a body.
+///
+/// The body is stored as raw bytes, so binary content such as images can be
+/// returned from handlers. The `Content-Length` header is computed from the
+/// body when the response is serialized and must not be set manually.
+///
+/// Responses built with [`Response::event_stream`] have no body; their events
+/// are produced while the response is sent instead. Responses built with
+/// [`Response::deferred`] stand in for one resolved later.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Response {
+    pub status: u16,
+    /// The header fields, sent in this order. A name may appear more than
+    /// once, e.g. for several `Set-Cookie` headers
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+    stream: Option<StreamBody>,
+    pending: Option<Pending>,
+}
+
+impl Response {
+    /// Creates a new response with the given status code and body.
+    ///
+    /// # Arguments
+    ///
+    /// * `status` - The HTTP status code
+    /// * `body` - The response body, as a string or bytes
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use rwf::connection::response::Response;
+    ///
+    /// let response = Response::new(200, "Hello World");
+    /// assert_eq!(response.body, b"Hello World");
+    /// ```
+    pub fn new(status: u16, body: impl Into<Vec<u8>>) -> Self {
+        Self {
+            status,
+            headers: Vec::new(),
+            body: body.into(),
+            stream: None,
+            pending: None,
+        }
+    }
+
+    /// Creates a response with the given status code and no body.
+    ///
+    /// The response is sent with `Content-Length: 0` and nothing after the
+    /// header block, and no default `Content-Type` is added to it.
+    ///
+    /// # Arguments
+    ///
+    /// * `status` - The HTTP status code
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use rwf::connection::response::Response;
+    ///
+    /// let bytes = Response::status(202).to_bytes();
+    /// assert_eq!(bytes, b"HTTP/1.1 202 Accepted\r\nContent-Length: 0\r\n\r\n");
+    /// ```
+    pub fn status(status: u16) -> Self {
+        Self::new(status, Vec::new())
+    }
+
+    /// Creates a bodyless `204 No Content` response, e.g. for a successful
+    /// `DELETE`.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use rwf::connection::response::Response;
+    ///
+    /// let bytes = Response::no_content().to_bytes();
+    /// assert_eq!(bytes, b"HTTP/1.1 204 No Content\r\nContent-Length: 0\r\n\r\n");
+    /// ```
+    ///
+    /// Nothing follows the header block when it is sent by a server either:
+    ///
+    /// ```rust
+    /// use rwf::{connection::response::Response, Server};
+    ///
+    /// let mut server = Server::build("127.0.0.1:0").unwrap();
+    /// server.route("/item", |_req, _session| Response::no_content());
+    /// # let addr = rwf::test_support::serve(server);
+    /// # let send = |request: &str| rwf::test_support::send(addr, request);
+    ///
+    /// let response = send("GET /item HTTP/1.1\r\nConnection: close\r\n\r\n");
+    ///
+    /// let (head, body) = response.split_once("\r\n\r\n").unwrap();
+    /// assert!(head.starts_with("HTTP/1.1 204 No Content"));
+    /// assert!(head.contains("Content-Length: 0"));
+    /// assert!(!head.contains("Content-Type"));
+    /// assert!(body.is_empty());
+    /// ```
+    pub fn no_content() -> Self {
+        Self::status(204)
+    }
+
+    /// Creates a bodyless `304 Not Modified` response, telling the client its
+    /// cached copy is still valid.
+    ///
+    /// Validators such as `ETag` can be added with [`Response::with_header`].
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use rwf::connection::response::Response;
+    ///
+    /// let bytes = Response::not_modified().with_header("ETag", "\"abc\"").to_bytes();
+    /// assert_eq!(
+    ///     bytes,
+    ///     b"HTTP/1.1 304 Not Modified\r\nETag: \"abc\"\r\nContent-Length: 0\r\n\r\n"
+    /// );
+    /// ```
+    pub fn not_modified() -> Self {
+        Self::status(304)
+    }
+
+    /// Creates a `302 Found` response sending the client to `location`.
+    ///
+    /// The usual redirect for "go there for now", e.g. to a login page. See
+    /// [`Response::redirect_permanent`] and [`Response::redirect_see_other`]
+    /// for the other kinds.
+    ///
+    /// # Arguments
+    ///
+    /// * `location` - The destination, a path such as `/login` or a full URL
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use rwf::connection::response::Response;
+    ///
+    /// let response = Response::redirect("/login");
+    /// assert_eq!(response.status, 302);
+    /// assert_eq!(response.header("Location"), Some("/login"));
+    /// assert!(response.to_bytes().starts_with(b"HTTP/1.1 302 Found\r\nLocation: /login\r\n"));
+    /// ```
+    pub fn redirect(location: &str) -> Self {
+        Self::status(302).with_header("Location", location)
+    }
+
+    /// Creates a `301 Moved Permanently` response sending the client to
+    /// `location`.
+    ///
+    /// For canonical URLs: browsers and search engines remember the move and
+    /// go to the new location directly from then on.
+    ///
+    /// # Arguments
+    ///
+    /// * `location` - The destination, a path such as `/docs` or a full URL
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use rwf::connection::response::Response;
+    ///
+    /// let response = Response::redirect_permanent("https://example.com/docs");
+    /// assert_eq!(response.status, 301);
+    /// assert_eq!(response.header("Location"), Some("https://example.com/docs"));
+    /// assert!(response.to_bytes().starts_with(b"HTTP/1.1 301 Moved Permanently\r\n"));
+    /// ```
+    pub fn redirect_permanent(location: &str) -> Self {
+        Self::status(301).with_header("Location", location)
+    }
+
+    /// Creates a `303 See Other` response sending the client to `location`.
+    ///
+    /// For the POST-redirect-GET pattern: the client follows it with a `GET`
+    /// whatever the method of the original request, so reloading the
+    /// resulting page does not submit the form again.
+    ///
+    /// # Arguments
+    ///
+    /// * `location` - The destination, a path such as `/posts/7` or a full URL
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use rwf::connection::response::Response;
+    ///
+    /// let response = Response::redirect_see_other("/posts/7");
+    /// assert_eq!(response.status, 303);
+    /// assert_eq!(response.header("Location"), Some("/posts/7"));
+    /// assert!(response.to_bytes().starts_with(b"HTTP/1.1 303 See Other\r\n"));
+    /// ```
+    ///
+    /// Returned from a handler, the redirect is sent as is:
+    ///
+    /// ```rust
+    /// use rwf::{connection::{method::Method, response::Response}, Server};
+    ///
+    /// let mut server = Server::build("127.0.0.1:0").unwrap();
+    /// server.route_with(Method::POST, "/posts", "Creates a post", |_req, _session| {
+    ///     Response::redirect_see_other("/posts/7")
+    /// });
+    /// server.route("/old", |_req, _session| Response::redirect_permanent("/new"));
+    /// server.route("/account", |_req, _session| Response::redirect("/login"));
+    /// # let addr = rwf::test_support::serve(server);
+    /// # let send = |method: &str, path: &str| {
+    /// #     rwf::test_support::send(addr, &format!("{method} {path} HTTP/1.1\r\nContent-Length: 0\r\nConnection: close\r\n\r\n"))
+    /// # };
+    ///
+    /// let response = send("POST", "/posts");
+    /// assert!(response.starts_with("HTTP/1.1 303 See Other\r\n"));
+    /// assert!(response.contains("Location: /posts/7\r\n"));
+    ///
+    /// let response = send("GET", "/old");
+    /// assert!(response.starts_with("HTTP/1.1 301 Moved Permanently\r\n"));
+    /// assert!(response.contains("Location: /new\r\n"));
+    ///
+    /// let response = send("GET", "/account");
+    /// assert!(response.starts_with("HTTP/1.1 302 Found\r\n"));
+    /// assert!(response.contains("Location: /login\r\n"));
+    /// assert!(response.ends_with("\r\n\r\n"));
+    /// ```
+    pub fn redirect_see_other(location: &str) -> Self {
+        Self::status(303).with_header("Location", location)
+    }
+
+    /// Creates an error response with a JSON body, as API clients expect.
+    ///
+    /// The body is `{"error":"<message>"}` and the `Content-Type` is
+    /// `application/json`. See [`Server::json_errors`](crate::Server::json_errors)
+    /// to render the server's own errors this way.
+    ///
+    /// # Arguments
+    ///
+    /// * `status` - The HTTP status code, usually `4xx` or `5xx`
+    /// * `message` - The error message, escaped as a JSON string
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use rwf::connection::response::Response;
+    ///
+    /// let response = Response::json_error(422, "\"age\" must be a number");
+    /// assert_eq!(response.status, 422);
+    /// assert_eq!(response.header("Content-Type"), Some("application/json"));
+    /// assert_eq!(response.body, br#"{"error":"\"age\" must be a number"}"#);
+    /// ```
+    pub fn json_error(status: u16, message: &str) -> Self {
+        let mut body = String::from("{\"error\":\"");
+        for c in message.chars() {
+            match c {
+                '"' => body.push_str("\\\""),
+                '\\' => body.push_str("\\\\"),
+                '\n' => body.push_str("\\n"),
+                '\r' => body.push_str("\\r"),
+                '\t' => body.push_str("\\t"),
+                c if c.is_control() => body.push_str(&format!("\\u{:04x}", c as u32)),
+                c => body.push(c),
+            }
+        }
+        body.push_str("\"}");
+        Self::new(status, body).with_header("Content-Type", "application/json")
+    }
+
+    /// Reads a file from disk into a response.
+    ///
+    /// The `Content-Type` header is set from the file extension and the body
+    /// holds the raw file bytes, so binary files are served unchanged.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - Path to the file to serve
+    ///
+    /// # Returns
+    ///
+    /// * `io::Result<Response>` - A `200 OK` response with the file contents, or
+    ///   the IO error raised while reading the file
+    ///
+    /// # Example
+    ///
+    /// ```rust,ignore
+    /// server.route("/report", |_req, _session| {
+    ///     Response::file("reports/latest.pdf").unwrap_or_else(|_| error404())
+    /// });
+    /// ```
+    pub fn file(path: &str) -> io::Result<Self> {
+        let body = fs::read(path)?;
+        Ok(Self::new(200, body).with_header("Content-Type", mime::from_path(path)))
+    }
+
+    /// Reads a file from disk into a response, honouring the request's
+    /// `Range` and `If-Range` headers.
+    ///
+    /// The response carries `Accept-Ranges`, `ETag` and `Last-Modified`
+    /// headers. A request for a single byte range (`bytes=0-99`, `bytes=100-`
+    /// or `bytes=-100`) is answered with a `206 Partial Content` holding only
+    /// that range, or a `416 Range Not Satisfiable` if it lies past the end of
+    /// the file. When the request also sends `If-Range`, the range is only
+    /// served if the header matches the file's current `ETag` or
+    /// `Last-Modified` value; otherwise the whole file is sent with a `200`,
+    /// so a resumed download never mixes two versions of the file. Requests
+    /// for several ranges also get the whole file.
+    ///
+    /// If a pre-compressed `<path>.gz` file sits next to the file and the
+    /// request's `Accept-Encoding` allows `gzip`, that file is sent instead
+    /// with `Content-Encoding: gzip`, keeping the original's `Content-Type`,
+    /// so build pipelines can compress assets once instead of per request.
+    /// Responses for such files carry `Vary: Accept-Encoding` either way.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - Path to the file to serve
+    /// * `request` - The request the file is served for
+    ///
+    /// # Returns
+    ///
+    /// * `io::Result<Response>` - A `200`, `206` or `416` response, or the IO
+    ///   error raised while reading the file
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use rwf::connection::{request::{Limits, Request}, response::Response};
+    ///
+    /// let path = std::env::temp_dir().join("rwf-if-range.txt");
+    /// std::fs::write(&path, "Hello, World!").unwrap();
+    /// let path = path.to_str().unwrap();
+    ///
+    /// let parse = |raw: String| Request::parse(raw.as_bytes(), &Limits::default(), b"secret").unwrap();
+    /// let etag = Response::file_for(path, &parse("GET /f HTTP/1.1\r\n\r\n".to_string()))
+    ///     .unwrap()
+    ///     .header("ETag")
+    ///     .unwrap()
+    ///     .to_string();
+    ///
+    /// // The file is unchanged, so only the range is sent
+    /// let request = parse(format!("GET /f HTTP/1.1\r\nRange: bytes=7-\r\nIf-Range: {etag}\r\n\r\n"));
+    /// let response = Response::file_for(path, &request).unwrap();
+    /// assert_eq!(response.status, 206);
+    /// assert_eq!(response.header("Content-Range"), Some("bytes 7-12/13"));
+    /// assert_eq!(response.body, b"World!");
+    ///
+    /// // The client holds an older version, so the whole file is sent
+    /// let request = parse("GET /f HTTP/1.1\r\nRange: bytes=7-\r\nIf-Range: \"old\"\r\n\r\n".to_string());
+    /// let response = Response::file_for(path, &request).unwrap();
+    /// assert_eq!(response.status, 200);
+    /// assert_eq!(response.body, b"Hello, World!");
+    /// ```
+    ///
+    /// A `.gz` sidecar is only sent to clients accepting `gzip`:
+    ///
+    /// ```rust
+    /// use rwf::connection::{request::{Limits, Request}, response::Response};
+    ///
+    /// let dir = std::env::temp_dir().join(format!("rwf-sidecar-{}", std::process::id()));
+    /// std::fs::create_dir_all(&dir).unwrap();
+    /// std::fs::write(dir.join("app.js"), "console.log(1)").unwrap();
+    /// std::fs::write(dir.join("app.js.gz"), b"\x1f\x8b compressed").unwrap();
+    /// let path = dir.join("app.js");
+    /// let path = path.to_str().unwrap();
+    ///
+    /// let parse = |raw: &str| Request::parse(raw.as_bytes(), &Limits::default(), b"secret").unwrap();
+    ///
+    /// let request = parse("GET /app.js HTTP/1.1\r\nAccept-Encoding: br, gzip\r\n\r\n");
+    /// let response = Response::file_for(path, &request).unwrap();
+    /// assert_eq!(response.header("Content-Encoding"), Some("gzip"));
+    /// assert_eq!(response.header("Content-Type"), Some("text/javascript"));
+    /// assert_eq!(response.header("Vary"), Some("Accept-Encoding"));
+    /// assert_eq!(response.body, b"\x1f\x8b compressed");
+    ///
+    /// for raw in ["GET /app.js HTTP/1.1\r\n\r\n", "GET /app.js HTTP/1.1\r\nAccept-Encoding: gzip;q=0\r\n\r\n"] {
+    ///     let response = Response::file_for(path, &parse(raw)).unwrap();
+    ///     assert_eq!(response.header("Content-Encoding"), None);
+    ///     assert_eq!(response.header("Vary"), Some("Accept-Encoding"));
+    ///     assert_eq!(response.body, b"console.log(1)");
+    /// }
+    /// ```
+    pub fn file_for(path: &str, request: &Request) -> io::Result<Self> {
+        let sidecar = format!("{path}.gz");
+        let has_sidecar = fs::metadata(&sidecar).is_ok_and(|metadata| metadata.is_file());
+        let gzip = has_sidecar && accepts_gzip(request);
+        let served = if gzip { sidecar.as_str() } else { path };
+
+        let body = fs::read(served)?;
+        let modified = fs::metadata(served)?.modified()?;
+        let since_epoch = modified.duration_since(UNIX_EPOCH).unwrap_or_default();
+        let etag = format!("\"{:x}-{:x}\"", body.len(), since_epoch.as_nanos());
+        let last_modified = http_date(since_epoch.as_secs());
+
+        let full = |body: Vec<u8>| {
+            let mut response = Self::new(200, body)
+                .with_header("Content-Type", mime::from_path(path))
+                .with_header("Accept-Ranges", "bytes")
+                .with_header("ETag", &etag)
+                .with_header("Last-Modified", &last_modified);
+            if gzip {
+                response.append_header("Content-Encoding", "gzip");
+            }
+            if has_sidecar {
+                response.append_header("Vary", "Accept-Encoding");
+            }
+            response
+        };
+
+        let Some(range) = request.header("range") else {
+            return Ok(full(body));
+        };
+        if let Some(validator) = request.header("if-range") {
+            // Weak entity tags never match, as they cannot vouch for byte ranges
+            if validator.trim() != etag && validator.trim() != last_modified {
+                return Ok(full(body));
+            }
+        }
+
+        match byte_range(range, body.len()) {
+            ByteRange::Ignored => Ok(full(body)),
+            ByteRange::Unsatisfiable => Ok(Self::new(416, Vec::new())
+                .with_header("Content-Range", &format!("bytes */{}", body.len()))),
+            ByteRange::Satisfiable(start, end) => {
+                let content_range = format!("bytes {start}-{end}/{}", body.len());
+                let mut response = full(body[start..=end].to_vec());
+                response.status = 206;
+                Ok(response.with_header("Content-Range", &content_range))
+            }
+        }
+    }
+
+    /// Adds a header to the response.
+    ///
+    /// Line breaks in the name or value are dropped when the response is
+    /// serialized, so a value taken from the request cannot end the header
+    /// early and inject headers or a body of its own.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The header name
+    /// * `value` - The header value
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use rwf::connection::response::Response;
+    ///
+    /// let response = Response::new(200, "{}").with_header("Content-Type", "application/json");
+    /// assert_eq!(response.headers[0].1, "application/json");
+    ///
+    /// let response = Response::redirect("/home\r\nSet-Cookie: admin=1\r\n\r\n<script>");
+    /// let text = String::from_utf8(response.to_bytes()).unwrap();
+    /// assert!(text.contains("Location: /homeSet-Cookie: admin=1<script>\r\n"));
+    /// assert!(!text.contains("\r\nSet-Cookie"));
+    /// ```
+    pub fn with_header(mut self, name: &str, value: &str) -> Self {
+        self.headers.push((name.to_string(), value.to_string()));
+        self
+    }
+
+    /// Adds a header after the existing ones, keeping any with the same name.
+    ///
+    /// Headers are sent in the order they were added, so repeated headers
+    /// such as `Set-Cookie` or `Link` reach the client in a predictable order.
+    /// Line breaks are dropped on serialization, as for [`Response::with_header`].
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The header name
+    /// * `value` - The header value
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use rwf::connection::response::Response;
+    ///
+    /// let mut response = Response::new(200, "");
+    /// response
+    ///     .append_header("Set-Cookie", "theme=dark")
+    ///     .append_header("Set-Cookie", "lang=en");
+    ///
+    /// let bytes = String::from_utf8(response.to_bytes()).unwrap();
+    /// assert_eq!(
+    ///     bytes,
+    ///     "HTTP/1.1 200 OK\r\nSet-Cookie: theme=dark\r\nSet-Cookie: lang=en\r\nContent-Length: 0\r\n\r\n"
+    /// );
+    /// ```
+    pub fn append_header(&mut self, name: &str, value: &str) -> &mut Self {
+        self.headers.push((name.to_string(), value.to_string()));
+        self
+    }
+
+    /// Sets a header, replacing every existing header with the same name.
+    ///
+    /// Header names are matched case-insensitively. The new value takes the
+    /// place of the first replaced header, or is added last if there was none.
+    /// Line breaks are dropped on serialization, as for [`Response::with_header`].
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The header name
+    /// * `value` - The header value
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use rwf::connection::response::Response;
+    ///
+    /// let mut response = Response::new(200, "")
+    ///     .with_header("Cache-Control", "no-cache")
+    ///     .with_header("Link", "</style.css>; rel=preload")
+    ///     .with_header("cache-control", "private");
+    /// response.set_header("Cache-Control", "no-store");
+    ///
+    /// assert_eq!(
+    ///     response.headers,
+    ///     [
+    ///         ("Cache-Control".to_string(), "no-store".to_string()),
+    ///         ("Link".to_string(), "</style.css>; rel=preload".to_string()),
+    ///     ]
+    /// );
+    /// ```
+    pub fn set_header(&mut self, name: &str, value: &str) -> &mut Self {
+        let position = self
+            .headers
+            .iter()
+            .position(|(header, _)| header.eq_ignore_ascii_case(name));
+        self.headers
+            .retain(|(header, _)| !header.eq_ignore_ascii_case(name));
+        let header = (name.to_string(), value.to_string());
+        match position {
+            Some(position) => self.headers.insert(position, header),
+            None => self.headers.push(header),
+        }
+        self
+    }
+
+    /// Creates a `text/event-stream` response whose events are pushed as they happen.
+    ///
+    /// Once the handler returns, the response head is sent and `producer` runs
+    /// with an [`EventStream`] that writes and flushes each event right away.
+    /// The connection is closed when `producer` returns or fails, for example
+    /// because the client went away. The producer may block between events.
+    /// Streaming responses are never cached, and `HEAD` requests only get
+    /// the head.
+    ///
+    /// # Arguments
+    ///
+    /// * `producer` - The closure sending the events
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use rwf::{connection::response::Response, Server};
+    ///
+    /// let mut server = Server::build("127.0.0.1:0").unwrap();
+    /// server.route("/events", |_req, _session| {
+    ///     Response::event_stream(|events| {
+    ///         events.send("first")?;
+    ///         events.event("update", "line one\nline two")?;
+    ///         Ok(())
+    ///     })
+    /// });
+    /// # let addr = rwf::test_support::serve(server);
+    /// # let send = |request: &str| rwf::test_support::send(addr, request);
+    ///
+    /// let response = send("GET /events HTTP/1.1\r\n\r\n");
+    ///
+    /// let (head, body) = response.split_once("\r\n\r\n").unwrap();
+    /// assert!(head.contains("Content-Type: text/event-stream"));
+    /// assert!(head.contains("Connection: close") && !head.contains("Content-Length"));
+    /// assert_eq!(body, "data: first\n\nevent: update\ndata: line one\ndata: line two\n\n");
+    /// ```
+    ///
+    /// Line breaks cannot inject fields or events, whichever of `\n`, `\r\n`
+    /// or `\r` they are:
+    ///
+    /// ```rust
+    /// use rwf::{connection::response::Response, Server};
+    ///
+    /// let mut server = Server::build("127.0.0.1:0").unwrap();
+    /// server.route("/events", |_req, _session| {
+    ///     Response::event_stream(|events| {
+    ///         events.send("one\r\ntwo\rthree")?;
+    ///         events.event("update\r\ndata: forged", "four")?;
+    ///         events.comment("ping\rdata: forged")?;
+    ///         Ok(())
+    ///     })
+    /// });
+    /// # let addr = rwf::test_support::serve(server);
+    /// # let send = |request: &str| rwf::test_support::send(addr, request);
+    ///
+    /// let response = send("GET /events HTTP/1.1\r\n\r\n");
+    ///
+    /// let (_, body) = response.split_once("\r\n\r\n").unwrap();
+    /// assert_eq!(
+    ///     body,
+    ///     "data: one\ndata: two\ndata: three\n\n\
+    ///      event: updatedata: forged\ndata: four\n\n\
+    ///      : ping\n: data: forged\n\n"
+    /// );
+    /// ```
+    pub fn event_stream(
+        producer: impl FnOnce(&mut EventStream) -> io::Result<()> + Send + 'static,
+    ) -> Self {
+        let mut response = Self::new(200, Vec::new())
+            .with_header("Content-Type", "text/event-stream")
+            .with_header("Cache-Control", "no-store");
+        response.stream = Some(StreamBody::new(producer));
+        response
+    }
+
+    /// Adds a `Set-Cookie` header for the given cookie.
+    ///
+    /// Each call adds its own header line, so a response can set several
+    /// cookies. They are sent alongside the session cookie, if any.
+    ///
+    /// # Arguments
+    ///
+    /// * `cookie` - The cookie to set on the client
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use rwf::{connection::{cookie::Cookie, response::Response}, Server};
+    ///
+    /// let mut server = Server::build("127.0.0.1:0").unwrap();
+    /// server.route("/", |_req, session| {
+    ///     session.add("user".to_string(), "ferris".to_string());
+    ///     Response::new(200, "Hi")
+    ///         .with_cookie(Cookie::new("theme", "dark").path("/"))
+    ///         .with_cookie(Cookie::new("lang", "en"))
+    /// });
+    /// # let addr = rwf::test_support::serve(server);
+    /// # let send = |request: &str| rwf::test_support::send(addr, request);
+    ///
+    /// let response = send("GET / HTTP/1.1\r\nConnection: close\r\n\r\n");
+    ///
+    /// assert!(response.contains("Set-Cookie: theme=dark; Path=/\r\n"));
+    /// assert!(response.contains("Set-Cookie: lang=en\r\n"));
+    /// assert!(response.contains("Set-Cookie: session_id="));
+    /// ```
+    pub fn with_cookie(self, cookie: Cookie) -> Self {
+        self.with_header("Set-Cookie", &cookie.to_string())
+    }
+
+    /// Returns the first value of the given header, if present.
+    ///
+    /// Header names are matched case-insensitively.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The header name
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use rwf::connection::response::Response;
+    ///
+    /// let response = Response::new(200, "{}").with_header("Content-Type", "application/json");
+    /// assert_eq!(response.header("content-type"), Some("application/json"));
+    /// assert_eq!(response.header("location"), None);
+    /// ```
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(header, _)| header.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// Serializes the response into the bytes sent over the connection.
+    ///
+    /// Writes the status line, every header, a `Content-Length` computed from
+    /// the body, and finally the body itself. A `Content-Length` header set
+    /// by hand is replaced by the computed one, so it cannot go stale when
+    /// the body changes.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use rwf::connection::response::Response;
+    ///
+    /// let bytes = Response::new(200, "Hi").to_bytes();
+    /// assert_eq!(bytes, b"HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\nHi");
+    ///
+    /// let mut response = Response::new(200, "Hi").with_header("Content-Length", "2");
+    /// response.body = b"Hello".to_vec();
+    /// assert_eq!(response.to_bytes(), b"HTTP/1.1 200 OK\r\nContent-Length: 5\r\n\r\nHello");
+    /// ```
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = self.to_head_bytes();
+        bytes.extend_from_slice(&self.body);
+        bytes
+    }
+
+    /// Serializes only the header section of the response, as sent for `HEAD` requests.
+    ///
+    /// The `Content-Length` still reflects the size of the body that a `GET`
+    /// request would have received.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use rwf::connection::response::Response;
+    ///
+    /// let bytes = Response::new(200, "Hello").to_head_bytes();
+    /// assert_eq!(bytes, b"HTTP/1.1 200 OK\r\nContent-Length: 5\r\n\r\n");
+    /// ```
+    pub fn to_head_bytes(&self) -> Vec<u8> {
+        let mut head = format!(
+            "HTTP/1.1 {} {}\r\n",
+            self.status,
+            reason_phrase(self.status)
+        );
+        for (name, value) in &self.headers {
+            // The length is always taken from the body as it is now
+            if self.stream.is_none() && name.eq_ignore_ascii_case("Content-Length") {
+                continue;
+            }
+            // A line break would end the header and start another one
+            let (name, value) = (without_line_breaks(name), without_line_breaks(value));
+            head.push_str(&format!("{name}: {value}\r\n"));
+        }
+        // A streamed body ends when the connection is closed
+        if self.stream.is_none() {
+            head.push_str(&format!("Content-Length: {}\r\n", self.body.len()));
+        }
+        head.push_str("\r\n");
+        head.into_bytes()
+    }
+
+    /// Creates a placeholder for a response sent later, for long polling.
+    ///
+    /// Once the handler returns the placeholder, the connection stays open
+    /// and waits until the [`Resolver`] sends the real response, from any
+    /// thread, or until `timeout` elapses. A request that times out, or
+    /// whose resolver is dropped, is answered with the placeholder itself:
+    /// `204 No Content`. Headers added to the placeholder after the handler
+    /// returned, such as the session cookie, are kept on the real response.
+    /// See the [`deferred`](super::deferred) module.
+    ///
+    /// # Arguments
+    ///
+    /// * `timeout` - How long the connection waits for the response
+    ///
+    /// # Returns
+    ///
+    /// The placeholder to return from the handler, and the resolver to hand
+    /// to the code producing the response
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use rwf::{
+    ///     connection::{deferred::Resolver, response::Response},
+    ///     Server,
+    /// };
+    /// use std::{
+    ///     sync::{Arc, Mutex},
+    ///     thread,
+    ///     time::Duration,
+    /// };
+    ///
+    /// let waiting: Arc<Mutex<Vec<Resolver>>> = Arc::default();
+    /// let clients = Arc::clone(&waiting);
+    ///
+    /// let mut server = Server::build("127.0.0.1:0").unwrap();
+    /// server.route("/poll", move |_req, _session| {
+    ///     let (response, resolver) = Response::deferred(Duration::from_secs(30));
+    ///     clients.lock().unwrap().push(resolver);
+    ///     response
+    /// });
+    /// server.route("/quick", |_req, _session| Response::deferred(Duration::from_millis(10)).0);
+    /// # let addr = rwf::test_support::serve(server);
+    ///
+    /// // A background thread answers once the client is waiting
+    /// thread::spawn(move || loop {
+    ///     if let Some(resolver) = waiting.lock().unwrap().pop() {
+    ///         assert!(resolver.resolve("new message"));
+    ///         break;
+    ///     }
+    ///     thread::sleep(Duration::from_millis(10));
+    /// });
+    /// # let get = |path: &str| rwf::test_support::get(addr, path);
+    ///
+    /// let polled = get("/poll");
+    /// assert!(polled.starts_with("HTTP/1.1 200 OK") && polled.ends_with("new message"));
+    ///
+    /// // Nobody resolves this one in time
+    /// assert!(get("/quick").starts_with("HTTP/1.1 204 No Content"));
+    /// ```
+    pub fn deferred(timeout: Duration) -> (Self, Resolver) {
+        let (pending, resolver) = Pending::new(timeout);
+        let mut response = Self::no_content();
+        response.pending = Some(pending);
+        (response, resolver)
+    }
+
+    /// Waits for the real response of a [`Response::deferred`] placeholder.
+    ///
+    /// Other responses are returned unchanged, and so is a placeholder whose
+    /// request timed out.
+    pub(crate) fn wait(self) -> Self {
+        match self.pending.as_ref().and_then(Pending::wait) {
+            Some(mut resolved) => {
+                resolved.headers.extend(self.headers);
+                resolved
+            }
+            None => Self {
+                pending: None,
+                ..self
+            },
+        }
+    }
+
+    /// Returns whether the response is an event stream produced while it is sent.
+    pub fn is_streaming(&self) -> bool {
+        self.stream.is_some()
+    }
+
+    /// Writes the events of a streaming response to the connection.
+    ///
+    /// Does nothing for other responses, or if the events were already sent.
+    pub(crate) fn stream_to(&self, stream: &mut dyn Stream) -> io::Result<()> {
+        match &self.stream {
+            Some(body) => body.run(stream),
+            None => Ok(()),
+        }
+    }
+}
+
+impl From<String> for Response {
+    /// Converts a handler's string output into the body of a `200 OK`
+    /// response.
+    ///
+    /// The string is never read as a status line or headers, so handlers may
+    /// return user data as is.
+    fn from(value: String) -> Self {
+        Self::new(200, value)
+    }
+}
+
+impl From<&str> for Response {
+    fn from(value: &str) -> Self {
+        Self::from(value.to_string())
+    }
+}
+
+impl From<(u16, String)> for Response {
+    /// Converts a status code and a body into a response.
+    fn from((status, body): (u16, String)) -> Self {
+        Self::new(status, body)
+    }
+}
+
+impl From<(u16, &str)> for Response {
+    fn from((status, body): (u16, &str)) -> Self {
+        Self::new(status, body)
+    }
+}
+
+/// A value a route handler may return, converted into the response sent.
+///
+/// Implemented for every type converting into a [`Response`], which are:
+///
+/// * `Response` - sent as is
+/// * `String` and `&str` - the body of a `200 OK` response
+/// * `(u16, String)` and `(u16, &str)` - a status code and a body
+/// * [`HttpError`] - its status code and message
+/// * `Result<T, HttpError>` - the response of either outcome, for any `T`
+///   in this list
+///
+/// Types of your own become valid handler results by implementing
+/// `From<T> for Response`.
+///
+/// # Example
+///
+/// ```rust
+/// use rwf::connection::response::{HttpError, IntoResponse, Response};
+///
+/// assert_eq!("Hello".into_response().status, 200);
+///
+/// // Strings are always a body, even when they look like a response
+/// let echoed = "HTTP/1.1 302 Found\r\nLocation: http://evil/\r\n\r\n";
+/// assert_eq!(echoed.into_response().status, 200);
+/// assert_eq!(echoed.into_response().header("Location"), None);
+/// assert_eq!((201, "Created".to_string()).into_response().status, 201);
+/// assert_eq!(Response::no_content().into_response().status, 204);
+///
+/// let result: Result<Response, HttpError> = Err(HttpError::not_found());
+/// assert_eq!(result.into_response().status, 404);
+///
+/// // Handlers may return any of them
+/// let mut server = rwf::Server::build("127.0.0.1:0").unwrap();
+/// server.route("/teapot", |_req, _session| (418, "Short and stout"));
+/// ```
+pub trait IntoResponse {
+    /// Converts the value into a response.
+    fn into_response(self) -> Response;
+}
+
+impl<T: Into<Response>> IntoResponse for T {
+    fn into_response(self) -> Response {
+        self.into()
+    }
+}
+
+/// An error that ends a handler early with a status code and a message.
+///
+/// Handlers may return `Result<R, HttpError>` for any `R` they could return
+/// on its own, so validation failures can be propagated with `?`. The error
+/// becomes a response with its status and the message as the body.
+///
+/// # Example
+///
+/// ```rust
+/// use rwf::{connection::response::HttpError, Server};
+///
+/// fn parse_age(value: Option<&str>) -> Result<u8, HttpError> {
+///     let value = value.ok_or_else(|| HttpError::bad_request("missing age"))?;
+///     value
+///         .parse()
+///         .map_err(|_| HttpError::unprocessable_entity(&format!("'{value}' is not an age")))
+/// }
+///
+/// let mut server = Server::build("127.0.0.1:0").unwrap();
+/// server.route("/age", |req, _session| -> Result<String, HttpError> {
+///     let form = req.form().ok_or_else(HttpError::not_found)?;
+///     let age = parse_age(form.get("age").map(String::as_str))?;
+///     Ok(format!("{age} years"))
+/// });
+/// # let addr = rwf::test_support::serve(server);
+///
+/// # let post = |body: &str| {
+/// #     let request = format!(
+/// #         "POST /age HTTP/1.1\r\nContent-Type: application/x-www-form-urlencoded\r\n\
+/// #          Content-Length: {}\r\nConnection: close\r\n\r\n{body}",
+/// #         body.len()
+/// #     );
+/// #     rwf::test_support::send(addr, &request)
+/// # };
+///
+/// assert!(post("age=42").ends_with("42 years"));
+/// let invalid = post("age=old");
+/// assert!(invalid.starts_with("HTTP/1.1 422 Unprocessable Entity"));
+/// assert!(invalid.ends_with("'old' is not an age"));
+/// assert!(post("name=ferris").starts_with("HTTP/1.1 400"));
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HttpError {
+    /// The status code of the response
+    pub status: u16,
+    /// The message sent as the response body
+    pub message: String,
+}
+
+impl HttpError {
+    /// Creates an error with any status code and message.
+    ///
+    /// # Arguments
+    ///
+    /// * `status` - The HTTP status code, usually `4xx` or `5xx`
+    /// * `message` - The message sent to the client
+    pub fn new(status: u16, message: &str) -> Self {
+        Self {
+            status,
+            message: message.to_string(),
+        }
+    }
+
+    /// A `400 Bad Request` error, for requests that cannot be understood.
+    pub fn bad_request(message: &str) -> Self {
+        Self::new(400, message)
+    }
+
+    /// A `401 Unauthorized` error, for requests lacking valid credentials.
+    pub fn unauthorized() -> Self {
+        Self::new(401, "Unauthorized")
+    }
+
+    /// A `403 Forbidden` error, for clients not allowed to access a resource.
+    pub fn forbidden() -> Self {
+        Self::new(403, "Forbidden")
+    }
+
+    /// A `404 Not Found` error, for resources that do not exist.
+    pub fn not_found() -> Self {
+        Self::new(404, "Not found")
+    }
+
+    /// A `409 Conflict` error, for requests clashing with the resource's state.
+    pub fn conflict(message: &str) -> Self {
+        Self::new(409, message)
+    }
+
+    /// A `422 Unprocessable Entity` error, for well-formed requests holding
+    /// invalid data.
+    pub fn unprocessable_entity(message: &str) -> Self {
+        Self::new(422, message)
+    }
+
+    /// A `500 Internal Server Error`, for failures on the server side. The
+    /// message is sent to the client, so it should not hold internal details.
+    pub fn internal_server_error() -> Self {
+        Self::new(500, "Internal server error")
+    }
+}
+
+impl fmt::Display for HttpError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} {}", self.status, self.message)
+    }
+}
+
+impl std::error::Error for HttpError {}
+
+impl From<HttpError> for Response {
+    fn from(error: HttpError) -> Self {
+        Self::new(error.status, error.message)
+    }
+}
+
+impl<T: Into<Response>> From<Result<T, HttpError>> for Response {
+    /// Converts a handler's result into the response of either outcome.
+    fn from(result: Result<T, HttpError>) -> Self {
+        match result {
+            Ok(value) => value.into(),
+            Err(error) => error.into(),
+        }
+    }
+}
+
+/// Removes the carriage returns and line feeds from a header name or value.
+fn without_line_breaks(text: &str) -> Cow<'_, str> {
+    if text.contains(['\r', '\n']) {
+        Cow::Owned(text.replace(['\r', '\n'], ""))
+    } else {
+        Cow::Borrowed(text)
+    }
+}
+
+/// Checks whether a request's `Accept-Encoding` header allows `gzip`,
+/// directly or through `*`, with a nonzero `q` weight.
+fn accepts_gzip(request: &Request) -> bool {
+    request
+        .headers
+        .get_all("accept-encoding")
+        .iter()
+        .flat_map(|value| value.split(','))
+        .any(|entry| {
+            let mut parts = entry.split(';');
+            let coding = parts.next().unwrap_or_default().trim();
+            let quality = parts
+                .filter_map(|param| param.trim().split_once('='))
+                .find(|(name, _)| name.trim() == "q")
+                .map_or(1.0, |(_, q)| q.trim().parse().unwrap_or(0.0));
+            (coding.eq_ignore_ascii_case("gzip") || coding == "*") && quality > 0.0
+        })
+}
+
+/// The outcome of matching a `Range` header against a body.
+enum ByteRange {
+    /// The header is malformed or asks for several ranges, so it is ignored
+    Ignored,
+    /// The range starts past the end of the body
+    Unsatisfiable,
+    /// The inclusive first and last byte to send
+    Satisfiable(usize, usize),
+}
+
+/// Parses a single `bytes=` range against a body of the given length.
+fn byte_range(header: &str, len: usize) -> ByteRange {
+    let Some(spec) = header.trim().strip_prefix("bytes=") else {
+        return ByteRange::Ignored;
+    };
+    if spec.contains(',') {
+        return ByteRange::Ignored;
+    }
+    let Some((start, end)) = spec.trim().split_once('-') else {
+        return ByteRange::Ignored;
+    };
+
+    let (start, end) = match (start.parse::<usize>(), end.parse::<usize>()) {
+        // bytes=-N asks for the last N bytes
+        (Err(_), Ok(suffix)) if start.is_empty() => {
+            if suffix == 0 {
+                return ByteRange::Unsatisfiable;
+            }
+            (len.saturating_sub(suffix), len.saturating_sub(1))
+        }
+        (Ok(start), Err(_)) if end.is_empty() => (start, len.saturating_sub(1)),
+        (Ok(start), Ok(end)) if start <= end => (start, end.min(len.saturating_sub(1))),
+        _ => return ByteRange::Ignored,
+    };
+
+    if start >= len {
+        ByteRange::Unsatisfiable
+    } else {
+        ByteRange::Satisfiable(start, end)
+    }
+}
+
+/// Formats seconds since the Unix epoch as an HTTP date, such as
+/// `Thu, 01 Jan 1970 00:00:00 GMT`.
+fn http_date(secs: u64) -> String {
+    const WEEKDAYS: [&str; 7] = ["Thu", "Fri", "Sat", "Sun", "Mon", "Tue", "Wed"];
+    const MONTHS: [&str; 12] = [
+        "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+    ];
+
+    let days = secs / 86_400;
+    let (hour, minute, second) = (secs % 86_400 / 3600, secs % 3600 / 60, secs % 60);
+
+    // Converts days since the epoch to a civil date (Howard Hinnant's algorithm)
+    let z = days + 719_468;
+    let era = z / 146_097;
+    let doe = z % 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + u64::from(month <= 2);
+
+    format!(
+        "{}, {day:02} {} {year} {hour:02}:{minute:02}:{second:02} GMT",
+        WEEKDAYS[(days % 7) as usize],
+        MONTHS[(month - 1) as usize]
+    )
+}
+
+/// Returns the standard reason phrase of a status code.
+///
+/// Used for the status line when a [`Response`] is serialized, so only the
+/// numeric code has to be given. Covers the codes registered for HTTP, from
+/// `100` to `511`; any other code gets `Unknown`.
+///
+/// # Arguments
+///
+/// * `code` - The HTTP status code
+///
+/// # Example
+///
+/// ```rust
+/// use rwf::connection::response::{reason_phrase, Response};
+///
+/// assert_eq!(reason_phrase(100), "Continue");
+/// assert_eq!(reason_phrase(201), "Created");
+/// assert_eq!(reason_phrase(301), "Moved Permanently");
+/// assert_eq!(reason_phrase(404), "Not Found");
+/// assert_eq!(reason_phrase(418), "I'm a teapot");
+/// assert_eq!(reason_phrase(503), "Service Unavailable");
+/// assert_eq!(reason_phrase(299), "Unknown");
+///
+/// let bytes = Response::status(418).to_bytes();
+/// assert!(bytes.starts_with(b"HTTP/1.1 418 I'm a teapot\r\n"));
+/// ```
+pub fn reason_phrase(code: u16) -> &'static str {
+    match code {
+        100 => "Continue",
+        101 => "Switching Protocols",
+        102 => "Processing",
+        103 => "Early Hints",
+        200 => "OK",
+        201 => "Created",
+        202 => "Accepted",
+        203 => "Non-Authoritative Information",
+        204 => "No Content",
+        205 => "Reset Content",
+        206 => "Partial Content",
+        207 => "Multi-Status",
+        208 => "Already Reported",
+        226 => "IM Used",
+        300 => "Multiple Choices",
+        301 => "Moved Permanently",
+        302 => "Found",
+        303 => "See Other",
+        304 => "Not Modified",
+        305 => "Use Proxy",
+        307 => "Temporary Redirect",
+        308 => "Permanent Redirect",
+        400 => "Bad Request",
+        401 => "Unauthorized",
+        402 => "Payment Required",
+        403 => "Forbidden",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        406 => "Not Acceptable",
+        407 => "Proxy Authentication Required",
+        408 => "Request Timeout",
+        409 => "Conflict",
+        410 => "Gone",
+        411 => "Length Required",
+        412 => "Precondition Failed",
+        413 => "Payload Too Large",
+        414 => "URI Too Long",
+        415 => "Unsupported Media Type",
+        416 => "Range Not Satisfiable",
+        417 => "Expectation Failed",
+        418 => "I'm a teapot",
+        421 => "Misdirected Request",
+        422 => "Unprocessable Entity",
+        423 => "Locked",
+        424 => "Failed Dependency",
+        425 => "Too Early",
+        426 => "Upgrade Required",
+        428 => "Precondition Required",
+        429 => "Too Many Requests",
+        431 => "Request Header Fields Too Large",
+        451 => "Unavailable For Legal Reasons",
+        500 => "Internal Server Error",
+        501 => "Not Implemented",
+        502 => "Bad Gateway",
+        503 => "Service Unavailable",
+        504 => "Gateway Timeout",
+        505 => "HTTP Version Not Supported",
+        506 => "Variant Also Negotiates",
+        507 => "Insufficient Storage",
+        508 => "Loop Detected",
+        510 => "Not Extended",
+        511 => "Network Authentication Required",
+        _ => "Unknown",
+    }
+}
 
 /// Formats content into a complete HTTP response with headers.
 ///
@@ -41,6 +1274,8 @@ use std::usize;
 /// # Example
 ///
 /// ```rust
+/// use rwf::connection::response::format_content;
+///
 /// let response = format_content(
 ///     11,
 ///     "Hello World",
@@ -48,23 +1283,26 @@ use std::usize;
 /// );
 /// ```
 pub fn format_content(length: usize, content: &str, session_id: usize) -> Vec<u8> {
-    format!("HTTP/1.1 200 OK\r\nSet-Cookie: session_id={session_id}; HttpOnly\r\nContent-Lenght: {length}\r\n\r\n{content}").into_bytes()
+    format!("HTTP/1.1 200 OK\r\nSet-Cookie: session_id={session_id}; HttpOnly\r\nContent-Length: {length}\r\n\r\n{content}").into_bytes()
 }
 
-// Generates a 404 Not Found error response.
+/// Generates a 404 Not Found error response.
 ///
 /// # Returns
 ///
-/// A string containing a complete 404 HTTP response
+/// A `404 Not Found` response
 ///
 /// # Example
 ///
 /// ```rust
-/// let not_found = error();
-/// assert!(not_found.contains("404 NOT FOUND"));
+/// use rwf::connection::response::error404;
+///
+/// let not_found = error404();
+/// assert_eq!(not_found.status, 404);
+/// assert_eq!(not_found.body, b"Resource not found");
 /// ```
-pub fn error404() -> String {
-    "HTTP/1.1 404 NOT FOUND\r\nContent-Length: 21\r\n\r\nResource not found".into()
+pub fn error404() -> Response {
+    Response::new(404, "Resource not found")
 }
 
 /// A macro for loading and optionally processing template files with variable substitution.
@@ -74,12 +1312,12 @@ pub fn error404() -> String {
 /// This macro provides two ways to work with template files:
 ///
 /// 1. Simple file loading:
-/// ```rust
+/// ```rust,ignore
 /// let content = template!("path/to/file.txt");
 /// ```
 ///
 /// 2. Template processing with variable substitution:
-/// ```rust
+/// ```rust,ignore
 /// let name = "Alice";
 /// let age = "25";
 /// let content = template!("path/to/template.txt", name, age);
@@ -89,7 +1327,7 @@ pub fn error404() -> String {
 ///
 /// * `$path` - A string literal representing the path to the template file
 /// * `$value` - (Optional) One or more variables whose values will replace their corresponding
-///             placeholders in the template
+///   placeholders in the template
 ///
 /// # Template Format
 ///
@@ -106,7 +1344,7 @@ pub fn error404() -> String {
 ///
 /// # Examples
 ///
-/// ```rust
+/// ```rust,ignore
 /// // Simple file reading
 /// let result = template!("templates/welcome.txt");
 ///
@@ -140,14 +1378,27 @@ macro_rules! template {
 }
 /// Handles request redirection by generating an 302 HTTP response to the new route.
 ///
+/// Handlers returning a [`Response`] can use [`Response::redirect`] instead,
+/// or [`Response::redirect_permanent`] and [`Response::redirect_see_other`]
+/// for `301` and `303` redirects.
+///
 /// # Arguments
 ///
 /// * `route` - the destination route
 ///
 /// # Returns
 ///
-/// The response string from the redirect handler
+/// A `302 Found` response with `route` as its `Location`
+///
+/// # Example
+///
+/// ```rust
+/// use rwf::connection::response::redirect;
 ///
-pub fn redirect(route: &str) -> String {
-    format!("HTTP/1.1 302 Found\r\nLocation: {route}\r\n\r\n")
+/// let response = redirect("/login");
+/// assert_eq!(response.status, 302);
+/// assert_eq!(response.header("Location"), Some("/login"));
+/// ```
+pub fn redirect(route: &str) -> Response {
+    Response::redirect(route)
 }
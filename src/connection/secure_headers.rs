@@ -0,0 +1,102 @@
+//! # Secure Headers Module
+//!
+//! Defines the baseline security headers `Server::secure_headers` adds to
+//! every response.
+//!
+//! The defaults are:
+//!
+//! * `X-Content-Type-Options: nosniff`
+//! * `X-Frame-Options: DENY`
+//! * `Referrer-Policy: no-referrer`
+//! * `Content-Security-Policy: default-src 'self'`
+//!
+//! Each can be changed or removed, and more can be added. A response that
+//! already carries one of the headers keeps its own value, so a single
+//! handler can relax a policy for its page.
+//!
+//! ## Example
+//!
+//! ```rust
+//! use rwf::connection::secure_headers::SecureHeaders;
+//!
+//! let mut headers = SecureHeaders::default();
+//! headers
+//!     .set("X-Frame-Options", "SAMEORIGIN")
+//!     .content_security_policy("default-src 'self'; img-src *")
+//!     .remove("Referrer-Policy");
+//!
+//! assert_eq!(headers.get("x-frame-options"), Some("SAMEORIGIN"));
+//! assert_eq!(headers.get("Referrer-Policy"), None);
+//! ```
+
+use super::response::Response;
+
+/// The headers added to every response once `Server::secure_headers` is called.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SecureHeaders {
+    /// The header names and values, in the order they are added
+    headers: Vec<(String, String)>,
+}
+
+impl Default for SecureHeaders {
+    fn default() -> Self {
+        let headers = [
+            ("X-Content-Type-Options", "nosniff"),
+            ("X-Frame-Options", "DENY"),
+            ("Referrer-Policy", "no-referrer"),
+            ("Content-Security-Policy", "default-src 'self'"),
+        ];
+        Self {
+            headers: headers
+                .iter()
+                .map(|(name, value)| (name.to_string(), value.to_string()))
+                .collect(),
+        }
+    }
+}
+
+impl SecureHeaders {
+    /// Sets the value of a header, adding it if it is not in the set yet.
+    ///
+    /// Header names are matched case-insensitively.
+    pub fn set(&mut self, name: &str, value: &str) -> &mut Self {
+        match self
+            .headers
+            .iter_mut()
+            .find(|(header, _)| header.eq_ignore_ascii_case(name))
+        {
+            Some((_, existing)) => *existing = value.to_string(),
+            None => self.headers.push((name.to_string(), value.to_string())),
+        }
+        self
+    }
+
+    /// Stops adding a header, e.g. one a reverse proxy already sets.
+    pub fn remove(&mut self, name: &str) -> &mut Self {
+        self.headers
+            .retain(|(header, _)| !header.eq_ignore_ascii_case(name));
+        self
+    }
+
+    /// Sets the `Content-Security-Policy`, `default-src 'self'` by default.
+    pub fn content_security_policy(&mut self, policy: &str) -> &mut Self {
+        self.set("Content-Security-Policy", policy)
+    }
+
+    /// Returns the value added for a header, if it is in the set.
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(header, _)| header.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// Adds every header of the set the response does not carry yet.
+    pub(crate) fn apply(&self, response: &mut Response) {
+        for (name, value) in &self.headers {
+            if response.header(name).is_none() {
+                response.append_header(name, value);
+            }
+        }
+    }
+}
@@ -11,11 +11,14 @@
 //!   of any type that implements the `Any` trait.
 //! - **Sessions**: A manager for multiple sessions, allowing creation and retrieval
 //!   of sessions by their unique IDs.
+//! - **SessionStore**: The storage backend sessions are kept in, in process
+//!   memory by default through `MemorySessionStore`, or on disk through
+//!   `FileSessionStore`.
 //!
 //! ## Usage
 //!
 //! ### Example: Working with a Single Session
-//! ```rust
+//! ```rust,ignore
 //! use rwf::{Session, Sessions};
 //!
 //! let mut session = Session::new();
@@ -30,18 +33,20 @@
 //! ```
 //!
 //! ### Example: Managing Multiple Sessions
-//! ```rust
+//! ```rust,ignore
 //! use rwf::{Session, Sessions};
 //!
-//! let mut sessions = Sessions::new();
+//! let sessions = Sessions::new();
 //!
 //! // Add a new session and get its ID
 //! let session_id = sessions.add();
 //!
-//! // Access the session by its ID
-//! let session = sessions.get(session_id);
+//! // Load the session by its ID and save it back after changing it
+//! let mut session = sessions.load(&session_id).unwrap();
 //! session.add("is_logged_in".to_string(), true);
+//! sessions.save(&session_id, session);
 //!
+//! let session = sessions.load(&session_id).unwrap();
 //! let is_logged_in: Option<&bool> = session.get("is_logged_in");
 //! assert_eq!(is_logged_in, Some(&true));
 //! ```
@@ -49,38 +54,67 @@
 //! ## Key Features
 //!
 //! - **Flexible Data Storage**: Store values of any type that implements `Any`.
-//! - **Unique Session IDs**: Manage multiple sessions with unique identifiers.
+//! - **Unique Session IDs**: Manage multiple sessions with random, unguessable 128-bit identifiers.
 //! - **Type Safety**: Retrieve stored values with type safety by specifying the expected type.
+//! - **Signed Cookies**: Session IDs sent to clients carry an HMAC-SHA256 signature,
+//!   so a client cannot forge another user's session cookie.
 
-use std::{any::Any, collections::HashMap};
+mod file_store;
+
+pub use file_store::FileSessionStore;
+
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use std::{
+    any::Any,
+    collections::{HashMap, HashSet},
+    fmt,
+    sync::{Condvar, Mutex, MutexGuard},
+};
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// The session key the CSRF token is stored under.
+const CSRF_KEY: &str = "csrf_token";
 
 /// Represents a session that can store and retrieve data of any type.
 ///
 /// The `Session` struct provides a flexible way to store and retrieve data using
 /// string keys. Internally, it uses a `HashMap` to manage the data, where the values
-/// are stored as `Box<dyn Any>`.
+/// are stored as `Box<dyn Any + Send>`.
 pub struct Session {
     /// The internal data storage for the session.
-    data: HashMap<String, Box<dyn Any>>,
+    data: HashMap<String, Box<dyn Any + Send>>,
+    /// Whether the session is deleted once the request finishes.
+    destroyed: bool,
 }
 
 impl Session {
     /// Creates a new, empty `Session`.
     ///
+    /// Handlers receive their session from the server; creating one is
+    /// mostly useful to [`SessionStore`] implementations restoring sessions
+    /// and to tests calling a handler with a request from `Request::builder`.
+    ///
     /// # Examples
     ///
-    /// ```
+    /// ```rust
+    /// use rwf::connection::session::Session;
+    ///
     /// let session = Session::new();
+    /// assert!(session.is_empty());
     /// ```
-    fn new() -> Self {
+    pub fn new() -> Self {
         Self {
             data: HashMap::new(),
+            destroyed: false,
         }
     }
 
     /// Adds a key-value pair to the session.
     ///
-    /// The value can be of any type that implements the `Any` trait.
+    /// The value can be of any type that implements the `Any` trait and can
+    /// be sent to another thread.
     ///
     /// # Arguments
     ///
@@ -89,11 +123,11 @@ impl Session {
     ///
     /// # Examples
     ///
-    /// ```
+    /// ```rust,ignore
     /// let mut session = Session::new();
     /// session.add("username", "Alice");
     /// ```
-    pub fn add<T: Any>(&mut self, key: String, value: T) {
+    pub fn add<T: Any + Send>(&mut self, key: String, value: T) {
         self.data.insert(key, Box::new(value));
     }
 
@@ -108,7 +142,7 @@ impl Session {
     ///
     /// # Examples
     ///
-    /// ```
+    /// ```rust,ignore
     /// let mut session = Session::new();
     /// session.add("username".to_string(), "Alice");
     /// let username: Option<&str> = session.get("username");
@@ -117,31 +151,459 @@ impl Session {
     pub fn get<T: Any>(&self, key: &str) -> Option<&T> {
         self.data.get(key)?.downcast_ref()
     }
+
+    /// Retrieves a value like [`Session::get`], telling a missing key apart
+    /// from a value of another type.
+    ///
+    /// # Arguments
+    ///
+    /// * `key` - The key to look up
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(&T)` - The stored value
+    /// * `Err(SessionValueError::Missing)` - If nothing is stored under `key`
+    /// * `Err(SessionValueError::WrongType)` - If the value is not a `T`
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use rwf::connection::session::{Session, SessionValueError};
+    ///
+    /// let mut session = Session::new();
+    /// session.add("visits".to_string(), 3u32);
+    ///
+    /// assert_eq!(session.try_get::<u32>("visits"), Ok(&3));
+    /// assert_eq!(session.try_get::<i64>("visits"), Err(SessionValueError::WrongType));
+    /// assert_eq!(session.try_get::<u32>("user"), Err(SessionValueError::Missing));
+    /// ```
+    pub fn try_get<T: Any>(&self, key: &str) -> Result<&T, SessionValueError> {
+        self.data
+            .get(key)
+            .ok_or(SessionValueError::Missing)?
+            .downcast_ref()
+            .ok_or(SessionValueError::WrongType)
+    }
+
+    /// Retrieves a text value, stored as a `String` or a `&'static str`.
+    ///
+    /// Logs a warning if the key holds a value of another type.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use rwf::connection::session::Session;
+    ///
+    /// let mut session = Session::new();
+    /// session.add("user".to_string(), "Alice".to_string());
+    /// session.add("theme".to_string(), "dark");
+    /// session.add("visits".to_string(), 3u32);
+    ///
+    /// assert_eq!(session.get_string("user"), Some("Alice"));
+    /// assert_eq!(session.get_string("theme"), Some("dark"));
+    /// assert_eq!(session.get_string("visits"), None); // logs a warning
+    /// assert_eq!(session.get_string("missing"), None);
+    /// ```
+    pub fn get_string(&self, key: &str) -> Option<&str> {
+        let value = self.data.get(key)?;
+        let text = value
+            .downcast_ref::<String>()
+            .map(String::as_str)
+            .or_else(|| value.downcast_ref::<&'static str>().copied());
+        warn_on_mismatch(key, text, "a string")
+    }
+
+    /// Retrieves an integer value, stored as any integer type that fits in an `i64`.
+    ///
+    /// Logs a warning if the key holds a value of another type.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use rwf::connection::session::Session;
+    ///
+    /// let mut session = Session::new();
+    /// session.add("visits".to_string(), 3u32);
+    /// session.add("balance".to_string(), -20i64);
+    /// session.add("name".to_string(), "Alice".to_string());
+    ///
+    /// assert_eq!(session.get_i64("visits"), Some(3));
+    /// assert_eq!(session.get_i64("balance"), Some(-20));
+    /// assert_eq!(session.get_i64("name"), None); // logs a warning
+    /// assert_eq!(session.get_i64("missing"), None);
+    /// ```
+    pub fn get_i64(&self, key: &str) -> Option<i64> {
+        let value = self.data.get(key)?;
+        let number = downcast_int!(value, i64, i8, i16, i32, i64, isize, u8, u16, u32, u64, usize);
+        warn_on_mismatch(key, number, "an integer")
+    }
+
+    /// Retrieves a floating point value, stored as an `f64` or an `f32`.
+    ///
+    /// Logs a warning if the key holds a value of another type.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use rwf::connection::session::Session;
+    ///
+    /// let mut session = Session::new();
+    /// session.add("ratio".to_string(), 0.5f32);
+    ///
+    /// assert_eq!(session.get_f64("ratio"), Some(0.5));
+    /// assert_eq!(session.get_f64("missing"), None);
+    /// ```
+    pub fn get_f64(&self, key: &str) -> Option<f64> {
+        let value = self.data.get(key)?;
+        let number = value
+            .downcast_ref::<f64>()
+            .copied()
+            .or_else(|| value.downcast_ref::<f32>().map(|&n| f64::from(n)));
+        warn_on_mismatch(key, number, "a float")
+    }
+
+    /// Retrieves a `bool` value.
+    ///
+    /// Logs a warning if the key holds a value of another type.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use rwf::connection::session::Session;
+    ///
+    /// let mut session = Session::new();
+    /// session.add("admin".to_string(), true);
+    /// session.add("visits".to_string(), 1u32);
+    ///
+    /// assert_eq!(session.get_bool("admin"), Some(true));
+    /// assert_eq!(session.get_bool("visits"), None); // logs a warning
+    /// ```
+    pub fn get_bool(&self, key: &str) -> Option<bool> {
+        let flag = self.data.get(key)?.downcast_ref::<bool>().copied();
+        warn_on_mismatch(key, flag, "a bool")
+    }
+
+    /// Returns the keys stored in the session, in no particular order.
+    ///
+    /// Values are type-erased, so only their keys can be listed; read them
+    /// back with [`Session::get`] and the expected type.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use rwf::connection::session::Session;
+    ///
+    /// let mut session = Session::new();
+    /// session.add("user".to_string(), "Alice");
+    /// session.add("theme".to_string(), "dark");
+    ///
+    /// let mut keys = session.keys();
+    /// keys.sort();
+    /// assert_eq!(keys, ["theme", "user"]);
+    /// ```
+    pub fn keys(&self) -> Vec<&String> {
+        self.data.keys().collect()
+    }
+
+    /// Returns the number of values stored in the session.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use rwf::connection::session::Session;
+    ///
+    /// let mut session = Session::new();
+    /// assert_eq!(session.len(), 0);
+    /// assert!(session.is_empty());
+    ///
+    /// session.add("visits".to_string(), 1u32);
+    /// assert_eq!(session.len(), 1);
+    /// assert!(!session.is_empty());
+    /// ```
+    pub fn len(&self) -> usize {
+        self.data.len()
+    }
+
+    /// Returns `true` if nothing has been stored in the session.
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+
+    /// Destroys the session, e.g. to log the user out.
+    ///
+    /// Every value is dropped right away. Once the handler returns, the
+    /// session is deleted from the store and the client is told to forget
+    /// its cookie, so the old ID cannot be used again: a later request
+    /// carrying it gets a fresh, empty session.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use rwf::Server;
+    ///
+    /// let mut server = Server::build("127.0.0.1:0").unwrap();
+    /// server.route("/login", |_req, session| {
+    ///     session.add("user".to_string(), "ferris".to_string());
+    ///     "Logged in".to_string()
+    /// });
+    /// server.route("/logout", |_req, session| {
+    ///     session.destroy();
+    ///     "Logged out".to_string()
+    /// });
+    /// server.route("/me", |_req, session| {
+    ///     session.get_string("user").unwrap_or("nobody").to_string()
+    /// });
+    /// # let addr = rwf::test_support::serve(server);
+    /// # let get = |path: &str, cookie: &str| {
+    /// #     rwf::test_support::send(addr, &format!("GET {path} HTTP/1.1\r\nCookie: {cookie}\r\nConnection: close\r\n\r\n"))
+    /// # };
+    ///
+    /// let login = get("/login", "");
+    /// let cookie = login.split("Set-Cookie: ").nth(1).unwrap().split(';').next().unwrap();
+    /// assert!(get("/me", cookie).ends_with("ferris"));
+    ///
+    /// // The client is told to drop the cookie...
+    /// let logout = get("/logout", cookie);
+    /// assert!(logout.contains("Set-Cookie: session_id=; Max-Age=0; HttpOnly"));
+    ///
+    /// // ...and the old ID no longer reaches the session
+    /// assert!(get("/me", cookie).ends_with("nobody"));
+    /// ```
+    pub fn destroy(&mut self) {
+        self.data.clear();
+        self.destroyed = true;
+    }
+
+    /// Returns `true` if [`Session::destroy`] was called on the session.
+    pub fn is_destroyed(&self) -> bool {
+        self.destroyed
+    }
+
+    /// Returns the session's CSRF token, generating and storing one on first use.
+    ///
+    /// The token is a random 128-bit hex string kept under the `csrf_token`
+    /// key, so it lasts as long as the session. Embed it in forms as a hidden
+    /// `csrf_token` field, or send it in an `X-CSRF-Token` header, to pass the
+    /// check enabled by `Server::csrf`.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use rwf::connection::session::Session;
+    ///
+    /// let mut session = Session::new();
+    /// let token = session.csrf_token();
+    /// assert_eq!(token.len(), 32);
+    /// assert_eq!(session.csrf_token(), token);
+    /// ```
+    pub fn csrf_token(&mut self) -> String {
+        if let Some(token) = self.get::<String>(CSRF_KEY) {
+            return token.clone();
+        }
+        let token = random_id();
+        self.add(CSRF_KEY.to_string(), token.clone());
+        token
+    }
+
+    /// Checks a token sent by the client against the session's CSRF token.
+    ///
+    /// The comparison runs in constant time. A session without a token
+    /// matches nothing.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use rwf::connection::session::Session;
+    ///
+    /// let mut session = Session::new();
+    /// assert!(!session.verify_csrf("guess"));
+    ///
+    /// let token = session.csrf_token();
+    /// assert!(session.verify_csrf(&token));
+    /// assert!(!session.verify_csrf("guess"));
+    /// ```
+    pub fn verify_csrf(&self, token: &str) -> bool {
+        let Some(expected) = self.get::<String>(CSRF_KEY) else {
+            return false;
+        };
+        expected.len() == token.len()
+            && expected
+                .bytes()
+                .zip(token.bytes())
+                .fold(0, |diff, (a, b)| diff | (a ^ b))
+                == 0
+    }
+}
+
+/// Why a value could not be read from a session with [`Session::try_get`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SessionValueError {
+    /// Nothing is stored under the key
+    Missing,
+    /// The key holds a value of another type
+    WrongType,
+}
+
+impl fmt::Display for SessionValueError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SessionValueError::Missing => write!(f, "no session value under this key"),
+            SessionValueError::WrongType => write!(f, "session value has a different type"),
+        }
+    }
+}
+
+impl std::error::Error for SessionValueError {}
+
+/// Tries each integer type in turn, converting the first match into `$target`.
+macro_rules! downcast_int {
+    ($value:expr, $target:ty, $($int:ty),+) => {{
+        let value = $value;
+        None$(.or_else(|| value.downcast_ref::<$int>().and_then(|&n| <$target>::try_from(n).ok())))+
+    }};
+}
+use downcast_int;
+
+/// Logs a warning when a typed getter finds a value of another type.
+fn warn_on_mismatch<T>(key: &str, value: Option<T>, expected: &str) -> Option<T> {
+    if value.is_none() {
+        println!("Session value '{key}' is not {expected}");
+    }
+    value
+}
+
+impl Default for Session {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A storage backend for sessions.
+///
+/// The server loads the session named by a request's cookie before calling
+/// the handler and saves it back afterwards, so a store is free to hand out
+/// its only copy from `load`, as [`MemorySessionStore`] does, or to read a
+/// fresh copy from an external system every time.
+///
+/// ## Persisting values
+///
+/// Session values are stored as `Box<dyn Any>`, which carries no way to
+/// serialize an arbitrary type. Stores keeping sessions outside the process
+/// should therefore persist the entries holding a `String`, found through
+/// [`Session::keys`] and `Session::get::<String>`, and skip the others.
+/// Handlers relying on such a store should only put `String` values in the
+/// session, encoding anything richer themselves.
+pub trait SessionStore: Send {
+    /// Returns the session stored under `id`, if any.
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - The session ID
+    fn load(&mut self, id: &str) -> Option<Session>;
+
+    /// Stores `session` under `id`, replacing any previous session with that ID.
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - The session ID
+    /// * `session` - The session to store
+    fn save(&mut self, id: &str, session: Session);
+
+    /// Deletes the session stored under `id`, if any.
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - The session ID
+    fn remove(&mut self, id: &str);
+
+    /// Checks whether a session is stored under `id`.
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - The session ID
+    fn contains(&self, id: &str) -> bool;
+}
+
+/// The default session store, keeping sessions in process memory.
+///
+/// Sessions hold values of any type but are lost when the server stops.
+#[derive(Default)]
+pub struct MemorySessionStore {
+    /// A map of session IDs to their corresponding `Session` instances.
+    sessions: HashMap<String, Session>,
+}
+
+impl MemorySessionStore {
+    /// Creates an empty in-memory store.
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl SessionStore for MemorySessionStore {
+    /// Takes the session out of the store until it is saved back.
+    fn load(&mut self, id: &str) -> Option<Session> {
+        self.sessions.remove(id)
+    }
+
+    fn save(&mut self, id: &str, session: Session) {
+        self.sessions.insert(id.to_string(), session);
+    }
+
+    fn remove(&mut self, id: &str) {
+        self.sessions.remove(id);
+    }
+
+    fn contains(&self, id: &str) -> bool {
+        self.sessions.contains_key(id)
+    }
 }
 
 /// Manages multiple sessions, each identified by a unique ID.
 ///
 /// The `Sessions` struct provides functionality to create, store, and retrieve multiple
-/// `Session` instances.
+/// `Session` instances, kept in a [`SessionStore`].
+///
+/// `Sessions` is shared by the threads handling connections. A loaded
+/// session is checked out until it is saved back or released, and loading
+/// it again in the meantime waits, so concurrent requests from one client
+/// see each other's changes instead of overwriting them.
 pub struct Sessions {
-    /// A map of session IDs to their corresponding `Session` instances.
-    sessions: HashMap<usize, Session>,
-    /// A counter to generate unique session IDs.
-    counter: usize,
+    /// The backend the sessions are kept in.
+    store: Mutex<Box<dyn SessionStore>>,
+    /// The IDs of the sessions currently checked out.
+    in_use: Mutex<HashSet<String>>,
+    /// The IDs of checked-out sessions removed before they were saved back.
+    removed: Mutex<HashSet<String>>,
+    /// Signalled whenever a session is given back.
+    released: Condvar,
 }
 
 impl Sessions {
-    /// Creates a new, empty `Sessions` manager.
+    /// Creates a new, empty `Sessions` manager keeping sessions in memory.
     ///
     /// # Examples
     ///
-    /// ```
+    /// ```rust
+    /// use rwf::connection::session::Sessions;
+    ///
     /// let sessions = Sessions::new();
     /// ```
     pub fn new() -> Self {
+        Self::with_store(MemorySessionStore::new())
+    }
+
+    /// Creates a `Sessions` manager keeping sessions in the given store.
+    ///
+    /// # Arguments
+    ///
+    /// * `store` - The session storage backend
+    pub fn with_store(store: impl SessionStore + 'static) -> Self {
         Self {
-            sessions: HashMap::new(),
-            counter: 0,
+            store: Mutex::new(Box::new(store)),
+            in_use: Mutex::new(HashSet::new()),
+            removed: Mutex::new(HashSet::new()),
+            released: Condvar::new(),
         }
     }
 
@@ -153,48 +615,258 @@ impl Sessions {
     ///
     /// # Examples
     ///
-    /// ```
+    /// ```rust
+    /// use rwf::connection::session::Sessions;
+    ///
     /// let sessions = Sessions::new();
-    /// assert!(!sessions.contains(&1));
+    /// assert!(!sessions.contains("0123456789abcdef0123456789abcdef"));
     /// ```
-    pub fn contains(&self, id: &usize) -> bool {
-        self.sessions.contains_key(id)
+    pub fn contains(&self, id: &str) -> bool {
+        self.store().contains(id)
     }
 
     /// Adds a new session and returns its unique ID.
     ///
+    /// IDs are 128-bit values from the operating system's cryptographically
+    /// secure random number generator, rendered as 32 hex characters.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the operating system's random number generator is unavailable.
+    ///
     /// # Examples
     ///
-    /// ```
-    /// let mut sessions = Sessions::new();
+    /// ```rust
+    /// use rwf::connection::session::Sessions;
+    ///
+    /// let sessions = Sessions::new();
     /// let session_id = sessions.add();
     /// assert!(sessions.contains(&session_id));
+    /// assert_eq!(session_id.len(), 32);
     /// ```
-    pub fn add(&mut self) -> usize {
-        self.sessions.insert(self.counter, Session::new());
-        let prev = self.counter;
-        self.counter += 1;
-        prev
+    pub fn add(&self) -> String {
+        self.insert(Session::new())
     }
 
-    /// Retrieves a mutable reference to the session with the given ID.
+    /// Stores an existing session under a new unique ID and returns the ID.
     ///
     /// # Arguments
     ///
-    /// * `key` - The session ID to retrieve.
+    /// * `session` - The session to store
     ///
     /// # Panics
     ///
-    /// Panics if the session ID does not exist.
+    /// Panics if the operating system's random number generator is unavailable.
+    pub fn insert(&self, session: Session) -> String {
+        let mut store = self.store();
+        let id = loop {
+            let id = random_id();
+            if !store.contains(&id) {
+                break id;
+            }
+        };
+        store.save(&id, session);
+        id
+    }
+
+    /// Loads the session with the given ID from the store and checks it out.
+    ///
+    /// Changes to the session are kept once it is passed back to
+    /// [`Sessions::save`]. Until then, or until [`Sessions::release`] is
+    /// called, loading the same session again blocks.
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - The session ID to load.
+    ///
+    /// # Returns
+    ///
+    /// * `Some(Session)` - The session, if it exists
+    /// * `None` - If no session has this ID
     ///
     /// # Examples
     ///
+    /// ```rust
+    /// use rwf::connection::session::Sessions;
+    ///
+    /// let sessions = Sessions::new();
+    /// let session_id = sessions.add();
+    ///
+    /// let mut session = sessions.load(&session_id).unwrap();
+    /// session.add("is_logged_in".to_string(), true);
+    /// sessions.save(&session_id, session);
+    ///
+    /// let session = sessions.load(&session_id).unwrap();
+    /// assert_eq!(session.get::<bool>("is_logged_in"), Some(&true));
     /// ```
-    /// let mut sessions = Sessions::new();
+    pub fn load(&self, id: &str) -> Option<Session> {
+        let mut in_use = lock(&self.in_use);
+        while in_use.contains(id) {
+            in_use = self
+                .released
+                .wait(in_use)
+                .unwrap_or_else(|e| e.into_inner());
+        }
+        let session = self.store().load(id)?;
+        in_use.insert(id.to_string());
+        Some(session)
+    }
+
+    /// Saves a session under the given ID, checking it back in.
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - The session ID
+    /// * `session` - The session to save
+    pub fn save(&self, id: &str, session: Session) {
+        // A session removed while checked out must not come back
+        if !lock(&self.removed).remove(id) {
+            self.store().save(id, session);
+        }
+        self.release(id);
+    }
+
+    /// Checks a loaded session back in without saving it.
+    ///
+    /// With the in-memory store, the session is lost, as loading took it out
+    /// of the store.
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - The session ID
+    pub fn release(&self, id: &str) {
+        lock(&self.in_use).remove(id);
+        lock(&self.removed).remove(id);
+        self.released.notify_all();
+    }
+
+    /// Deletes the session with the given ID, if it exists.
+    ///
+    /// A session currently checked out by a request is deleted as well:
+    /// saving it back afterwards drops it instead of storing it again. Later
+    /// requests carrying the ID get a fresh session. Handlers log a user out
+    /// through [`Session::destroy`], which calls this method once they return.
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - The session ID to delete
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use rwf::connection::session::Sessions;
+    ///
+    /// let sessions = Sessions::new();
     /// let session_id = sessions.add();
-    /// let session = sessions.get(session_id);
+    ///
+    /// let session = sessions.load(&session_id).unwrap();
+    /// sessions.remove(&session_id);
+    /// sessions.save(&session_id, session);
+    /// assert!(!sessions.contains(&session_id));
+    /// assert!(sessions.load(&session_id).is_none());
     /// ```
-    pub fn get(&mut self, key: usize) -> &mut Session {
-        self.sessions.get_mut(&key).unwrap()
+    pub fn remove(&self, id: &str) {
+        let in_use = lock(&self.in_use);
+        if in_use.contains(id) {
+            lock(&self.removed).insert(id.to_string());
+        }
+        self.store().remove(id);
+    }
+
+    /// Locks the session store.
+    fn store(&self) -> MutexGuard<'_, Box<dyn SessionStore>> {
+        lock(&self.store)
     }
 }
+
+/// Locks a mutex, recovering the data if a thread panicked while holding it.
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+impl Default for Sessions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Signs a session ID, producing the value stored in the session cookie.
+///
+/// The value has the form `<id>.<signature>`, where the signature is the
+/// hex-encoded HMAC-SHA256 of the ID keyed by `secret`.
+///
+/// # Arguments
+///
+/// * `id` - The session ID to sign
+/// * `secret` - The server secret used as HMAC key
+///
+/// # Example
+///
+/// ```rust
+/// use rwf::connection::session::{sign, verify};
+///
+/// let value = sign("a1b2", b"secret");
+/// assert!(value.starts_with("a1b2."));
+/// assert_eq!(verify(&value, b"secret"), Some("a1b2".to_string()));
+/// ```
+pub fn sign(id: &str, secret: &[u8]) -> String {
+    let signature = mac(id, secret).finalize().into_bytes();
+    format!("{id}.{}", to_hex(&signature))
+}
+
+/// Verifies a signed session cookie value and returns its session ID.
+///
+/// The signature is compared in constant time.
+///
+/// # Arguments
+///
+/// * `value` - The cookie value, as produced by [`sign`]
+/// * `secret` - The server secret used as HMAC key
+///
+/// # Returns
+///
+/// * `Some(String)` - The session ID, if the signature matches
+/// * `None` - If the value is malformed or its signature does not match
+///
+/// # Example
+///
+/// ```rust
+/// use rwf::connection::session::{sign, verify};
+///
+/// let forged = sign("a1b2", b"secret").replacen('a', "b", 1);
+/// assert_eq!(verify(&forged, b"secret"), None);
+/// assert_eq!(verify(&sign("a1b2", b"other"), b"secret"), None);
+/// ```
+pub fn verify(value: &str, secret: &[u8]) -> Option<String> {
+    let (id, hex) = value.split_once('.')?;
+
+    if hex.len() % 2 != 0 || !hex.is_ascii() {
+        return None;
+    }
+    let signature = (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).ok())
+        .collect::<Option<Vec<u8>>>()?;
+
+    mac(id, secret).verify_slice(&signature).ok()?;
+    Some(id.to_string())
+}
+
+/// Builds the HMAC state over a session ID.
+fn mac(id: &str, secret: &[u8]) -> HmacSha256 {
+    let mut mac = HmacSha256::new_from_slice(secret).expect("HMAC accepts keys of any size");
+    mac.update(id.as_bytes());
+    mac
+}
+
+/// Generates a random 128-bit session ID rendered as hex.
+fn random_id() -> String {
+    let mut bytes = [0u8; 16];
+    getrandom::fill(&mut bytes).expect("failed to generate a session ID");
+    to_hex(&bytes)
+}
+
+/// Encodes bytes as lowercase hex.
+pub(crate) fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{byte:02x}")).collect()
+}
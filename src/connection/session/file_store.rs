@@ -0,0 +1,146 @@
+//! # File Session Store Module
+//!
+//! Provides a `SessionStore` persisting each session as a file, so sessions
+//! survive server restarts.
+//!
+//! ## Example
+//!
+//! ```rust
+//! use rwf::connection::session::{FileSessionStore, Session, SessionStore};
+//!
+//! let dir = std::env::temp_dir().join(format!("rwf-sessions-{}", std::process::id()));
+//!
+//! let mut store = FileSessionStore::new(&dir).unwrap();
+//! let mut session = Session::new();
+//! session.add("user".to_string(), "alice".to_string());
+//! session.add("visits".to_string(), 3u32);
+//! store.save("a1b2", session);
+//! drop(store);
+//!
+//! // A new store over the same directory finds the session again
+//! let mut store = FileSessionStore::new(&dir).unwrap();
+//! let session = store.load("a1b2").unwrap();
+//! assert_eq!(session.get::<String>("user").map(String::as_str), Some("alice"));
+//! // Only `String` values are persisted
+//! assert_eq!(session.get::<u32>("visits"), None);
+//!
+//! store.remove("a1b2");
+//! assert!(!store.contains("a1b2"));
+//! # std::fs::remove_dir_all(&dir).unwrap();
+//! ```
+
+use super::{Session, SessionStore};
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+/// A session store keeping one file per session in a directory.
+///
+/// Each file is named after its session ID and holds one `key=value` line
+/// per entry, with `%`, `=` and line breaks percent-encoded.
+///
+/// Session values are `Box<dyn Any>`, which cannot be serialized in general,
+/// so only values of type `String` are written to disk and every other value
+/// is dropped when the session is saved. This keeps the store free of extra
+/// dependencies and the files readable, at the cost of handlers encoding
+/// richer values (numbers, flags, JSON) into strings themselves. The
+/// in-memory store keeps values of any type but loses them on restart.
+#[derive(Debug, Clone)]
+pub struct FileSessionStore {
+    /// The directory session files are kept in
+    dir: PathBuf,
+}
+
+impl FileSessionStore {
+    /// Creates a store keeping sessions in `dir`, creating the directory if needed.
+    ///
+    /// # Arguments
+    ///
+    /// * `dir` - The directory session files are kept in
+    ///
+    /// # Returns
+    ///
+    /// * `io::Result<FileSessionStore>` - The store, or the IO error raised
+    ///   while creating the directory
+    pub fn new(dir: impl AsRef<Path>) -> io::Result<Self> {
+        let dir = dir.as_ref().to_path_buf();
+        fs::create_dir_all(&dir)?;
+        Ok(Self { dir })
+    }
+
+    /// Returns the file a session is kept in.
+    ///
+    /// IDs that could escape the directory, anything but ASCII letters and
+    /// digits, have no file.
+    fn path(&self, id: &str) -> Option<PathBuf> {
+        let valid = !id.is_empty() && id.bytes().all(|b| b.is_ascii_alphanumeric());
+        valid.then(|| self.dir.join(id))
+    }
+}
+
+impl SessionStore for FileSessionStore {
+    fn load(&mut self, id: &str) -> Option<Session> {
+        let contents = fs::read_to_string(self.path(id)?).ok()?;
+        let mut session = Session::new();
+        for (key, value) in contents.lines().filter_map(|line| line.split_once('=')) {
+            session.add(unescape(key), unescape(value));
+        }
+        Some(session)
+    }
+
+    /// Writes the session's `String` values to its file, replacing it.
+    ///
+    /// The file is written to a temporary name and renamed, so a crash never
+    /// leaves a half-written session behind. Errors are logged, as the
+    /// response does not depend on them.
+    fn save(&mut self, id: &str, session: Session) {
+        let Some(path) = self.path(id) else {
+            return;
+        };
+        let contents: String = session
+            .keys()
+            .into_iter()
+            .filter_map(|key| Some((key, session.get::<String>(key)?)))
+            .map(|(key, value)| format!("{}={}\n", escape(key), escape(value)))
+            .collect();
+
+        let temp = path.with_extension("tmp");
+        if let Err(e) = fs::write(&temp, contents).and_then(|_| fs::rename(&temp, &path)) {
+            println!("Failed to save session {id}: {e}");
+        }
+    }
+
+    fn remove(&mut self, id: &str) {
+        if let Some(path) = self.path(id) {
+            let _ = fs::remove_file(path);
+        }
+    }
+
+    fn contains(&self, id: &str) -> bool {
+        self.path(id).is_some_and(|path| path.is_file())
+    }
+}
+
+/// Percent-encodes the characters that would break the `key=value` line format.
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '%' => escaped.push_str("%25"),
+            '=' => escaped.push_str("%3D"),
+            '\n' => escaped.push_str("%0A"),
+            '\r' => escaped.push_str("%0D"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Reverses [`escape`].
+fn unescape(text: &str) -> String {
+    text.replace("%3D", "=")
+        .replace("%0A", "\n")
+        .replace("%0D", "\r")
+        .replace("%25", "%")
+}
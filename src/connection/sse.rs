@@ -0,0 +1,149 @@
+//! # Server-Sent Events Module
+//!
+//! Streams `text/event-stream` responses to the client.
+//!
+//! Handlers keep their usual signature and return a `Response`. A response
+//! built with [`Response::event_stream`](super::response::Response::event_stream)
+//! carries a producer closure instead of a body. Once the handler returns,
+//! the connection writes the response head, then runs the producer with an
+//! [`EventStream`], which writes and flushes each event as it is sent. The
+//! connection is closed when the producer returns, since the length of the
+//! body is not known up front.
+//!
+//! The producer runs on the connection's own thread, so it may block while
+//! waiting for the next event, e.g. on a channel receiver.
+//!
+//! ## Example
+//!
+//! ```rust,ignore
+//! server.route("/clock", |_req, _session| {
+//!     Response::event_stream(|events| {
+//!         for tick in 0..10 {
+//!             events.event("tick", &tick.to_string())?;
+//!             thread::sleep(Duration::from_secs(1));
+//!         }
+//!         Ok(())
+//!     })
+//! });
+//! ```
+
+use super::Stream;
+use std::{
+    fmt, io,
+    sync::{Arc, Mutex},
+};
+
+/// The closure producing the events of a streaming response.
+type Producer = Box<dyn FnOnce(&mut EventStream) -> io::Result<()> + Send>;
+
+/// The writer a streaming response pushes its events to.
+///
+/// Every method writes one complete event and flushes it, so it reaches the
+/// client right away. An error means the client has gone away and the
+/// producer should stop.
+pub struct EventStream<'a> {
+    stream: &'a mut dyn Stream,
+}
+
+impl<'a> EventStream<'a> {
+    /// Wraps the connection a streaming response is written to.
+    pub(crate) fn new(stream: &'a mut dyn Stream) -> Self {
+        Self { stream }
+    }
+
+    /// Sends an unnamed event, received by the client's `onmessage` handler.
+    ///
+    /// Multi-line data is split into several `data:` lines, which the client
+    /// joins back together. Lines may end in `\n`, `\r\n` or `\r`.
+    ///
+    /// # Arguments
+    ///
+    /// * `data` - The event data
+    pub fn send(&mut self, data: &str) -> io::Result<()> {
+        self.write(&format!("{}\n", data_lines(data)))
+    }
+
+    /// Sends a named event, received by the client's listener for `name`.
+    ///
+    /// Line breaks in `name` are dropped, as they would end the field and
+    /// start another one. `data` is split into lines as in
+    /// [`EventStream::send`].
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The event type
+    /// * `data` - The event data
+    pub fn event(&mut self, name: &str, data: &str) -> io::Result<()> {
+        let name = name.replace(['\r', '\n'], "");
+        self.write(&format!("event: {name}\n{}\n", data_lines(data)))
+    }
+
+    /// Sends a comment, ignored by the client.
+    ///
+    /// Useful to keep idle connections from being closed by proxies.
+    /// Multi-line text is sent as one comment line per line, so no line can
+    /// be read as a field.
+    ///
+    /// # Arguments
+    ///
+    /// * `text` - The comment text
+    pub fn comment(&mut self, text: &str) -> io::Result<()> {
+        let comment: String = lines(text).map(|line| format!(": {line}\n")).collect();
+        self.write(&format!("{comment}\n"))
+    }
+
+    /// Writes raw event-stream text and flushes it.
+    fn write(&mut self, text: &str) -> io::Result<()> {
+        self.stream.write_all(text.as_bytes())?;
+        self.stream.flush()
+    }
+}
+
+/// Formats data as `data:` lines, one per line of input.
+fn data_lines(data: &str) -> String {
+    lines(data).map(|line| format!("data: {line}\n")).collect()
+}
+
+/// Splits text into lines ending in `\r\n`, `\r` or `\n`, the line breaks
+/// of the event-stream format.
+fn lines(text: &str) -> impl Iterator<Item = &str> {
+    text.split('\n')
+        .flat_map(|line| line.strip_suffix('\r').unwrap_or(line).split('\r'))
+}
+
+/// The producer of a streaming response, run once by the connection.
+///
+/// Cloning a response shares the producer, which only the first clone to be
+/// sent gets to run.
+#[derive(Clone)]
+pub struct StreamBody(Arc<Mutex<Option<Producer>>>);
+
+impl StreamBody {
+    /// Wraps a producer closure.
+    pub(crate) fn new(
+        producer: impl FnOnce(&mut EventStream) -> io::Result<()> + Send + 'static,
+    ) -> Self {
+        Self(Arc::new(Mutex::new(Some(Box::new(producer)))))
+    }
+
+    /// Runs the producer against the connection, unless it already ran.
+    pub(crate) fn run(&self, stream: &mut dyn Stream) -> io::Result<()> {
+        let producer = self.0.lock().unwrap_or_else(|e| e.into_inner()).take();
+        match producer {
+            Some(producer) => producer(&mut EventStream::new(stream)),
+            None => Ok(()),
+        }
+    }
+}
+
+impl fmt::Debug for StreamBody {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("StreamBody")
+    }
+}
+
+impl PartialEq for StreamBody {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
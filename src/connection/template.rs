@@ -0,0 +1,188 @@
+//! # Template Module
+//!
+//! Renders templates chosen at runtime from a registered directory.
+//!
+//! Unlike the `template!` macro, which takes the path of one file,
+//! [`Templates::render`] looks templates up by name in a directory, so
+//! handlers can pick the template while they run. Handlers reach the
+//! directory registered with `Server::templates` through
+//! `Request::render`, so each server keeps its own. Templates are read on
+//! every call, so edits show up without a restart. Placeholders use the same
+//! `$name` format as `template!`. Values are HTML-escaped, unless rendered
+//! with [`Templates::render_raw`].
+//!
+//! ## Example
+//!
+//! ```rust
+//! use rwf::connection::template::{TemplateError, Templates};
+//! use std::fs;
+//!
+//! let dir = std::env::temp_dir().join(format!("rwf-templates-{}", std::process::id()));
+//! fs::create_dir_all(dir.join("emails")).unwrap();
+//! fs::write(dir.join("emails/welcome.txt"), "Welcome $name, your plan is $plan.").unwrap();
+//!
+//! let templates = Templates::new(dir.to_str().unwrap()).unwrap();
+//! let text = templates.render("emails/welcome.txt", &[("name", "Ferris"), ("plan", "pro")]);
+//! assert_eq!(text.unwrap(), "Welcome Ferris, your plan is pro.");
+//!
+//! // Values are escaped, unless rendered raw
+//! let text = templates.render("emails/welcome.txt", &[("name", "<b>Ferris</b>"), ("plan", "a&b")]);
+//! assert_eq!(text.unwrap(), "Welcome &lt;b&gt;Ferris&lt;/b&gt;, your plan is a&amp;b.");
+//! let text = templates.render_raw("emails/welcome.txt", &[("name", "<b>Ferris</b>"), ("plan", "pro")]);
+//! assert_eq!(text.unwrap(), "Welcome <b>Ferris</b>, your plan is pro.");
+//!
+//! // Missing templates and names leaving the directory are errors, not panics
+//! assert!(matches!(templates.render("missing.txt", &[]), Err(TemplateError::NotFound(_))));
+//! assert!(matches!(templates.render("../secret.txt", &[]), Err(TemplateError::NotFound(_))));
+//! ```
+
+use super::response::HttpError;
+use std::{
+    error::Error,
+    fmt, fs, io,
+    path::{Component, Path, PathBuf},
+};
+
+/// A directory of templates rendered at runtime.
+///
+/// Registered on a server with `Server::templates`, and used by handlers
+/// through `Request::render`.
+#[derive(Debug, Clone)]
+pub struct Templates {
+    /// The canonicalized directory templates are looked up in
+    directory: PathBuf,
+}
+
+/// Errors that can occur while rendering a template.
+///
+/// Converts into an [`HttpError`] with status `500`, so handlers returning
+/// `Result<_, HttpError>` can use `?` on [`Templates::render`].
+#[derive(Debug)]
+pub enum TemplateError {
+    /// No template directory was registered with `Server::templates`, when
+    /// rendering through `Request::render`.
+    NoDirectory,
+    /// No template with the given name exists inside the directory.
+    NotFound(String),
+    /// The template exists but could not be read, e.g. because it is not UTF-8.
+    Io(io::Error),
+}
+
+impl fmt::Display for TemplateError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TemplateError::NoDirectory => write!(f, "no template directory registered"),
+            TemplateError::NotFound(name) => write!(f, "template '{name}' not found"),
+            TemplateError::Io(e) => write!(f, "failed to read template: {e}"),
+        }
+    }
+}
+
+impl Error for TemplateError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            TemplateError::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<TemplateError> for HttpError {
+    /// Logs the error and hides its details from the client.
+    fn from(error: TemplateError) -> Self {
+        println!("Failed to render template: {error}");
+        HttpError::internal_server_error()
+    }
+}
+
+impl Templates {
+    /// Opens a directory of templates.
+    ///
+    /// # Arguments
+    ///
+    /// * `dir` - The directory holding the templates
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(Templates)` - The directory, ready to render from
+    /// * `Err(io::Error)` - If `dir` does not exist or is not a directory
+    pub fn new(dir: &str) -> io::Result<Self> {
+        let directory = fs::canonicalize(dir)?;
+        if !directory.is_dir() {
+            return Err(io::Error::other("not a directory"));
+        }
+        Ok(Self { directory })
+    }
+
+    /// Renders a template, HTML-escaping the values.
+    ///
+    /// Every `$key` placeholder is replaced with its escaped value. Longer
+    /// keys are replaced first, so `$name` does not clobber the start of
+    /// `$names`.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The path of the template relative to the directory, e.g. `home.html`
+    /// * `context` - The placeholder names, without `$`, and their values
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(String)` - The rendered template
+    /// * `Err(TemplateError::NotFound)` - If the template does not exist or its
+    ///   name points outside of the directory
+    /// * `Err(TemplateError::Io)` - If the template cannot be read
+    pub fn render(&self, name: &str, context: &[(&str, &str)]) -> Result<String, TemplateError> {
+        let escaped: Vec<(&str, String)> = context
+            .iter()
+            .map(|(key, value)| (*key, crate::html_escape(value)))
+            .collect();
+        self.substitute(
+            name,
+            escaped.iter().map(|(key, value)| (*key, value.as_str())),
+        )
+    }
+
+    /// Renders a template, inserting the values as given.
+    ///
+    /// Only for values known to be safe HTML, such as markup rendered by
+    /// another template: user data passed here can inject scripts into the
+    /// page. See [`Templates::render`] for the arguments and errors.
+    pub fn render_raw(
+        &self,
+        name: &str,
+        context: &[(&str, &str)],
+    ) -> Result<String, TemplateError> {
+        self.substitute(name, context.iter().copied())
+    }
+
+    /// Reads a template and replaces its placeholders with the given values.
+    fn substitute<'a>(
+        &self,
+        name: &str,
+        context: impl Iterator<Item = (&'a str, &'a str)>,
+    ) -> Result<String, TemplateError> {
+        let path = resolve(&self.directory, name)
+            .ok_or_else(|| TemplateError::NotFound(name.to_string()))?;
+        let mut template = fs::read_to_string(path).map_err(TemplateError::Io)?;
+
+        let mut context: Vec<_> = context.collect();
+        context.sort_by_key(|(key, _)| std::cmp::Reverse(key.len()));
+        for (key, value) in context {
+            template = template.replace(&format!("${key}"), value);
+        }
+        Ok(template)
+    }
+}
+
+/// Finds the file a template name refers to, staying inside `directory`.
+fn resolve(directory: &Path, name: &str) -> Option<PathBuf> {
+    let relative = Path::new(name);
+    if !relative
+        .components()
+        .all(|component| matches!(component, Component::Normal(_)))
+    {
+        return None;
+    }
+    let path = directory.join(relative).canonicalize().ok()?;
+    (path.starts_with(directory) && path.is_file()).then_some(path)
+}
@@ -0,0 +1,141 @@
+//! # Tracker Module
+//!
+//! Keeps track of the connections a server is serving, so it can drain
+//! them when it shuts down.
+//!
+//! Every accepted connection registers a handle to its socket and reports
+//! whether it is idle, waiting for the next request, or busy with one.
+//! Draining closes idle connections right away, lets busy ones finish their
+//! current request within a grace period and then closes whatever is left.
+
+use std::{
+    collections::HashMap,
+    net::{Shutdown, TcpStream},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Condvar, Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+/// The open connections of a server.
+#[derive(Debug, Default)]
+pub(crate) struct ConnectionTracker {
+    /// The open connections, keyed by registration order
+    open: Mutex<Open>,
+    /// Notified whenever a connection closes
+    closed: Condvar,
+    /// The key of the next registered connection
+    next_id: AtomicU64,
+}
+
+/// The state guarded by the tracker's lock.
+#[derive(Debug, Default)]
+struct Open {
+    connections: HashMap<u64, Entry>,
+    /// Whether the server is shutting down, so connections must not take
+    /// further requests
+    draining: bool,
+}
+
+/// A registered connection.
+#[derive(Debug)]
+struct Entry {
+    socket: TcpStream,
+    idle: bool,
+}
+
+impl ConnectionTracker {
+    /// Creates a tracker without connections.
+    pub(crate) fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers a connection, which counts as open until the returned
+    /// handle is dropped.
+    pub(crate) fn register(self: &Arc<Self>, socket: TcpStream) -> Tracked {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        self.lock()
+            .connections
+            .insert(id, Entry { socket, idle: true });
+        Tracked {
+            tracker: Arc::clone(self),
+            id,
+        }
+    }
+
+    /// Returns the number of open connections.
+    pub(crate) fn len(&self) -> usize {
+        self.lock().connections.len()
+    }
+
+    /// Closes every connection, giving busy ones `grace` to finish their
+    /// current request first.
+    ///
+    /// Returns the number of connections closed while still busy.
+    pub(crate) fn drain(&self, grace: Duration) -> usize {
+        let mut open = self.lock();
+        open.draining = true;
+        for entry in open.connections.values().filter(|entry| entry.idle) {
+            let _ = entry.socket.shutdown(Shutdown::Both);
+        }
+
+        let deadline = Instant::now() + grace;
+        while !open.connections.is_empty() {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining.is_zero() {
+                break;
+            }
+            open = self
+                .closed
+                .wait_timeout(open, remaining)
+                .unwrap_or_else(|e| e.into_inner())
+                .0;
+        }
+
+        for entry in open.connections.values() {
+            let _ = entry.socket.shutdown(Shutdown::Both);
+        }
+        let forced = open.connections.len();
+        // Lets the server run again later
+        open.draining = false;
+        forced
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Open> {
+        self.open.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// A connection registered with a [`ConnectionTracker`], unregistered on drop.
+#[derive(Debug)]
+pub(crate) struct Tracked {
+    tracker: Arc<ConnectionTracker>,
+    id: u64,
+}
+
+impl Tracked {
+    /// Marks the connection as idle between requests or busy with one.
+    ///
+    /// Returns `false` if the server is draining, in which case a connection
+    /// turning idle should close.
+    pub(crate) fn set_idle(&self, idle: bool) -> bool {
+        let mut open = self.tracker.lock();
+        if let Some(entry) = open.connections.get_mut(&self.id) {
+            entry.idle = idle;
+        }
+        !open.draining
+    }
+
+    /// Returns whether the server is draining its connections.
+    pub(crate) fn is_draining(&self) -> bool {
+        self.tracker.lock().draining
+    }
+}
+
+impl Drop for Tracked {
+    fn drop(&mut self) {
+        self.tracker.lock().connections.remove(&self.id);
+        self.tracker.closed.notify_all();
+    }
+}
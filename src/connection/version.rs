@@ -0,0 +1,66 @@
+//! # Version Module
+//!
+//! Defines the HTTP versions understood by the server.
+//!
+//! The version decides whether a connection stays open after a response:
+//! HTTP/1.1 connections are persistent unless the client asks otherwise,
+//! HTTP/1.0 connections are closed unless the client asks to keep them alive.
+//!
+//! ## Example
+//!
+//! ```rust
+//! use rwf::connection::version::Version;
+//!
+//! assert_eq!(Version::from_string("HTTP/1.0"), Version::Http10);
+//! assert_eq!(Version::Http11.to_string(), "HTTP/1.1");
+//! ```
+
+use std::fmt;
+
+/// Represents the HTTP version of a request.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Version {
+    /// HTTP/1.0, closing the connection after each response by default
+    Http10,
+    /// HTTP/1.1, keeping the connection alive by default
+    Http11,
+}
+
+impl Version {
+    /// Converts the version token of a request line to a `Version`.
+    ///
+    /// Anything but `HTTP/1.0` is treated as HTTP/1.1, the version the server
+    /// speaks.
+    ///
+    /// # Arguments
+    ///
+    /// * `string` - The version token, e.g. "HTTP/1.1"
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use rwf::connection::version::Version;
+    ///
+    /// assert_eq!(Version::from_string("HTTP/1.1"), Version::Http11);
+    /// ```
+    pub fn from_string(string: &str) -> Self {
+        match string {
+            "HTTP/1.0" => Self::Http10,
+            _ => Self::Http11,
+        }
+    }
+
+    /// Returns whether connections of this version stay open by default.
+    pub fn keeps_alive(&self) -> bool {
+        *self == Self::Http11
+    }
+}
+
+impl fmt::Display for Version {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Version::Http10 => write!(f, "HTTP/1.0"),
+            Version::Http11 => write!(f, "HTTP/1.1"),
+        }
+    }
+}
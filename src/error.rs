@@ -0,0 +1,124 @@
+//! # Error Module
+//!
+//! Defines the errors reported while setting up and running a `Server`.
+//!
+//! `ServerError` separates failures by where they happen, so callers can tell
+//! a port that is already taken apart from a bad configuration or an IO
+//! failure while serving. It converts into an `io::Error`, so functions
+//! returning `io::Result` can still use `?` on `Server::build`.
+//!
+//! ## Example
+//!
+//! ```rust
+//! use rwf::{Server, ServerError};
+//! use std::net::TcpListener;
+//!
+//! let taken = TcpListener::bind("127.0.0.1:0").unwrap();
+//! let addr = taken.local_addr().unwrap().to_string();
+//!
+//! match Server::build(&addr) {
+//!     Err(ServerError::Bind { addr: attempted, .. }) => assert_eq!(attempted, addr),
+//!     _ => panic!("expected a bind error"),
+//! }
+//! ```
+
+use std::{error::Error, fmt, io};
+
+/// An error raised while building or running a `Server`.
+#[derive(Debug)]
+pub enum ServerError {
+    /// The listener could not be bound to the requested address
+    Bind {
+        /// The address passed to `Server::build`
+        addr: String,
+        /// The error returned by the operating system
+        source: io::Error,
+    },
+    /// The server was given a setting it cannot use, such as an unreadable
+    /// certificate or a missing directory
+    Config(String),
+    /// An IO error while the server was running
+    Io(io::Error),
+}
+
+impl ServerError {
+    /// Builds a `Bind` error for the given address.
+    pub(crate) fn bind(addr: &str, source: io::Error) -> Self {
+        Self::Bind {
+            addr: addr.to_string(),
+            source,
+        }
+    }
+}
+
+impl fmt::Display for ServerError {
+    /// Formats the error with a hint about how to fix it, when one is known.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use rwf::ServerError;
+    /// use std::io;
+    ///
+    /// let error = ServerError::Bind {
+    ///     addr: "127.0.0.1:8080".to_string(),
+    ///     source: io::ErrorKind::AddrInUse.into(),
+    /// };
+    /// assert!(error.to_string().starts_with("failed to bind to 127.0.0.1:8080: the address is already in use"));
+    /// ```
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ServerError::Bind { addr, source } => {
+                write!(f, "failed to bind to {addr}: ")?;
+                match source.kind() {
+                    io::ErrorKind::AddrInUse => write!(
+                        f,
+                        "the address is already in use (is another server running on this port?)"
+                    ),
+                    io::ErrorKind::AddrNotAvailable => write!(
+                        f,
+                        "the address is not available on this machine (check the host part)"
+                    ),
+                    io::ErrorKind::PermissionDenied => write!(
+                        f,
+                        "permission denied (ports below 1024 usually need elevated privileges)"
+                    ),
+                    io::ErrorKind::InvalidInput => {
+                        write!(f, "the address is not in the \"host:port\" format")
+                    }
+                    _ => write!(f, "{source}"),
+                }
+            }
+            ServerError::Config(message) => write!(f, "invalid server configuration: {message}"),
+            ServerError::Io(e) => write!(f, "IO error: {e}"),
+        }
+    }
+}
+
+impl Error for ServerError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ServerError::Bind { source, .. } => Some(source),
+            ServerError::Config(_) => None,
+            ServerError::Io(e) => Some(e),
+        }
+    }
+}
+
+impl From<io::Error> for ServerError {
+    fn from(error: io::Error) -> Self {
+        Self::Io(error)
+    }
+}
+
+impl From<ServerError> for io::Error {
+    /// Converts the error back into an `io::Error`, keeping the original kind
+    /// and using the descriptive message.
+    fn from(error: ServerError) -> Self {
+        let kind = match &error {
+            ServerError::Bind { source, .. } | ServerError::Io(source) => source.kind(),
+            ServerError::Config(_) => io::ErrorKind::InvalidInput,
+        };
+        io::Error::new(kind, error)
+    }
+}
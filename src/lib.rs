@@ -7,11 +7,12 @@
 //! - Static file serving
 //! - Session management
 //! - TCP connection handling
+//! - Optional HTTPS through rustls (`tls` feature)
 //! - Custom response methods
 //!
 //! ## Example
 //!
-//! ```rust
+//! ```rust,no_run
 //! use rwf::Server;
 //!
 //! fn main() -> std::io::Result<()> {
@@ -23,7 +24,7 @@
 //!     });
 //!     
 //!     // Serve static files from 'css' directory
-//!     server.static_dir("css/");
+//!     server.static_dir("css/")?;
 //!     
 //!     // Start the server
 //!     server.run();
@@ -31,156 +32,2962 @@
 //! }
 //! ```
 
+mod builder;
+mod config;
 pub mod connection;
+pub mod error;
 mod macros;
-mod routing;
+pub mod routing;
+#[doc(hidden)]
+pub mod test_support;
+#[cfg(feature = "tls")]
+mod tls;
 
 use crate::{
+    config::{Config, HealthCheck, MetricsEndpoint, SpaFallback},
     connection::{
+        clock::Clock,
+        method::Method,
+        metrics::Metrics,
+        rate_limit::RateLimiter,
         request::Request,
-        session::{Session, Sessions},
+        response::{self, HttpError, IntoResponse, Response},
+        secure_headers::SecureHeaders,
+        session::{Session, SessionStore, Sessions},
+        template::Templates,
+        tracker::ConnectionTracker,
         RequestHandler,
     },
-    routing::Routes,
+    routing::{Handler, HostRoutes, Route, RouteInfo, Routes},
 };
+pub use builder::ServerBuilder;
+pub use error::ServerError;
 use std::{
-    cell::RefCell,
-    env::current_dir,
-    fs::{read_dir, read_to_string},
-    io,
-    net::TcpListener,
-    path::PathBuf,
-    rc::Rc,
+    env::{self, current_dir},
+    fs::{self, read_dir},
+    io::{self, Write},
+    net::{SocketAddr, TcpListener, TcpStream},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, RwLock, RwLockWriteGuard,
+    },
+    thread,
+    time::{Duration, Instant},
 };
 
 /// A TCP-based HTTP server with routing and session management capabilities.
 pub struct Server {
     listener: TcpListener,
-    routes: Rc<RefCell<Routes>>,
-    sessions: Rc<RefCell<Sessions>>,
+    routes: Arc<RwLock<Routes>>,
+    sessions: Arc<Sessions>,
+    connections: Arc<ConnectionTracker>,
+    /// The number of connection threads started so far, numbering their names
+    workers: AtomicUsize,
+    /// The number of refused connections still being answered
+    refusing: Arc<AtomicUsize>,
+    config: Config,
+    on_start: Option<fn(&Server)>,
+    quiet: bool,
+    #[cfg(feature = "tls")]
+    tls: Option<Arc<rustls::ServerConfig>>,
 }
 
-impl Server {
-    /// Creates a new server instance bound to the specified address.
+impl Server {
+    /// Creates a new server instance bound to the specified address.
+    ///
+    /// # Arguments
+    ///
+    /// * `addr` - A string slice containing the address in format "host:port"
+    ///
+    /// # Returns
+    ///
+    /// * `Result<Server, ServerError>` - A new server instance, or a
+    ///   `ServerError::Bind` naming the address if it cannot be bound
+    ///
+    /// # Example
+    ///
+    /// ```rust,ignore
+    /// let server = Server::build("127.0.0.1:8080")?;
+    /// ```
+    pub fn build(addr: &str) -> Result<Self, ServerError> {
+        let listener = TcpListener::bind(addr).map_err(|e| ServerError::bind(addr, e))?;
+        Ok(Self {
+            listener,
+            routes: Arc::new(RwLock::new(Routes::new())),
+            sessions: Arc::new(Sessions::new()),
+            connections: Arc::new(ConnectionTracker::new()),
+            workers: AtomicUsize::new(0),
+            refusing: Arc::new(AtomicUsize::new(0)),
+            config: Config::default(),
+            on_start: None,
+            quiet: env::var_os("RWF_QUIET").is_some_and(|value| value != "0"),
+            #[cfg(feature = "tls")]
+            tls: None,
+        })
+    }
+
+    /// Starts configuring a server through chained method calls.
+    ///
+    /// Use this instead of [`Server::build`] to set several options at once;
+    /// see [`ServerBuilder`] for the settings and their defaults.
+    ///
+    /// # Example
+    ///
+    /// ```rust,ignore
+    /// let server = Server::builder()
+    ///     .timeout(Duration::from_secs(10))
+    ///     .max_body(64 * 1024)
+    ///     .secret(&std::env::var("SESSION_SECRET").unwrap())
+    ///     .build("127.0.0.1:8080")?;
+    /// ```
+    pub fn builder() -> ServerBuilder {
+        ServerBuilder::new()
+    }
+
+    /// Creates a new HTTPS server instance bound to the specified address.
+    ///
+    /// Every accepted connection completes a TLS handshake before its request
+    /// is parsed. Connections whose handshake fails are dropped.
+    ///
+    /// Available with the `tls` feature.
+    ///
+    /// # Arguments
+    ///
+    /// * `addr` - A string slice containing the address in format "host:port"
+    /// * `cert_path` - Path to a PEM file with the certificate chain: the server
+    ///   certificate first, followed by any intermediate `CERTIFICATE` blocks
+    /// * `key_path` - Path to a PEM file with the private key, encoded as
+    ///   `PRIVATE KEY` (PKCS#8), `RSA PRIVATE KEY` (PKCS#1) or `EC PRIVATE KEY` (SEC1)
+    ///
+    /// # Returns
+    ///
+    /// * `Result<Server, ServerError>` - A new server instance, a
+    ///   `ServerError::Config` if the certificate/key cannot be loaded, or a
+    ///   `ServerError::Bind` if binding fails
+    ///
+    /// # Example
+    ///
+    /// ```rust,ignore
+    /// let server = Server::build_tls("127.0.0.1:8443", "certs/cert.pem", "certs/key.pem")?;
+    /// ```
+    #[cfg(feature = "tls")]
+    pub fn build_tls(addr: &str, cert_path: &str, key_path: &str) -> Result<Self, ServerError> {
+        let config = tls::load_config(cert_path, key_path)
+            .map_err(|e| ServerError::Config(e.to_string()))?;
+        Self::build(addr).map(|server| Self {
+            tls: Some(config),
+            ..server
+        })
+    }
+
+    /// Returns the local address the server is bound to.
+    ///
+    /// Useful when binding to port `0`, where the operating system picks the port.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use rwf::Server;
+    ///
+    /// let server = Server::build("127.0.0.1:0").unwrap();
+    /// assert_ne!(server.address().unwrap().port(), 0);
+    /// ```
+    pub fn address(&self) -> io::Result<SocketAddr> {
+        self.listener.local_addr()
+    }
+
+    /// Silences the startup messages printed by [`Server::run`].
+    ///
+    /// Unless quiet, `run` prints the address the server is bound to and a
+    /// notice once it starts accepting connections. Servers start quiet when
+    /// the `RWF_QUIET` environment variable is set to anything but `0`, which
+    /// makes the default configurable without touching the code.
+    ///
+    /// # Arguments
+    ///
+    /// * `quiet` - Whether the startup messages are suppressed
+    ///
+    /// # Example
+    ///
+    /// ```rust,ignore
+    /// server.quiet(true);
+    /// ```
+    pub fn quiet(&mut self, quiet: bool) {
+        self.quiet = quiet;
+    }
+
+    /// Registers a callback invoked once when the server starts running.
+    ///
+    /// The callback runs at the top of [`Server::run`], after the listener is
+    /// bound and right before connections are accepted. Registering a new
+    /// callback replaces the previous one.
+    ///
+    /// # Arguments
+    ///
+    /// * `f` - The callback, receiving the server
+    ///
+    /// # Example
+    ///
+    /// ```rust,ignore
+    /// server.on_start(|server| {
+    ///     println!("Ready on {}", server.address().unwrap());
+    /// });
+    /// ```
+    pub fn on_start(&mut self, f: fn(&Server)) {
+        self.on_start = Some(f);
+    }
+
+    /// Adds baseline security headers to every response.
+    ///
+    /// Enables the headers listed in [`SecureHeaders`]: `X-Content-Type-Options:
+    /// nosniff`, `X-Frame-Options: DENY`, `Referrer-Policy: no-referrer` and
+    /// `Content-Security-Policy: default-src 'self'`. The returned set can
+    /// change, remove or add headers. Responses that already carry one of
+    /// them keep their own value, and the headers are added before the
+    /// [`Server::after_response`] callbacks run.
+    ///
+    /// # Returns
+    ///
+    /// The header set, to adjust with chained calls
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use rwf::{connection::response::Response, Server};
+    ///
+    /// let mut server = Server::build("127.0.0.1:0").unwrap();
+    /// server
+    ///     .secure_headers()
+    ///     .content_security_policy("default-src 'self'; img-src *");
+    /// server.route("/", |_req, _session| "Home".to_string());
+    /// server.route("/embed", |_req, _session| {
+    ///     Response::new(200, "Widget").with_header("X-Frame-Options", "SAMEORIGIN")
+    /// });
+    /// # let addr = rwf::test_support::serve(server);
+    /// # let get = |path: &str| rwf::test_support::get(addr, path);
+    ///
+    /// let home = get("/");
+    /// assert!(home.contains("X-Content-Type-Options: nosniff\r\n"));
+    /// assert!(home.contains("X-Frame-Options: DENY\r\n"));
+    /// assert!(home.contains("Referrer-Policy: no-referrer\r\n"));
+    /// assert!(home.contains("Content-Security-Policy: default-src 'self'; img-src *\r\n"));
+    ///
+    /// // A handler's own value wins
+    /// let embed = get("/embed");
+    /// assert!(embed.contains("X-Frame-Options: SAMEORIGIN\r\n") && !embed.contains("DENY"));
+    /// ```
+    pub fn secure_headers(&mut self) -> &mut SecureHeaders {
+        self.config
+            .secure_headers
+            .get_or_insert_with(SecureHeaders::default)
+    }
+
+    /// Registers a callback run on every response just before it is sent.
+    ///
+    /// Callbacks see the request, without its body, and may change the
+    /// response, for example to add headers every page should carry. They
+    /// run in registration order, on handler responses as well as on the
+    /// server's own, such as `404 Not Found` or the health check, but not on
+    /// the responses to requests that could not be parsed. `X-Request-Id`
+    /// and the default `Content-Type` are already set when they run.
+    ///
+    /// The body can be read and replaced through `response.body`, e.g. to
+    /// minify HTML. The `Content-Length` header is computed from the body
+    /// once every callback has run, so it always matches what is sent.
+    /// Streamed bodies are sent as produced and cannot be changed this way.
+    ///
+    /// # Arguments
+    ///
+    /// * `f` - The callback, receiving the request and the response
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use rwf::Server;
+    ///
+    /// let mut server = Server::build("127.0.0.1:0").unwrap();
+    /// server.after_response(|_req, response| {
+    ///     response.set_header("X-Content-Type-Options", "nosniff");
+    /// });
+    /// server.after_response(|req, response| {
+    ///     response.set_header("X-Path", &req.resource);
+    /// });
+    /// server.route("/hello", |_req, _session| "Hello".to_string());
+    /// # let addr = rwf::test_support::serve(server);
+    /// # let get = |path: &str| rwf::test_support::get(addr, path);
+    ///
+    /// let hello = get("/hello");
+    /// assert!(hello.contains("X-Content-Type-Options: nosniff\r\n"));
+    /// assert!(hello.contains("X-Path: /hello\r\n") && hello.ends_with("Hello"));
+    /// assert!(get("/missing").contains("X-Content-Type-Options: nosniff\r\n"));
+    /// ```
+    ///
+    /// Callbacks rewriting the body get a matching `Content-Length`:
+    ///
+    /// ```rust
+    /// use rwf::{connection::response::Response, Server};
+    ///
+    /// let mut server = Server::build("127.0.0.1:0").unwrap();
+    /// server.after_response(|_req, response| {
+    ///     let body = String::from_utf8_lossy(&response.body).trim().to_uppercase();
+    ///     response.body = body.into_bytes();
+    /// });
+    /// server.route("/shout", |_req, _session| {
+    ///     Response::new(200, "  hello  \n").with_header("Content-Length", "10")
+    /// });
+    /// # let addr = rwf::test_support::serve(server);
+    /// # let send = |request: &str| rwf::test_support::send(addr, request);
+    ///
+    /// let response = send("GET /shout HTTP/1.1\r\nConnection: close\r\n\r\n");
+    /// assert!(response.contains("Content-Length: 5\r\n") && !response.contains("Content-Length: 10"));
+    /// assert!(response.ends_with("\r\n\r\nHELLO"));
+    /// ```
+    pub fn after_response(&mut self, f: fn(&Request, &mut Response)) {
+        self.config.after_response.push(f);
+    }
+
+    /// Starts the server and begins listening for incoming connections.
+    ///
+    /// This method runs indefinitely, processing incoming connections and
+    /// routing requests to appropriate handlers. The [`Server::on_start`]
+    /// callback, if any, runs first, followed by the startup messages unless
+    /// the server is [`quiet`](Server::quiet).
+    ///
+    /// Each connection is handled on its own thread, so a slow client or
+    /// handler does not hold up the others. HTTP/1.1 connections are kept
+    /// open for further requests, see [`RequestHandler::resolve`](connection::RequestHandler::resolve), and are
+    /// closed once the client stays silent for 5 seconds (see
+    /// [`ServerBuilder::timeout`]), whether idle
+    /// between requests or in the middle of sending one. A panicking handler
+    /// only fails its own request, answered with `500 Internal Server Error`,
+    /// see [`Server::thread_name`].
+    ///
+    /// Errors accepting a connection are logged and the loop carries on. When
+    /// the error comes from the client side, such as a connection aborted or
+    /// reset before it was accepted, the next connection is accepted right
+    /// away. Any other error is treated as fatal to the attempt, typically the
+    /// server running out of resources (`EMFILE`, `ENFILE`, `ENOBUFS` or
+    /// `ENOMEM`), and the loop backs off for 100 milliseconds before retrying
+    /// instead of spinning.
+    ///
+    /// # Example
+    ///
+    /// ```rust,ignore
+    /// let mut server = Server::build("127.0.0.1:8080")?;
+    /// server.run();
+    /// ```
+    ///
+    /// HTTP/1.1 connections serve several requests, HTTP/1.0 ones a single one:
+    ///
+    /// ```rust
+    /// use rwf::Server;
+    /// use std::{io::{BufRead, BufReader, Write}, net::TcpStream};
+    ///
+    /// let mut server = Server::build("127.0.0.1:0").unwrap();
+    /// server.route("/", |_req, _session| "Hi".to_string());
+    /// # let addr = rwf::test_support::serve(server);
+    /// # let send = |request: &str| rwf::test_support::send(addr, request);
+    ///
+    /// // Reads one response: its header lines, then a body of `Content-Length` bytes
+    /// fn read_response(reader: &mut impl BufRead) -> String {
+    ///     let mut head = String::new();
+    ///     while !head.ends_with("\r\n\r\n") {
+    ///         reader.read_line(&mut head).unwrap();
+    ///     }
+    ///     let length: usize = head
+    ///         .lines()
+    ///         .find_map(|line| line.strip_prefix("Content-Length: "))
+    ///         .unwrap()
+    ///         .parse()
+    ///         .unwrap();
+    ///     let mut body = vec![0; length];
+    ///     reader.read_exact(&mut body).unwrap();
+    ///     head + &String::from_utf8(body).unwrap()
+    /// }
+    ///
+    /// let mut stream = TcpStream::connect(addr).unwrap();
+    /// let mut reader = BufReader::new(stream.try_clone().unwrap());
+    /// stream.write_all(b"GET / HTTP/1.1\r\nHost: localhost\r\n\r\n").unwrap();
+    /// let first = read_response(&mut reader);
+    /// assert!(first.ends_with("Hi") && !first.contains("Connection: close"));
+    ///
+    /// // The same connection takes the next request
+    /// stream.write_all(b"GET / HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n").unwrap();
+    /// let second = read_response(&mut reader);
+    /// assert!(second.ends_with("Hi") && second.contains("Connection: close"));
+    ///
+    /// // HTTP/1.0 needs no Host header, and the connection closes after the response
+    /// let response = send("GET / HTTP/1.0\r\n\r\n");
+    /// assert!(response.contains("Connection: close") && response.ends_with("Hi"));
+    /// ```
+    pub fn run(&mut self) {
+        self.start();
+        let config = Arc::new(self.config.clone());
+        for connection in self.listener.incoming() {
+            match connection {
+                Ok(stream) => self.spawn_connection(stream, &config),
+                Err(e) => Self::accept_failed(&e),
+            }
+        }
+    }
+
+    /// Runs the server until `stop` returns `true`.
+    ///
+    /// Behaves like [`Server::run`], but polls the listener instead of
+    /// blocking on it, and calls `stop` between accepts, at least every 10
+    /// milliseconds. Once it returns `true`, no new connection is accepted and
+    /// the method returns. Connections accepted before keep being served on
+    /// their own threads, unless [`Server::shutdown_grace`] is set, in which
+    /// case they are drained before the method returns. The server can be run
+    /// again afterwards.
+    ///
+    /// # Arguments
+    ///
+    /// * `stop` - Called repeatedly; returning `true` stops the server
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use rwf::Server;
+    /// use std::{sync::{atomic::{AtomicBool, Ordering}, Arc}, thread};
+    /// # use std::sync::mpsc;
+    ///
+    /// let stop = Arc::new(AtomicBool::new(false));
+    /// let flag = Arc::clone(&stop);
+    ///
+    /// # let (tx, rx) = mpsc::channel();
+    /// let server = thread::spawn(move || {
+    ///     let mut server = Server::build("127.0.0.1:0").unwrap();
+    /// #   server.quiet(true);
+    ///     server.route("/", |_req, _session| "Hi".to_string());
+    /// #   tx.send(server.address().unwrap()).unwrap();
+    ///     server.run_until(|| flag.load(Ordering::SeqCst));
+    /// });
+    /// # let addr = rx.recv().unwrap();
+    /// # let get = |path: &str| rwf::test_support::get(addr, path);
+    ///
+    /// assert!(get("/").ends_with("Hi"));
+    ///
+    /// stop.store(true, Ordering::SeqCst);
+    /// server.join().unwrap();
+    /// ```
+    pub fn run_until(&mut self, mut stop: impl FnMut() -> bool) {
+        self.start();
+        if let Err(e) = self.listener.set_nonblocking(true) {
+            println!("Failed to poll the listener: {e}");
+            return;
+        }
+
+        let config = Arc::new(self.config.clone());
+        while !stop() {
+            match self.listener.accept() {
+                Ok((stream, _)) => {
+                    // Some platforms pass the listener's non-blocking mode on
+                    if let Err(e) = stream.set_nonblocking(false) {
+                        println!("Failed to accept connection: {e}");
+                        continue;
+                    }
+                    self.spawn_connection(stream, &config);
+                }
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => thread::sleep(POLL_INTERVAL),
+                Err(e) => Self::accept_failed(&e),
+            }
+        }
+
+        if let Some(grace) = self.config.shutdown_grace {
+            let forced = self.drain(grace, &config);
+            if forced > 0 {
+                println!("Closed {forced} connections still busy after the shutdown grace period");
+            }
+        }
+
+        if let Err(e) = self.listener.set_nonblocking(false) {
+            println!("Failed to restore the listener: {e}");
+        }
+    }
+
+    /// Gives open connections `grace` to finish, answering connections
+    /// arriving meanwhile with `503 Service Unavailable` rather than leaving
+    /// them waiting in the listen queue.
+    ///
+    /// Expects the listener to be non-blocking. Returns the number of
+    /// connections closed while still busy.
+    fn drain(&self, grace: Duration, config: &Config) -> usize {
+        thread::scope(|scope| {
+            let draining = scope.spawn(|| self.connections.drain(grace));
+            while !draining.is_finished() {
+                match self.listener.accept() {
+                    Ok((stream, _)) => {
+                        if let Err(e) = stream.set_nonblocking(false) {
+                            println!("Failed to accept connection: {e}");
+                            continue;
+                        }
+                        self.refuse_connection(stream, config);
+                    }
+                    Err(e) if e.kind() == io::ErrorKind::WouldBlock => thread::sleep(POLL_INTERVAL),
+                    Err(e) => Self::accept_failed(&e),
+                }
+            }
+            draining.join().unwrap_or_default()
+        })
+    }
+
+    /// Drains open connections when [`Server::run_until`] or
+    /// [`Server::run_for`] stops.
+    ///
+    /// Once the server stops accepting, connections idle between requests
+    /// are closed right away. Connections busy with a request get up to
+    /// `grace` to finish it; their response carries `Connection: close`.
+    /// Connections arriving in the meantime are answered with `503 Service
+    /// Unavailable` and closed.
+    /// Connections still busy after that are closed, and the count is
+    /// logged. Without a grace period, the default, accepted connections
+    /// keep being served after the method returns.
+    ///
+    /// # Arguments
+    ///
+    /// * `grace` - How long busy connections may take to finish
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use rwf::Server;
+    /// use std::{
+    ///     io::{Read, Write},
+    ///     net::TcpStream,
+    ///     sync::{atomic::{AtomicBool, Ordering}, Arc},
+    ///     thread,
+    ///     time::{Duration, Instant},
+    /// };
+    /// # use std::sync::mpsc;
+    ///
+    /// let stop = Arc::new(AtomicBool::new(false));
+    /// let flag = Arc::clone(&stop);
+    ///
+    /// # let (tx, rx) = mpsc::channel();
+    /// let server = thread::spawn(move || {
+    ///     let mut server = Server::build("127.0.0.1:0").unwrap();
+    /// #   server.quiet(true);
+    ///     server.shutdown_grace(Duration::from_secs(10));
+    ///     server.route("/slow", |_req, _session| {
+    ///         thread::sleep(Duration::from_millis(500));
+    ///         "Finished".to_string()
+    ///     });
+    ///     server.route("/", |_req, _session| "Hi".to_string());
+    /// #   tx.send(server.address().unwrap()).unwrap();
+    ///     server.run_until(|| flag.load(Ordering::SeqCst));
+    /// });
+    /// # let addr = rx.recv().unwrap();
+    ///
+    /// // An idle keep-alive connection and one waiting for a slow handler
+    /// let mut idle = TcpStream::connect(addr).unwrap();
+    /// idle.write_all(b"GET / HTTP/1.1\r\n\r\n").unwrap();
+    /// let mut first = [0; 512];
+    /// let _ = idle.read(&mut first).unwrap();
+    /// let mut busy = TcpStream::connect(addr).unwrap();
+    /// busy.write_all(b"GET /slow HTTP/1.1\r\n\r\n").unwrap();
+    /// thread::sleep(Duration::from_millis(100));
+    ///
+    /// let started = Instant::now();
+    /// stop.store(true, Ordering::SeqCst);
+    ///
+    /// // New connections are refused while the slow request finishes
+    /// thread::sleep(Duration::from_millis(100));
+    /// let mut late = TcpStream::connect(addr).unwrap();
+    /// late.write_all(b"GET / HTTP/1.1\r\n\r\n").unwrap();
+    /// let mut refused = String::new();
+    /// late.read_to_string(&mut refused).unwrap();
+    /// assert!(refused.starts_with("HTTP/1.1 503"));
+    ///
+    /// server.join().unwrap();
+    ///
+    /// // The slow request finished within the grace period
+    /// let mut response = String::new();
+    /// busy.read_to_string(&mut response).unwrap();
+    /// assert!(response.contains("Connection: close") && response.ends_with("Finished"));
+    /// assert!(started.elapsed() < Duration::from_secs(10));
+    ///
+    /// // The idle connection was closed without waiting for its timeout
+    /// let mut rest = String::new();
+    /// idle.read_to_string(&mut rest).unwrap();
+    /// assert!(rest.is_empty());
+    /// ```
+    pub fn shutdown_grace(&mut self, grace: Duration) {
+        self.config.shutdown_grace = Some(grace);
+    }
+
+    /// Returns the number of connections currently being served.
+    ///
+    /// A connection counts from the moment it is accepted until its handler
+    /// thread finishes with it, whether it is busy with a request or idle
+    /// between two. Connections refused by [`Server::max_connections`] are
+    /// not counted.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use rwf::Server;
+    ///
+    /// let server = Server::build("127.0.0.1:0").unwrap();
+    /// assert_eq!(server.connection_count(), 0);
+    /// ```
+    pub fn connection_count(&self) -> usize {
+        self.connections.len()
+    }
+
+    /// Limits the number of connections served at once.
+    ///
+    /// Connections accepted while the limit is reached are answered with
+    /// `503 Service Unavailable`, a `Retry-After` header and
+    /// `Connection: close`, without reading their request, so a burst of
+    /// clients cannot exhaust the server's threads and file descriptors.
+    /// At most `count` refused connections are answered at once, each for
+    /// no longer than a couple of seconds; any beyond that are closed
+    /// without a response. Persistent connections count for as long as they
+    /// stay open, see [`Server::keepalive_timeout`]. Unlimited by default; a
+    /// limit of `0` refuses every connection.
+    ///
+    /// # Arguments
+    ///
+    /// * `count` - The number of connections served at once
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use rwf::Server;
+    /// use std::{io::{Read, Write}, net::TcpStream, thread, time::Duration};
+    ///
+    /// let mut server = Server::build("127.0.0.1:0").unwrap();
+    /// server.max_connections(1);
+    /// server.route("/", |_req, _session| "hello".to_string());
+    /// # let addr = rwf::test_support::serve(server);
+    /// # let get = || rwf::test_support::send(addr, "GET / HTTP/1.1\r\nConnection: close\r\n\r\n");
+    ///
+    /// // Holds the only slot with a connection that has not sent a request yet
+    /// let mut first = TcpStream::connect(addr).unwrap();
+    /// thread::sleep(Duration::from_millis(100));
+    ///
+    /// let refused = get();
+    /// assert!(refused.starts_with("HTTP/1.1 503"));
+    /// assert!(refused.contains("Retry-After: 1"));
+    ///
+    /// // The first connection is still served
+    /// first.write_all(b"GET / HTTP/1.1\r\nConnection: close\r\n\r\n").unwrap();
+    /// let mut response = String::new();
+    /// first.read_to_string(&mut response).unwrap();
+    /// assert!(response.starts_with("HTTP/1.1 200"));
+    /// drop(first);
+    ///
+    /// // Once it is closed, new connections are accepted again
+    /// let mut response = get();
+    /// for _ in 0..50 {
+    ///     if response.starts_with("HTTP/1.1 200") {
+    ///         break;
+    ///     }
+    ///     thread::sleep(Duration::from_millis(20));
+    ///     response = get();
+    /// }
+    /// assert!(response.starts_with("HTTP/1.1 200"));
+    /// ```
+    pub fn max_connections(&mut self, count: usize) {
+        self.config.max_connections = Some(count);
+    }
+
+    /// Runs the server for the given duration.
+    ///
+    /// A shorthand for [`Server::run_until`] with a deadline.
+    ///
+    /// # Arguments
+    ///
+    /// * `duration` - How long to accept connections for
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use rwf::Server;
+    /// use std::time::{Duration, Instant};
+    ///
+    /// let mut server = Server::build("127.0.0.1:0").unwrap();
+    /// server.quiet(true);
+    ///
+    /// let started = Instant::now();
+    /// server.run_for(Duration::from_millis(50));
+    /// assert!(started.elapsed() >= Duration::from_millis(50));
+    /// ```
+    pub fn run_for(&mut self, duration: Duration) {
+        let deadline = Instant::now() + duration;
+        self.run_until(|| Instant::now() >= deadline);
+    }
+
+    /// Runs the `on_start` callback and prints the startup messages.
+    fn start(&self) {
+        if let Some(on_start) = self.on_start {
+            on_start(self);
+        }
+
+        if !self.quiet {
+            if let Ok(addr) = self.address() {
+                println!("Server built on port: {addr}");
+            }
+            println!("Listening to connections.");
+        }
+    }
+
+    /// Logs a failed `accept` and backs off if the error may repeat.
+    fn accept_failed(error: &io::Error) {
+        println!("Failed to accept connection: {error}");
+        if let Some(delay) = accept_backoff(error) {
+            thread::sleep(delay);
+        }
+    }
+
+    /// Serves an accepted connection on its own thread.
+    fn spawn_connection(&self, mut stream: TcpStream, config: &Arc<Config>) {
+        if config
+            .max_connections
+            .is_some_and(|max| self.connections.len() >= max)
+        {
+            self.refuse_connection(stream, config);
+            return;
+        }
+
+        let mut handler = RequestHandler::new(
+            Arc::clone(&self.routes),
+            Arc::clone(&self.sessions),
+            Arc::clone(config),
+        );
+        let remote_addr = stream.peer_addr().ok();
+        match stream.try_clone() {
+            Ok(socket) => handler.set_tracked(self.connections.register(socket)),
+            Err(e) => println!("Failed to track connection: {e}"),
+        }
+        if let Err(e) = stream.set_read_timeout(Some(config.read_timeout)) {
+            println!("Failed to set read timeout: {e}");
+        }
+        match stream.try_clone() {
+            Ok(socket) => handler.set_socket(socket),
+            Err(e) => println!("Failed to clone the connection's socket: {e}"),
+        }
+
+        #[cfg(feature = "tls")]
+        let tls = self.tls.clone();
+        self.spawn_worker(config, move || {
+            #[cfg(feature = "tls")]
+            if let Some(config) = tls {
+                match tls::accept(&config, stream) {
+                    Ok(mut stream) => {
+                        handler.set_tls(true);
+                        handler.resolve(&mut stream, remote_addr)
+                    }
+                    Err(e) => println!("TLS handshake failed: {e}"),
+                }
+                return;
+            }
+
+            handler.resolve(&mut stream, remote_addr)
+        });
+    }
+
+    /// Starts a thread serving a connection, named after
+    /// `Server::thread_name` and numbered in the order threads are started.
+    fn spawn_worker(&self, config: &Config, work: impl FnOnce() + Send + 'static) {
+        let number = self.workers.fetch_add(1, Ordering::Relaxed) + 1;
+        let spawned = thread::Builder::new()
+            .name(format!("{}-{number}", config.thread_name))
+            .spawn(work);
+        if let Err(e) = spawned {
+            println!("Failed to start a connection thread: {e}");
+        }
+    }
+
+    /// Answers a connection over `Server::max_connections` with `503 Service
+    /// Unavailable` on its own thread, so a slow client cannot hold up the
+    /// accept loop.
+    ///
+    /// At most as many connections as the limit allows are answered at once;
+    /// beyond that, refused connections are closed without a response.
+    fn refuse_connection(&self, mut stream: TcpStream, config: &Config) {
+        let max = config.max_connections.unwrap_or(usize::MAX).max(1);
+        if self.refusing.fetch_add(1, Ordering::AcqRel) >= max {
+            self.refusing.fetch_sub(1, Ordering::AcqRel);
+            println!("Too many refused connections pending, closing connection");
+            return;
+        }
+        let refusal = Refusal(Arc::clone(&self.refusing));
+
+        if let Err(e) = stream.set_read_timeout(Some(config.read_timeout)) {
+            println!("Failed to set read timeout: {e}");
+        }
+        if let Err(e) = stream.set_write_timeout(Some(config.read_timeout)) {
+            println!("Failed to set write timeout: {e}");
+        }
+        let response = Response::new(503, "Service unavailable")
+            .with_header("Retry-After", "1")
+            .with_header("Connection", "close");
+
+        #[cfg(feature = "tls")]
+        let tls = self.tls.clone();
+        self.spawn_worker(config, move || {
+            let _refusal = refusal;
+            #[cfg(feature = "tls")]
+            if let Some(config) = tls {
+                if let Ok(mut stream) = tls::accept(&config, stream) {
+                    if stream.write_all(&response.to_bytes()).is_ok() {
+                        if let Ok(socket) = stream.sock.try_clone() {
+                            connection::drain(&mut stream, &socket);
+                        }
+                    }
+                }
+                return;
+            }
+
+            if stream.write_all(&response.to_bytes()).is_ok() {
+                connection::drain(&mut &stream, &stream);
+            }
+        });
+    }
+
+    /// Sets whether request paths match routes ignoring ASCII case.
+    ///
+    /// Disabled by default, so `/Hello` does not reach a route registered as
+    /// `/hello`.
+    ///
+    /// # Arguments
+    ///
+    /// * `enabled` - Whether paths are matched case-insensitively
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use rwf::Server;
+    ///
+    /// let mut server = Server::build("127.0.0.1:0").unwrap();
+    /// server.case_insensitive_routes(true);
+    /// server.route("/hello", |_req, _session| "Hello!".to_string());
+    /// # let addr = rwf::test_support::serve(server);
+    /// # let get = |path: &str| rwf::test_support::get(addr, path);
+    ///
+    /// assert!(get("/HELLO").starts_with("HTTP/1.1 200"));
+    /// assert!(get("/Hello").starts_with("HTTP/1.1 200"));
+    /// // The trailing slash is still significant
+    /// assert!(get("/hello/").starts_with("HTTP/1.1 404"));
+    /// ```
+    pub fn case_insensitive_routes(&mut self, enabled: bool) {
+        self.routes_mut().set_case_insensitive(enabled);
+    }
+
+    /// Sets whether a trailing `/` is ignored when matching request paths to routes.
+    ///
+    /// When enabled, `/path` and `/path/` reach the same route, whichever of
+    /// the two it was registered as. The root path `/` is left untouched.
+    /// Disabled by default.
+    ///
+    /// # Arguments
+    ///
+    /// * `enabled` - Whether a single trailing slash is ignored
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use rwf::Server;
+    ///
+    /// let mut server = Server::build("127.0.0.1:0").unwrap();
+    /// server.ignore_trailing_slash(true);
+    /// server.route("/hello", |_req, _session| "Hello!".to_string());
+    /// server.route("/docs/", |_req, _session| "Docs".to_string());
+    /// # let addr = rwf::test_support::serve(server);
+    /// # let get = |path: &str| rwf::test_support::get(addr, path);
+    ///
+    /// assert!(get("/hello/").starts_with("HTTP/1.1 200"));
+    /// assert!(get("/hello").starts_with("HTTP/1.1 200"));
+    /// assert!(get("/docs").starts_with("HTTP/1.1 200"));
+    /// // Case is still significant
+    /// assert!(get("/Hello").starts_with("HTTP/1.1 404"));
+    /// ```
+    pub fn ignore_trailing_slash(&mut self, enabled: bool) {
+        self.routes_mut().set_ignore_trailing_slash(enabled);
+    }
+
+    /// Sets whether registering a route twice panics.
+    ///
+    /// A route is a duplicate if one with the same method, or the same lack
+    /// of one, is already registered under a path matching the same requests.
+    /// Duplicates are never added: by default a warning naming the route is
+    /// logged and the first handler stays, while in strict mode registering
+    /// panics, so a copy-pasted route cannot silently shadow another.
+    /// Disabled by default.
+    ///
+    /// # Arguments
+    ///
+    /// * `enabled` - Whether a duplicate (path, method) pair panics
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use rwf::Server;
+    ///
+    /// let mut server = Server::build("127.0.0.1:0").unwrap();
+    /// server.route("/users", |_req, _session| "First".to_string());
+    /// // Logs "Ignoring duplicate route /users"
+    /// server.route("/users", |_req, _session| "Second".to_string());
+    /// # let addr = rwf::test_support::serve(server);
+    /// # let send = |request: &str| rwf::test_support::send(addr, request);
+    ///
+    /// let response = send("GET /users HTTP/1.1\r\nConnection: close\r\n\r\n");
+    /// assert!(response.ends_with("First"));
+    /// ```
+    ///
+    /// In strict mode the second registration panics:
+    ///
+    /// ```rust,should_panic
+    /// use rwf::Server;
+    ///
+    /// let mut server = Server::build("127.0.0.1:0").unwrap();
+    /// server.strict_routes(true);
+    /// server.route("/users", |_req, _session| "First".to_string());
+    /// // Panics with "Route /users is already registered"
+    /// server.route("/users", |_req, _session| "Second".to_string());
+    /// ```
+    pub fn strict_routes(&mut self, enabled: bool) {
+        self.routes_mut().set_strict(enabled);
+    }
+
+    /// Sets the maximum size in bytes of a request's header lines, the request
+    /// line excluded: it is limited by [`Server::max_uri_length`] alone.
+    ///
+    /// Requests exceeding it are answered with `431 Request Header Fields Too Large`
+    /// and their connection is closed. Defaults to 8 KiB.
+    ///
+    /// # Example
+    ///
+    /// ```rust,ignore
+    /// server.max_header_size(16 * 1024);
+    /// ```
+    pub fn max_header_size(&mut self, bytes: usize) {
+        self.config.limits.max_header_size = bytes;
+    }
+
+    /// Sets the maximum length in bytes of a request target, the path and
+    /// query string together.
+    ///
+    /// Requests exceeding it are answered with `414 URI Too Long` and their
+    /// connection is closed. The request line is read only as far as needed
+    /// to tell, so an endless target costs no more memory than the limit.
+    /// Defaults to 8 KiB.
+    ///
+    /// # Arguments
+    ///
+    /// * `bytes` - The maximum target length
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use rwf::Server;
+    ///
+    /// let mut server = Server::build("127.0.0.1:0").unwrap();
+    /// server.max_uri_length(1024);
+    /// server.max_header_size(512);
+    /// server.route("/search", |req, _session| req.query["q"].len().to_string());
+    /// # let addr = rwf::test_support::serve(server);
+    /// # let get = |target: &str| {
+    /// #     rwf::test_support::send(addr, &format!("GET {target} HTTP/1.1\r\nConnection: close\r\n\r\n"))
+    /// # };
+    ///
+    /// assert!(get(&format!("/search?q={}", "a".repeat(1000))).ends_with("1000"));
+    /// // A target of exactly the limit is served, even past the header size limit
+    /// assert!(get(&format!("/search?q={}", "a".repeat(1014))).ends_with("1014"));
+    /// assert!(get(&format!("/search?q={}", "a".repeat(1015))).starts_with("HTTP/1.1 414"));
+    ///
+    ///
+    /// let response = get(&format!("/search?q={}", "a".repeat(64 * 1024)));
+    /// assert!(response.starts_with("HTTP/1.1 414 URI Too Long"));
+    /// assert!(response.contains("Connection: close\r\n"));
+    /// ```
+    pub fn max_uri_length(&mut self, bytes: usize) {
+        self.config.limits.max_uri_length = bytes;
+    }
+
+    /// Sets the maximum number of header lines a request may carry.
+    ///
+    /// Requests exceeding it are answered with `431 Request Header Fields Too Large`
+    /// and their connection is closed. Defaults to 100.
+    ///
+    /// # Example
+    ///
+    /// ```rust,ignore
+    /// server.max_headers(50);
+    /// ```
+    pub fn max_headers(&mut self, count: usize) {
+        self.config.limits.max_headers = count;
+    }
+
+    /// Sets the maximum size in bytes of a request body.
+    ///
+    /// The limit is checked against the `Content-Length` header before the
+    /// body is read. Requests exceeding it are answered with `413 Payload Too
+    /// Large`, or with `417 Expectation Failed` if the client sent `Expect:
+    /// 100-continue` and is waiting for permission to send the body, and
+    /// their connection is closed. Requests within the limit that expect it
+    /// get a `100 Continue` response before the body is read. Defaults to 1 MiB.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use rwf::Server;
+    /// use std::{io::{Read, Write}, net::TcpStream};
+    ///
+    /// let mut server = Server::build("127.0.0.1:0").unwrap();
+    /// server.max_body_size(16);
+    /// server.route("/upload", |req, _session| req.body_str().unwrap_or_default().to_string());
+    /// # let addr = rwf::test_support::serve(server);
+    /// # let send = |request: &str| rwf::test_support::send(addr, request);
+    ///
+    /// // The server acknowledges the headers before the body is sent
+    /// let mut stream = TcpStream::connect(addr).unwrap();
+    /// stream
+    ///     .write_all(b"POST /upload HTTP/1.1\r\nContent-Length: 5\r\nConnection: close\r\nExpect: 100-continue\r\n\r\n")
+    ///     .unwrap();
+    /// let mut interim = [0u8; 25];
+    /// stream.read_exact(&mut interim).unwrap();
+    /// assert_eq!(&interim, b"HTTP/1.1 100 Continue\r\n\r\n");
+    /// stream.write_all(b"hello").unwrap();
+    /// let mut response = String::new();
+    /// stream.read_to_string(&mut response).unwrap();
+    /// assert!(response.starts_with("HTTP/1.1 200") && response.ends_with("hello"));
+    ///
+    /// // Too large a body is refused before it is sent
+    /// let response = send("POST /upload HTTP/1.1\r\nContent-Length: 100\r\nExpect: 100-continue\r\n\r\n");
+    /// assert!(response.starts_with("HTTP/1.1 417"));
+    /// ```
+    pub fn max_body_size(&mut self, bytes: usize) {
+        self.config.limits.max_body_size = bytes;
+    }
+
+    /// Sets the size in bytes above which request bodies are not kept in memory.
+    ///
+    /// Larger bodies, still bounded by [`Server::max_body_size`], are copied
+    /// to a temporary file as they arrive and handlers read them through
+    /// [`Request::body_reader`](connection::request::Request::body_reader)
+    /// instead of `body`. The file is removed once the reader or the request
+    /// is dropped. By default every body is kept in memory. Bodies spooled
+    /// this way are not decompressed, and are still held in memory by
+    /// `Server::capture_raw` if it is enabled.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use rwf::Server;
+    /// use std::{io::{self, Read, Write}, net::TcpStream};
+    ///
+    /// let mut server = Server::build("127.0.0.1:0").unwrap();
+    /// server.max_body_size(8 * 1024 * 1024);
+    /// server.max_buffered_body(64 * 1024);
+    /// server.route("/upload", |mut req, _session| {
+    ///     let mut reader = req.body_reader().unwrap();
+    ///     let copied = io::copy(&mut reader, &mut io::sink()).unwrap();
+    ///     format!("{copied} bytes")
+    /// });
+    /// # let addr = rwf::test_support::serve(server);
+    ///
+    /// let size = 4 * 1024 * 1024;
+    /// let mut stream = TcpStream::connect(addr).unwrap();
+    /// write!(stream, "POST /upload HTTP/1.1\r\nContent-Length: {size}\r\nConnection: close\r\n\r\n").unwrap();
+    /// io::copy(&mut io::repeat(b'x').take(size as u64), &mut stream).unwrap();
+    /// let mut response = String::new();
+    /// stream.read_to_string(&mut response).unwrap();
+    /// assert!(response.ends_with(&format!("{size} bytes")));
+    /// ```
+    pub fn max_buffered_body(&mut self, bytes: usize) {
+        self.config.limits.max_buffered_body = Some(bytes);
+    }
+
+    /// Sets how long a persistent connection may stay idle between two requests.
+    ///
+    /// A client that sends nothing for this long after a response has its
+    /// connection closed, freeing the thread serving it. Once a request has
+    /// started arriving, the regular read timeout applies again. Defaults to
+    /// the read timeout, 5 seconds unless set with `ServerBuilder::timeout`.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use rwf::Server;
+    /// use std::time::{Duration, Instant};
+    ///
+    /// let mut server = Server::builder()
+    ///     .timeout(Duration::from_secs(60))
+    ///     .build("127.0.0.1:0")
+    ///     .unwrap();
+    /// server.keepalive_timeout(Duration::from_millis(100));
+    /// server.route("/", |_req, _session| "hello".to_string());
+    /// # let addr = rwf::test_support::serve(server);
+    /// # let send = |request: &str| rwf::test_support::send(addr, request);
+    ///
+    /// // The connection is kept alive, then closed once it stays idle
+    /// let start = Instant::now();
+    /// let response = send("GET / HTTP/1.1\r\n\r\n");
+    /// assert!(response.starts_with("HTTP/1.1 200 OK") && response.ends_with("hello"));
+    /// assert!(start.elapsed() < Duration::from_secs(30));
+    /// ```
+    pub fn keepalive_timeout(&mut self, timeout: Duration) {
+        self.config.keepalive_timeout = Some(timeout);
+    }
+
+    /// Limits the number of requests served on a single connection.
+    ///
+    /// The response to the last allowed request carries `Connection: close`
+    /// and the connection is closed afterwards, so clients reconnect and
+    /// long-lived connections are spread over time. Requests the client
+    /// pipelined past the limit are not answered. Unlimited by default; a
+    /// limit of `0` behaves like `1`.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use rwf::Server;
+    ///
+    /// let mut server = Server::build("127.0.0.1:0").unwrap();
+    /// server.max_requests_per_conn(2);
+    /// server.route("/", |_req, _session| "hello".to_string());
+    /// # let addr = rwf::test_support::serve(server);
+    /// # let send = |request: &str| rwf::test_support::send(addr, request);
+    ///
+    /// let responses = send("GET / HTTP/1.1\r\n\r\nGET / HTTP/1.1\r\n\r\nGET / HTTP/1.1\r\n\r\n");
+    ///
+    /// // Only the second response announces the close
+    /// assert_eq!(responses.matches("HTTP/1.1 200 OK").count(), 2);
+    /// let (first, second) = responses.split_once("helloHTTP/1.1").unwrap();
+    /// assert!(!first.contains("Connection: close"));
+    /// assert!(second.contains("Connection: close"));
+    /// ```
+    pub fn max_requests_per_conn(&mut self, count: usize) {
+        self.config.max_requests_per_conn = Some(count);
+    }
+
+    /// Moves the built-in health check endpoint to the specified path.
+    ///
+    /// The health check is enabled by default on `/healthz`. It answers every
+    /// request with `200 OK` and an empty body, before any route lookup and
+    /// without creating a session, so load balancer probes stay cheap.
+    /// Use [`Server::health_check_body`] to change the body and
+    /// [`Server::disable_health_check`] to turn it off.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The URL path of the health check
+    ///
+    /// # Example
+    ///
+    /// ```rust,ignore
+    /// server.health_check("/status");
+    /// ```
+    pub fn health_check(&mut self, path: &str) {
+        let health = self.config.health_check.take();
+        self.config.health_check = Some(HealthCheck {
+            path: path.to_string(),
+            body: health.map(|health| health.body).unwrap_or_default(),
+        });
+    }
+
+    /// Sets the body returned by the health check endpoint.
+    ///
+    /// Re-enables the endpoint on `/healthz` if it was disabled.
+    ///
+    /// # Example
+    ///
+    /// ```rust,ignore
+    /// server.health_check_body("ok");
+    /// ```
+    pub fn health_check_body(&mut self, body: &str) {
+        let health = self.config.health_check.take();
+        self.config.health_check = Some(HealthCheck {
+            path: health.map_or("/healthz".to_string(), |health| health.path),
+            body: body.to_string(),
+        });
+    }
+
+    /// Disables the built-in health check endpoint.
+    ///
+    /// Requests to its path are then routed like any other request.
+    ///
+    /// # Example
+    ///
+    /// ```rust,ignore
+    /// server.disable_health_check();
+    /// ```
+    pub fn disable_health_check(&mut self) {
+        self.config.health_check = None;
+    }
+
+    /// Collects request metrics and exposes them on the specified path.
+    ///
+    /// Every request is counted by method, route and response status, and
+    /// the time taken to produce its response is recorded in a latency
+    /// histogram. Requests are labelled with the route as registered, such
+    /// as `/users/:id`, or `unmatched` when no route matched, and with the
+    /// method, or `other` for an extension method no route is registered
+    /// for. The endpoint
+    /// answers `GET` requests with the totals in the Prometheus text format,
+    /// before any route lookup and without creating a session, see
+    /// [`connection::metrics`]. Metrics are off by default; calling this
+    /// again moves the endpoint and keeps the counts.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The URL path of the metrics endpoint
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use rwf::Server;
+    ///
+    /// let mut server = Server::build("127.0.0.1:0").unwrap();
+    /// server.metrics("/metrics");
+    /// server.route("/users/:id", |req, _session| req.param("id").unwrap().to_string());
+    /// # let addr = rwf::test_support::serve(server);
+    /// # let get = |path: &str| rwf::test_support::get(addr, path);
+    ///
+    /// get("/users/1");
+    /// get("/users/2");
+    /// get("/missing");
+    ///
+    /// let metrics = get("/metrics");
+    /// assert!(metrics.contains("Content-Type: text/plain; version=0.0.4\r\n"));
+    /// assert!(metrics.contains(r#"rwf_requests_total{method="GET",route="/users/:id",status="200"} 2"#));
+    /// assert!(metrics.contains(r#"rwf_requests_total{method="GET",route="unmatched",status="404"} 1"#));
+    /// assert!(metrics.contains(r#"rwf_request_duration_seconds_count{method="GET",route="/users/:id"} 2"#));
+    ///
+    /// // The counters keep growing, including for the endpoint itself
+    /// get("/users/3");
+    /// let metrics = get("/metrics");
+    /// assert!(metrics.contains(r#"route="/users/:id",status="200"} 3"#));
+    /// assert!(metrics.contains(r#"rwf_requests_total{method="GET",route="/metrics",status="200"} 1"#));
+    /// ```
+    ///
+    /// Responses served from the cache count towards their route:
+    ///
+    /// ```rust
+    /// use rwf::Server;
+    /// use std::time::Duration;
+    ///
+    /// let mut server = Server::build("127.0.0.1:0").unwrap();
+    /// server.metrics("/metrics");
+    /// server.route("/report", |_req, _session| "Report".to_string());
+    /// server.cache("/report", Duration::from_secs(60));
+    /// # let addr = rwf::test_support::serve(server);
+    /// # let get = |path: &str| rwf::test_support::get(addr, path);
+    ///
+    /// for _ in 0..3 {
+    ///     assert!(get("/report").ends_with("Report"));
+    /// }
+    /// let metrics = get("/metrics");
+    /// assert!(metrics.contains(r#"rwf_requests_total{method="GET",route="/report",status="200"} 3"#));
+    /// assert!(!metrics.contains(r#"route="unmatched""#));
+    /// ```
+    ///
+    /// Extension methods are labelled by name only when a route is
+    /// registered for them, so arbitrary method tokens cannot add series:
+    ///
+    /// ```rust
+    /// use rwf::{connection::method::Method, Server};
+    ///
+    /// let mut server = Server::build("127.0.0.1:0").unwrap();
+    /// server.metrics("/metrics");
+    /// server.route("/files", |_req, _session| "Any method".to_string());
+    /// let propfind = Method::Other("PROPFIND".to_string());
+    /// server.route_with(propfind, "/dav", "Lists properties", |_req, _session| "Props".to_string());
+    /// # let addr = rwf::test_support::serve(server);
+    /// # let send = |method: &str, path: &str| {
+    /// #     rwf::test_support::send(addr, &format!("{method} {path} HTTP/1.1\r\nConnection: close\r\n\r\n"))
+    /// # };
+    ///
+    /// send("PROPFIND", "/dav");
+    /// send("RANDOM1", "/files");
+    /// send("RANDOM2", "/missing");
+    ///
+    /// let metrics = send("GET", "/metrics");
+    /// assert!(metrics.contains(r#"rwf_requests_total{method="PROPFIND",route="/dav",status="200"} 1"#));
+    /// assert!(metrics.contains(r#"rwf_requests_total{method="other",route="/files",status="200"} 1"#));
+    /// assert!(metrics.contains(r#"rwf_requests_total{method="other",route="unmatched",status="404"} 1"#));
+    /// assert!(!metrics.contains("RANDOM"));
+    /// ```
+    pub fn metrics(&mut self, path: &str) {
+        let collector = self
+            .config
+            .metrics
+            .take()
+            .map_or_else(|| Arc::new(Metrics::new()), |metrics| metrics.collector);
+        self.config.metrics = Some(MetricsEndpoint {
+            path: path.to_string(),
+            collector,
+        });
+    }
+
+    /// Keeps the exact bytes received for each request in `Request::raw`.
+    ///
+    /// A debugging aid for requests that do not parse or route as expected.
+    /// Disabled by default, as it keeps a second copy of every request in
+    /// memory while it is handled.
+    ///
+    /// # Arguments
+    ///
+    /// * `enabled` - Whether raw requests are captured
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use rwf::Server;
+    ///
+    /// let mut server = Server::build("127.0.0.1:0").unwrap();
+    /// server.capture_raw(true);
+    /// server.route("/echo", |req, _session| {
+    ///     String::from_utf8_lossy(&req.raw.unwrap()).to_string()
+    /// });
+    /// # let addr = rwf::test_support::serve(server);
+    ///
+    /// let raw = "POST /echo HTTP/1.1\r\nContent-Length: 2\r\nConnection: close\r\n\r\nhi";
+    /// let response = rwf::test_support::send(addr, raw);
+    /// assert!(response.ends_with(raw));
+    /// ```
+    pub fn capture_raw(&mut self, enabled: bool) {
+        self.config.capture_raw = enabled;
+    }
+
+    /// Logs a warning for each handler that runs longer than `threshold`.
+    ///
+    /// The warning names the method, the path and how long the handler
+    /// took. Only the handler itself is timed, not reading the request or
+    /// sending the response. Disabled by default.
+    ///
+    /// # Arguments
+    ///
+    /// * `threshold` - The longest a handler may run without a warning
+    ///
+    /// # Example
+    ///
+    /// ```rust,ignore
+    /// server.slow_threshold(Duration::from_millis(200));
+    /// // Logs e.g. "Slow handler: GET /report took 1.2s (threshold: 200ms)"
+    /// ```
+    pub fn slow_threshold(&mut self, threshold: Duration) {
+        self.config.slow_threshold = Some(threshold);
+    }
+
+    /// Replaces the clock handler durations are measured with.
+    ///
+    /// Meant for tests, which can make handlers appear slow without sleeping.
+    ///
+    /// # Arguments
+    ///
+    /// * `clock` - The clock to read the time from
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use rwf::{connection::clock::Clock, Server};
+    /// use std::{
+    ///     sync::{atomic::{AtomicU64, Ordering}, Arc},
+    ///     time::{Duration, Instant},
+    /// };
+    ///
+    /// // A clock moving a second forward every time it is read
+    /// #[derive(Debug)]
+    /// struct SteppingClock {
+    ///     start: Instant,
+    ///     reads: Arc<AtomicU64>,
+    /// }
+    ///
+    /// impl Clock for SteppingClock {
+    ///     fn now(&self) -> Instant {
+    ///         self.start + Duration::from_secs(self.reads.fetch_add(1, Ordering::SeqCst))
+    ///     }
+    /// }
+    ///
+    /// let reads = Arc::new(AtomicU64::new(0));
+    /// let clock = SteppingClock { start: Instant::now(), reads: Arc::clone(&reads) };
+    /// let mut server = Server::build("127.0.0.1:0").unwrap();
+    /// server.clock(clock);
+    /// // Every handler now takes a second, logging a warning
+    /// server.slow_threshold(Duration::from_millis(500));
+    /// server.route("/report", |_req, _session| "Report".to_string());
+    /// # let addr = rwf::test_support::serve(server);
+    /// # let send = |request: &str| rwf::test_support::send(addr, request);
+    ///
+    /// let response = send("GET /report HTTP/1.1\r\nConnection: close\r\n\r\n");
+    /// assert!(response.ends_with("Report"));
+    /// // Read once before and once after the handler
+    /// assert_eq!(reads.load(Ordering::SeqCst), 2);
+    /// ```
+    pub fn clock(&mut self, clock: impl Clock + 'static) {
+        self.config.clock = Arc::new(clock);
+    }
+
+    /// Sets the backend sessions are kept in.
+    ///
+    /// Sessions are kept in process memory by default, through a
+    /// [`MemorySessionStore`](connection::session::MemorySessionStore). Any sessions created before the call are
+    /// discarded.
+    ///
+    /// # Arguments
+    ///
+    /// * `store` - The session storage backend
+    ///
+    /// # Example
+    ///
+    /// ```rust,ignore
+    /// server.session_store(MemorySessionStore::new());
+    /// ```
+    pub fn session_store(&mut self, store: impl SessionStore + 'static) {
+        self.sessions = Arc::new(Sessions::with_store(store));
+    }
+
+    /// Sets the secret key session cookies are signed with.
+    ///
+    /// Session cookies carry an HMAC-SHA256 signature of the session ID, and
+    /// cookies whose signature doesn't match are ignored, giving the client a
+    /// fresh session instead. When no secret is set, a random one is generated
+    /// when the server is built.
+    ///
+    /// # Arguments
+    ///
+    /// * `key` - The secret key; use a long, random value and keep it private
+    ///
+    /// # Example
+    ///
+    /// ```rust,ignore
+    /// server.secret(&std::env::var("SESSION_SECRET").unwrap());
+    /// ```
+    pub fn secret(&mut self, key: &str) {
+        self.config.secret = key.as_bytes().to_vec();
+    }
+
+    /// Sets whether the client address is read from proxy headers.
+    ///
+    /// When enabled, `Request::client_ip` reports the address the proxy
+    /// added last to the `X-Forwarded-For` or `Forwarded` header, falling
+    /// back to the socket peer address when neither is present. Disabled by
+    /// default.
+    ///
+    /// **OBS**: Only enable this when the server is reachable exclusively
+    /// through a proxy that sets these headers. Otherwise any client can spoof
+    /// its address by sending them itself.
+    ///
+    /// # Example
+    ///
+    /// ```rust,ignore
+    /// server.trust_proxy(true);
+    /// ```
+    pub fn trust_proxy(&mut self, trust: bool) {
+        self.config.trust_proxy = trust;
+    }
+
+    /// Limits how many requests each client may send.
+    ///
+    /// Clients are identified by `Request::client_ip`, and each one may send
+    /// bursts of up to `requests` requests, refilled at a rate of `requests`
+    /// per `per_duration`. Requests over the limit are answered with
+    /// `429 Too Many Requests` and a `Retry-After` header, without running
+    /// the handler. The health check endpoint is never limited.
+    ///
+    /// # Arguments
+    ///
+    /// * `requests` - The number of requests allowed per period
+    /// * `per_duration` - The length of the period
+    ///
+    /// # Example
+    ///
+    /// ```rust,ignore
+    /// // At most 100 requests per minute per client
+    /// server.rate_limit(100, Duration::from_secs(60));
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// Panics if `requests` or `per_duration` is zero.
+    pub fn rate_limit(&mut self, requests: u32, per_duration: Duration) {
+        self.config.rate_limiter = Some(Arc::new(RateLimiter::new(requests, per_duration)));
+    }
+
+    /// Requires a CSRF token on requests that may change state.
+    ///
+    /// When enabled, every request with a method other than `GET`, `HEAD` or `OPTIONS`
+    /// must carry the token returned by `Session::csrf_token`, either in a
+    /// `csrf_token` form field or an `X-CSRF-Token` header. Requests without a
+    /// session, without a token or with a wrong one are answered with
+    /// `403 Forbidden`, without running the handler. Disabled by default.
+    ///
+    /// Templates embed the token in a hidden form field:
+    ///
+    /// ```html
+    /// <form method="post" action="/comment">
+    ///   <input type="hidden" name="csrf_token" value="$csrf_token">
+    ///   <textarea name="text"></textarea>
+    /// </form>
+    /// ```
+    ///
+    /// ```rust,ignore
+    /// server.route("/comment", |_req, session| {
+    ///     let csrf_token = &session.csrf_token();
+    ///     template!("templates/comment.html", csrf_token)
+    /// });
+    /// ```
+    ///
+    /// # Arguments
+    ///
+    /// * `enabled` - Whether the token is required
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use rwf::{connection::method::Method, Server};
+    /// # use rwf::test_support::send;
+    ///
+    /// let mut server = Server::build("127.0.0.1:0").unwrap();
+    /// server.csrf(true);
+    /// server.route("/comment", |req, session| match req.method {
+    ///     Method::GET => session.csrf_token(),
+    ///     _ => "Saved".to_string(),
+    /// });
+    /// # let addr = rwf::test_support::serve(server);
+    ///
+    /// let form = send(addr, "GET /comment HTTP/1.1\r\nConnection: close\r\n\r\n");
+    /// let cookie = form.split("Set-Cookie: ").nth(1).unwrap().split(';').next().unwrap();
+    /// let token = form.rsplit("\r\n").next().unwrap();
+    ///
+    /// let forged = format!("POST /comment HTTP/1.1\r\nCookie: {cookie}\r\nConnection: close\r\n\r\n");
+    /// assert!(send(addr, &forged).starts_with("HTTP/1.1 403"));
+    ///
+    /// let body = format!("csrf_token={token}");
+    /// let genuine = format!(
+    ///     "POST /comment HTTP/1.1\r\nCookie: {cookie}\r\nConnection: close\r\n\
+    ///      Content-Type: application/x-www-form-urlencoded\r\nContent-Length: {}\r\n\r\n{body}",
+    ///     body.len()
+    /// );
+    /// assert!(send(addr, &genuine).ends_with("Saved"));
+    /// ```
+    pub fn csrf(&mut self, enabled: bool) {
+        self.config.csrf = enabled;
+    }
+
+    /// Caches the responses of a route for `ttl`.
+    ///
+    /// The first `GET` request to `path` runs its handler as usual and the
+    /// response is stored. Later `GET` requests with the same query string get
+    /// the stored response directly, without running the handler, until it is
+    /// older than `ttl`. Only `200 OK` responses without a `Cache-Control:
+    /// no-store` header are stored, and other methods always reach the
+    /// handler.
+    ///
+    /// Cached responses are shared by every client, so only cache routes
+    /// whose response does not depend on the session or on request headers.
+    /// A route reading the session is cached all the same: the response
+    /// rendered for the first client's session is served to every other
+    /// client until it expires. Routes registered with
+    /// [`Server::protected_route`] are never cached.
+    ///
+    /// At most [`cache::DEFAULT_CAPACITY`](connection::cache::DEFAULT_CAPACITY)
+    /// responses are kept, across all cached routes and query strings, so
+    /// clients sending endless distinct query strings cannot exhaust memory:
+    /// expired responses make room first, then the ones closest to expiring.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The route path to cache
+    /// * `ttl` - How long a stored response is served
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use rwf::Server;
+    /// use std::{
+    ///     sync::{atomic::{AtomicUsize, Ordering}, Arc},
+    ///     time::Duration,
+    /// };
+    ///
+    /// let runs = Arc::new(AtomicUsize::new(0));
+    /// let counter = Arc::clone(&runs);
+    ///
+    /// let mut server = Server::build("127.0.0.1:0").unwrap();
+    /// server.route("/report", move |_req, _session| {
+    ///     format!("Report #{}", counter.fetch_add(1, Ordering::SeqCst) + 1)
+    /// });
+    /// server.cache("/report", Duration::from_secs(60));
+    /// # let addr = rwf::test_support::serve(server);
+    /// # let send = |request: &str| rwf::test_support::send(addr, request);
+    ///
+    /// for _ in 0..3 {
+    ///     let response = send("GET /report HTTP/1.1\r\nConnection: close\r\n\r\n");
+    ///     assert!(response.ends_with("Report #1"));
+    /// }
+    /// assert_eq!(runs.load(Ordering::SeqCst), 1);
+    /// ```
+    ///
+    /// A cached protected route still turns away clients without a session:
+    ///
+    /// ```rust
+    /// use rwf::Server;
+    /// use std::time::Duration;
+    ///
+    /// let mut server = Server::build("127.0.0.1:0").unwrap();
+    /// server.route("/login", |_req, session| {
+    ///     session.add("user".to_string(), "ferris".to_string());
+    ///     "Logged in".to_string()
+    /// });
+    /// server.protected_route("/secret", |_req, _session| "TOP-SECRET".to_string());
+    /// server.cache("/secret", Duration::from_secs(60));
+    /// # let addr = rwf::test_support::serve(server);
+    /// # let get = |path: &str, cookie: &str| {
+    /// #     rwf::test_support::send(addr, &format!("GET {path} HTTP/1.1\r\nCookie: {cookie}\r\nConnection: close\r\n\r\n"))
+    /// # };
+    ///
+    /// let login = get("/login", "");
+    /// let cookie = login.split("Set-Cookie: ").nth(1).unwrap().split(';').next().unwrap();
+    /// assert!(get("/secret", cookie).ends_with("TOP-SECRET"));
+    /// assert!(get("/secret", "").starts_with("HTTP/1.1 401"));
+    /// ```
+    ///
+    /// Virtual hosts with routes of their own are cached separately:
+    ///
+    /// ```rust
+    /// use rwf::Server;
+    /// use std::time::Duration;
+    ///
+    /// let mut server = Server::build("127.0.0.1:0").unwrap();
+    /// server.host("a.example.com").route("/home", |_req, _session| "host-a".to_string());
+    /// server.host("b.example.com").route("/home", |_req, _session| "host-b".to_string());
+    /// server.route("/home", |_req, _session| "default".to_string());
+    /// server.cache("/home", Duration::from_secs(60));
+    /// # let addr = rwf::test_support::serve(server);
+    /// # let get = |host: &str| {
+    /// #     rwf::test_support::send(addr, &format!("GET /home HTTP/1.1\r\nHost: {host}\r\nConnection: close\r\n\r\n"))
+    /// # };
+    ///
+    /// for _ in 0..2 {
+    ///     assert!(get("a.example.com").ends_with("host-a"));
+    ///     assert!(get("B.example.com:8080").ends_with("host-b"));
+    ///     assert!(get("other.example.com").ends_with("default"));
+    /// }
+    /// ```
+    ///
+    /// Query strings are keyed by their parameters, so an escaped `&` or `=`
+    /// does not pass for a separator:
+    ///
+    /// ```rust
+    /// use rwf::Server;
+    /// use std::time::Duration;
+    ///
+    /// let mut server = Server::build("127.0.0.1:0").unwrap();
+    /// server.route("/search", |req, _session| format!("{} parameters", req.query.len()));
+    /// server.cache("/search", Duration::from_secs(60));
+    /// # let addr = rwf::test_support::serve(server);
+    /// # let get = |path: &str| rwf::test_support::get(addr, path);
+    ///
+    /// assert!(get("/search?a=b%26c%3Dd").ends_with("1 parameters"));
+    /// assert!(get("/search?a=b&c=d").ends_with("2 parameters"));
+    /// assert!(get("/search?c=d&a=b").ends_with("2 parameters"));
+    /// ```
+    pub fn cache(&mut self, path: &str, ttl: Duration) {
+        self.config.cached_routes.insert(path.to_string(), ttl);
+    }
+
+    /// Registers a new route handler for the specified path.
+    /// If the route exists, it logs a warning and does nothing, or panics with
+    /// [`Server::strict_routes`] enabled.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The URL path to match
+    /// * `f` - Handler function or closure taking a Request and Session,
+    ///   returning any [`IntoResponse`] type, such as a `String`, a
+    ///   [`Response`] or a `(u16, String)` status and body. Closures may capture state,
+    ///   which must be `Send + Sync`; share mutable state through an `Arc` with
+    ///   atomics or a `Mutex`.
+    ///
+    /// # Example
+    ///
+    /// ```rust,ignore
+    /// server.route("/hello", |req, session| {
+    ///     "Hello, World!".to_string()
+    /// });
+    ///
+    /// server.route("/download", |req, session| {
+    ///     Response::file("reports/latest.pdf").unwrap()
+    /// });
+    ///
+    /// server.route("/teapot", |req, session| (418, "Short and stout"));
+    /// ```
+    ///
+    /// A closure capturing a shared counter:
+    ///
+    /// ```rust
+    /// use rwf::Server;
+    /// use std::sync::{atomic::{AtomicUsize, Ordering}, Arc};
+    ///
+    /// let visits = Arc::new(AtomicUsize::new(0));
+    /// let counter = Arc::clone(&visits);
+    ///
+    /// let mut server = Server::build("127.0.0.1:0").unwrap();
+    /// server.route("/visits", move |_req, _session| {
+    ///     let count = counter.fetch_add(1, Ordering::SeqCst) + 1;
+    ///     format!("Visit number {count}")
+    /// });
+    /// # let addr = rwf::test_support::serve(server);
+    /// # let send = |request: &str| rwf::test_support::send(addr, request);
+    ///
+    /// for expected in ["Visit number 1", "Visit number 2"] {
+    ///     let response = send("GET /visits HTTP/1.1\r\nConnection: close\r\n\r\n");
+    ///     assert!(response.ends_with(expected));
+    /// }
+    /// assert_eq!(visits.load(Ordering::SeqCst), 2);
+    /// ```
+    pub fn route<R, F>(&mut self, path: &str, f: F)
+    where
+        R: IntoResponse + 'static,
+        F: Fn(Request, &mut Session) -> R + Send + Sync + 'static,
+    {
+        self.routes_mut().route(path, f);
+    }
+
+    /// Registers a route handler only reachable with an existing session.
+    ///
+    /// Requests without a correctly signed session cookie naming a stored
+    /// session, e.g. from clients that never logged in or whose session was
+    /// destroyed or expired, are rejected before the handler runs: with
+    /// `401 Unauthorized`, or with a `302 Found` redirect to
+    /// [`Server::login_path`] if one is set. The handler can thus rely on
+    /// the session holding what the login handler stored. If the route
+    /// exists, it is handled as by [`Server::route`].
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The URL path to match
+    /// * `f` - Handler function or closure taking a Request and Session
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use rwf::Server;
+    ///
+    /// let mut server = Server::build("127.0.0.1:0").unwrap();
+    /// server.route("/login", |_req, session| {
+    ///     session.add("user".to_string(), "ferris".to_string());
+    ///     "Logged in".to_string()
+    /// });
+    /// server.protected_route("/account", |_req, session| {
+    ///     format!("Hello, {}", session.get::<String>("user").unwrap())
+    /// });
+    /// # let addr = rwf::test_support::serve(server);
+    /// # let get = |path: &str, cookie: &str| {
+    /// #     rwf::test_support::send(addr, &format!("GET {path} HTTP/1.1\r\n{cookie}Connection: close\r\n\r\n"))
+    /// # };
+    ///
+    /// // Without a session the handler never runs
+    /// assert!(get("/account", "").starts_with("HTTP/1.1 401"));
+    /// assert!(get("/account", "Cookie: session_id=forged\r\n").starts_with("HTTP/1.1 401"));
+    ///
+    /// let login = get("/login", "");
+    /// let cookie = login
+    ///     .lines()
+    ///     .find_map(|line| line.strip_prefix("Set-Cookie: "))
+    ///     .and_then(|cookie| cookie.split(';').next())
+    ///     .unwrap();
+    /// let account = get("/account", &format!("Cookie: {cookie}\r\n"));
+    /// assert!(account.starts_with("HTTP/1.1 200") && account.ends_with("Hello, ferris"));
+    /// ```
+    pub fn protected_route<R, F>(&mut self, path: &str, f: F)
+    where
+        R: IntoResponse + 'static,
+        F: Fn(Request, &mut Session) -> R + Send + Sync + 'static,
+    {
+        self.routes_mut().protected_route(path, f);
+    }
+
+    /// Sets where requests to protected routes without a session are redirected.
+    ///
+    /// By default they get `401 Unauthorized`, which suits APIs; browsers
+    /// are better sent to a login page with a `302 Found` instead.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The location of the login page, e.g. `/login`
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use rwf::Server;
+    ///
+    /// let mut server = Server::build("127.0.0.1:0").unwrap();
+    /// server.login_path("/login");
+    /// server.protected_route("/account", |_req, _session| "Account".to_string());
+    /// # let addr = rwf::test_support::serve(server);
+    /// # let send = |request: &str| rwf::test_support::send(addr, request);
+    ///
+    /// let response = send("GET /account HTTP/1.1\r\nConnection: close\r\n\r\n");
+    /// assert!(response.starts_with("HTTP/1.1 302") && response.contains("Location: /login\r\n"));
+    /// ```
+    pub fn login_path(&mut self, path: &str) {
+        self.config.login_path = Some(path.to_string());
+    }
+
+    /// Registers one handler for several paths.
+    ///
+    /// Behaves like calling [`Server::route`] once per path with the same
+    /// handler, so aliases such as `/` and `/index` need not repeat it.
+    ///
+    /// # Arguments
+    ///
+    /// * `paths` - The URL paths to match
+    /// * `f` - The handler function or closure shared by every path
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use rwf::Server;
+    ///
+    /// let mut server = Server::build("127.0.0.1:0").unwrap();
+    /// server.route_many(&["/", "/index"], |req, _session| format!("Home via {}", req.resource));
+    /// # let addr = rwf::test_support::serve(server);
+    /// # let get = |path: &str| rwf::test_support::get(addr, path);
+    ///
+    /// assert!(get("/").ends_with("Home via /"));
+    /// assert!(get("/index").ends_with("Home via /index"));
+    /// assert!(get("/home").starts_with("HTTP/1.1 404"));
+    /// ```
+    pub fn route_many<R, F>(&mut self, paths: &[&str], f: F)
+    where
+        R: IntoResponse + 'static,
+        F: Fn(Request, &mut Session) -> R + Send + Sync + 'static,
+    {
+        self.routes_mut().route_many(paths, f);
+    }
+
+    /// Registers one handler answering each of several methods on a path.
+    ///
+    /// Behaves like calling [`Server::route_with`] once per method, without
+    /// a description. Other methods fall back to a route registered with
+    /// [`Server::route`] for the same path, if any.
+    ///
+    /// # Arguments
+    ///
+    /// * `methods` - The methods the handler answers
+    /// * `path` - The URL path to match
+    /// * `f` - The handler function or closure shared by every method
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use rwf::{connection::method::Method, Server};
+    ///
+    /// let mut server = Server::build("127.0.0.1:0").unwrap();
+    /// server.route_methods(&[Method::PUT, Method::PATCH], "/users/:id", |_req, _session| {
+    ///     "Saved".to_string()
+    /// });
+    ///
+    /// let methods: Vec<_> = server.describe_routes().into_iter().map(|route| route.method).collect();
+    /// assert_eq!(methods, [Some(Method::PUT), Some(Method::PATCH)]);
+    /// ```
+    pub fn route_methods<R, F>(&mut self, methods: &[Method], path: &str, f: F)
+    where
+        R: IntoResponse + 'static,
+        F: Fn(Request, &mut Session) -> R + Send + Sync + 'static,
+    {
+        self.routes_mut().route_methods(methods, path, f);
+    }
+
+    /// Registers a handler answering only one method, with a description.
+    ///
+    /// Unlike [`Server::route`], which answers every method, the route only
+    /// handles requests with `method`, so each method of a path can have its
+    /// own handler. Requests with other methods fall back to a route
+    /// registered with [`Server::route`] for the same path, if any. The
+    /// description is reported by [`Server::describe_routes`].
+    ///
+    /// # Arguments
+    ///
+    /// * `method` - The method the route answers
+    /// * `path` - The URL path to match
+    /// * `description` - A human-readable description of the route
+    /// * `f` - The handler function or closure
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use rwf::{connection::method::Method, Server};
+    ///
+    /// let mut server = Server::build("127.0.0.1:0").unwrap();
+    /// server.route_with(Method::GET, "/users", "Lists users", |_req, _session| "[]".to_string());
+    /// server.route_with(Method::POST, "/users", "Creates a user", |_req, _session| "Created".to_string());
+    ///
+    /// let routes = server.describe_routes();
+    /// assert_eq!(routes.len(), 2);
+    /// assert_eq!(routes[1].method, Some(Method::POST));
+    /// assert_eq!(routes[1].description.as_deref(), Some("Creates a user"));
+    /// ```
+    ///
+    /// `PUT` and `PATCH` requests to the same path reach their own handlers,
+    /// while other methods get `405 Method Not Allowed` with an `Allow`
+    /// header listing the registered ones:
+    ///
+    /// ```rust
+    /// use rwf::{connection::method::Method, Server};
+    ///
+    /// let mut server = Server::build("127.0.0.1:0").unwrap();
+    /// server.route_with(Method::PUT, "/users/:id", "Replaces a user", |req, _session| {
+    ///     format!("replaced {}", req.param("id").unwrap())
+    /// });
+    /// server.route_with(Method::PATCH, "/users/:id", "Updates a user", |req, _session| {
+    ///     format!("updated {}", req.param("id").unwrap())
+    /// });
+    /// server.route_with(Method::GET, "/x", "Reads x", |_req, _session| "x".to_string());
+    /// # let addr = rwf::test_support::serve(server);
+    /// # let send = |method: &str, path: &str| {
+    /// #     rwf::test_support::send(addr, &format!("{method} {path} HTTP/1.1\r\nContent-Length: 0\r\nConnection: close\r\n\r\n"))
+    /// # };
+    ///
+    /// assert!(send("PUT", "/users/7").ends_with("replaced 7"));
+    /// assert!(send("PATCH", "/users/7").ends_with("updated 7"));
+    /// let response = send("GET", "/users/7");
+    /// assert!(response.starts_with("HTTP/1.1 405") && response.contains("Allow: PUT, PATCH\r\n"));
+    ///
+    /// let response = send("POST", "/x");
+    /// assert!(response.starts_with("HTTP/1.1 405") && response.contains("Allow: GET\r\n"));
+    /// // Unknown paths are still not found
+    /// assert!(send("POST", "/y").starts_with("HTTP/1.1 404"));
+    /// ```
+    ///
+    /// Extension methods such as WebDAV's `PROPFIND` are routed through
+    /// `Method::Other`, reaching the handler with the method unchanged:
+    ///
+    /// ```rust
+    /// use rwf::{connection::method::Method, Server};
+    ///
+    /// let mut server = Server::build("127.0.0.1:0").unwrap();
+    /// let propfind = Method::from_string("PROPFIND");
+    /// server.route_with(propfind, "/dav/:file", "Lists properties", |req, _session| {
+    ///     format!("{} {}", req.method, req.param("file").unwrap())
+    /// });
+    /// # let addr = rwf::test_support::serve(server);
+    /// # let send = |request_line: &str| {
+    /// #     rwf::test_support::send(addr, &format!("{request_line}\r\nConnection: close\r\n\r\n"))
+    /// # };
+    ///
+    /// assert!(send("PROPFIND /dav/notes.txt HTTP/1.1").ends_with("PROPFIND notes.txt"));
+    /// let response = send("GET /dav/notes.txt HTTP/1.1");
+    /// assert!(response.starts_with("HTTP/1.1 405") && response.contains("Allow: PROPFIND\r\n"));
+    /// // Methods that are not valid tokens are rejected
+    /// assert!(send("PROP@FIND /dav/notes.txt HTTP/1.1").starts_with("HTTP/1.1 400"));
+    /// ```
+    ///
+    /// `HEAD` requests reach a route registered for `HEAD`, or else the `GET`
+    /// route, whose handler sees a `GET` request. Only the headers are sent
+    /// back, and metrics record the request as `HEAD`:
+    ///
+    /// ```rust
+    /// use rwf::{connection::{method::Method, response::Response}, Server};
+    ///
+    /// let mut server = Server::build("127.0.0.1:0").unwrap();
+    /// server.metrics("/metrics");
+    /// server.route_with(Method::GET, "/page", "Shows the page", |req, _session| {
+    ///     format!("{} page", req.method)
+    /// });
+    /// server.route_with(Method::GET, "/file", "Downloads the file", |_req, _session| "data".to_string());
+    /// server.route_with(Method::HEAD, "/file", "Describes the file", |_req, _session| {
+    ///     Response::new(200, "data").with_header("X-Checksum", "abc")
+    /// });
+    /// # let addr = rwf::test_support::serve(server);
+    /// # let send = |request_line: &str| {
+    /// #     rwf::test_support::send(addr, &format!("{request_line}\r\nConnection: close\r\n\r\n"))
+    /// # };
+    ///
+    /// let response = send("HEAD /page HTTP/1.1");
+    /// assert!(response.contains("Content-Length: 8\r\n") && response.ends_with("\r\n\r\n"));
+    ///
+    /// let response = send("HEAD /file HTTP/1.1");
+    /// assert!(response.contains("X-Checksum: abc\r\n") && response.ends_with("\r\n\r\n"));
+    /// assert!(!send("GET /file HTTP/1.1").contains("X-Checksum"));
+    ///
+    /// let metrics = send("GET /metrics HTTP/1.1");
+    /// assert!(metrics.contains(r#"rwf_requests_total{method="HEAD",route="/page",status="200"} 1"#));
+    /// assert!(metrics.contains(r#"rwf_requests_total{method="HEAD",route="/file",status="200"} 1"#));
+    /// ```
+    pub fn route_with<R, F>(&mut self, method: Method, path: &str, description: &str, f: F)
+    where
+        R: IntoResponse + 'static,
+        F: Fn(Request, &mut Session) -> R + Send + Sync + 'static,
+    {
+        self.routes_mut().route_with(method, path, description, f);
+    }
+
+    /// Starts registering routes served only for one virtual host.
+    ///
+    /// Requests whose `Host` header names `name` are routed through the
+    /// host's own routes, and get a `404 Not Found` for paths it lacks.
+    /// Requests for any other host, or without a `Host` header, are routed
+    /// through the routes registered on the server itself, which act as the
+    /// default host. The host is matched ignoring ASCII case and the port.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The host name, e.g. `blog.example.com`
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use rwf::Server;
+    ///
+    /// let mut server = Server::build("127.0.0.1:0").unwrap();
+    /// server.route("/", |_req, _session| "Main site".to_string());
+    /// server
+    ///     .host("blog.example.com")
+    ///     .route("/", |_req, _session| "Blog".to_string())
+    ///     .route("/posts/:id", |req, _session| format!("Post {}", req.param("id").unwrap()));
+    /// server.host("shop.example.com").route("/", |req, _session| {
+    ///     format!("Shop on {}", req.host.unwrap())
+    /// });
+    /// # let addr = rwf::test_support::serve(server);
+    /// # let get = |host: &str, path: &str| {
+    /// #     rwf::test_support::send(addr, &format!("GET {path} HTTP/1.1\r\nHost: {host}\r\nConnection: close\r\n\r\n"))
+    /// # };
+    ///
+    /// assert!(get("blog.example.com", "/").ends_with("Blog"));
+    /// assert!(get("Blog.Example.com:8080", "/posts/7").ends_with("Post 7"));
+    /// assert!(get("shop.example.com", "/").ends_with("Shop on shop.example.com"));
+    /// // Unknown hosts get the default routes, known hosts only their own
+    /// assert!(get("example.com", "/").ends_with("Main site"));
+    /// assert!(get("shop.example.com", "/posts/7").starts_with("HTTP/1.1 404"));
+    /// ```
+    pub fn host(&mut self, name: &str) -> HostRoutes<'_> {
+        HostRoutes::new(self.routes_mut(), name)
+    }
+
+    /// Lists every registered route with its metadata, in registration order.
+    ///
+    /// Static file routes are reported with `uses_session` set to `false`.
+    ///
+    /// # Example
+    ///
+    /// ```rust,ignore
+    /// let index: Vec<String> = server
+    ///     .describe_routes()
+    ///     .iter()
+    ///     .map(|route| format!("{:?} {}", route.method, route.path))
+    ///     .collect();
+    /// ```
+    pub fn describe_routes(&self) -> Vec<RouteInfo> {
+        self.routes
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .describe()
+    }
+
+    /// Mounts a separately built routing table under a path prefix.
+    ///
+    /// Every route in `routes` is added with `prefix` prepended to its path,
+    /// so `/users` mounted under `/admin` answers `/admin/users`. A route
+    /// registered as `/` answers the prefix itself. The server's matching
+    /// options, such as [`Server::case_insensitive_routes`], apply to the
+    /// mounted routes as well.
     ///
     /// # Arguments
     ///
-    /// * `addr` - A string slice containing the address in format "host:port"
+    /// * `prefix` - The path prefix, e.g. `/admin`
+    /// * `routes` - The routes to mount
     ///
     /// # Returns
     ///
-    /// * `Result<Server, io::Error>` - A new server instance or an IO error
+    /// * `Result<(), ServerError>` - `Ok` once every route is mounted, or a
+    ///   `ServerError::Config` listing the prefixed paths that are already
+    ///   registered. No route is mounted on error.
     ///
     /// # Example
     ///
     /// ```rust
-    /// let server = Server::build("127.0.0.1:8080")?;
+    /// use rwf::{routing::Routes, Server, ServerError};
+    ///
+    /// fn admin_routes() -> Routes {
+    ///     let mut routes = Routes::new();
+    ///     routes.route("/", |_req, _session| "Dashboard".to_string());
+    ///     routes.route("/users", |_req, _session| "Users".to_string());
+    ///     routes
+    /// }
+    ///
+    /// let mut server = Server::build("127.0.0.1:0").unwrap();
+    /// server.mount("/admin", admin_routes()).unwrap();
+    ///
+    /// // Mounting onto paths that already exist is refused
+    /// let error = server.mount("/admin", admin_routes()).unwrap_err();
+    /// assert!(matches!(error, ServerError::Config(_)));
+    /// assert!(error.to_string().contains("/admin/users"));
+    ///
+    /// # let addr = rwf::test_support::serve(server);
+    /// # let send = |request: &str| rwf::test_support::send(addr, request);
+    /// for (path, expected) in [("/admin", "Dashboard"), ("/admin/users", "Users")] {
+    ///     let response = send(&format!("GET {path} HTTP/1.1\r\nConnection: close\r\n\r\n"));
+    ///     assert!(response.ends_with(expected));
+    /// }
     /// ```
-    pub fn build(addr: &str) -> Result<Self, io::Error> {
-        TcpListener::bind(addr).map(|listener| {
-            println!("Server built on port: {addr}");
-            Self {
-                listener,
-                routes: Rc::new(RefCell::new(Routes::new())),
-                sessions: Rc::new(RefCell::new(Sessions::new())),
-            }
-        })
+    pub fn mount(&mut self, prefix: &str, routes: Routes) -> Result<(), ServerError> {
+        self.routes_mut()
+            .mount(prefix, routes)
+            .map_err(|conflicts| {
+                ServerError::Config(format!(
+                    "cannot mount routes under '{prefix}', already registered: {}",
+                    conflicts.join(", ")
+                ))
+            })
     }
 
-    /// Starts the server and begins listening for incoming connections.
+    /// Sets the file served for requests to a directory path.
     ///
-    /// This method runs indefinitely, processing incoming connections and
-    /// routing requests to appropriate handlers.
+    /// A request for a path ending in `/`, such as `/docs/`, that has no route
+    /// of its own is answered by the route of the index file in that
+    /// directory, e.g. `/docs/index.html` as registered by
+    /// [`Server::static_dir`]. If there is no such route either, the request
+    /// gets the usual `404 Not Found`. Defaults to `index.html`.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The index file name
     ///
     /// # Example
     ///
     /// ```rust
-    /// let mut server = Server::build("127.0.0.1:8080")?;
-    /// server.run();
+    /// use rwf::Server;
+    ///
+    /// let mut server = Server::build("127.0.0.1:0").unwrap();
+    /// server.index_file("home.html");
+    /// server.route("/docs/home.html", |_req, _session| "Docs home".to_string());
+    /// # let addr = rwf::test_support::serve(server);
+    /// # let get = |path: &str| rwf::test_support::get(addr, path);
+    ///
+    /// assert!(get("/docs/").ends_with("Docs home"));
+    /// // Directories without an index file are not found
+    /// assert!(get("/blog/").starts_with("HTTP/1.1 404"));
     /// ```
-    pub fn run(&mut self) {
-        println!("Listening to connections.");
-        for conn in self.listener.incoming() {
-            if let Ok(request) = conn {
-                let mut handler =
-                    RequestHandler::new(Rc::clone(&self.routes), Rc::clone(&self.sessions));
-                handler.resolve(request)
-            }
-        }
+    pub fn index_file(&mut self, name: &str) {
+        self.config.index_file = name.to_string();
     }
 
-    /// Registers a new route handler for the specified path.
-    /// If the route exists, it does nothing.
+    /// Sets whether [`Server::serve_dir`] lists the contents of directories
+    /// that have no index file.
+    ///
+    /// The listing is an HTML page linking every entry of the directory,
+    /// subdirectories first, with the size of each file. Entries resolving
+    /// outside of the served directory, e.g. through a symbolic link, are
+    /// left out. Only affects directories served by `serve_dir` calls made
+    /// after this one. Disabled by default, so such directories are not found.
     ///
     /// # Arguments
     ///
-    /// * `path` - The URL path to match
-    /// * `f` - Handler function taking a Request and Session, returning a String response
+    /// * `enabled` - Whether directories without an index file are listed
     ///
     /// # Example
     ///
     /// ```rust
-    /// server.route("/hello", |req, session| {
-    ///     "Hello, World!".to_string()
+    /// use rwf::Server;
+    /// use std::fs;
+    ///
+    /// let root = std::env::temp_dir().join(format!("rwf-listing-{}", std::process::id()));
+    /// fs::create_dir_all(root.join("photos")).unwrap();
+    /// fs::write(root.join("notes.txt"), "12345").unwrap();
+    /// fs::write(root.join("a&b.txt"), "").unwrap();
+    /// fs::write(root.join("a b.txt"), "spaced").unwrap();
+    /// fs::write(root.join("café.txt"), "accented").unwrap();
+    ///
+    /// let dir = root.to_str().unwrap().to_string();
+    /// let mut server = Server::build("127.0.0.1:0").unwrap();
+    /// server.listing(true);
+    /// server.serve_dir("/files", &dir).unwrap();
+    /// # let addr = rwf::test_support::serve(server);
+    /// # let get = |path: &str| rwf::test_support::get(addr, path);
+    ///
+    /// let listing = get("/files/");
+    /// assert!(listing.contains("Content-Type: text/html"));
+    /// assert!(listing.contains(r#"<a href="/files/photos/">photos/</a>"#));
+    /// assert!(listing.contains(r#"<a href="/files/notes.txt">notes.txt</a> 5 bytes"#));
+    /// // Names are escaped in both the link and its text
+    /// assert!(listing.contains(r#"<a href="/files/a%26b.txt">a&amp;b.txt</a>"#));
+    /// assert!(get("/files/photos").contains(r#"<a href="/files/">../</a>"#));
+    ///
+    /// // Every link can be followed, whatever characters the name holds
+    /// let href = |text: &str| {
+    ///     let end = listing.find(&format!("\">{text}</a>")).unwrap();
+    ///     listing[..end].rsplit("href=\"").next().unwrap().to_string()
+    /// };
+    /// assert_eq!(href("a b.txt"), "/files/a%20b.txt");
+    /// assert!(get(&href("a b.txt")).ends_with("spaced"));
+    /// assert!(get(&href("café.txt")).ends_with("accented"));
+    /// assert!(get(&href("a&amp;b.txt")).starts_with("HTTP/1.1 200"));
+    /// // Escapes cannot smuggle in a separator
+    /// assert!(get("/files/photos%2F..%2Fnotes.txt").starts_with("HTTP/1.1 404"));
+    /// ```
+    pub fn listing(&mut self, enabled: bool) {
+        self.config.listing = enabled;
+    }
+
+    /// Serves a single-page app's entry point for paths no route matches.
+    ///
+    /// `GET` requests that would get a `404 Not Found` are answered with the
+    /// contents of `index_path` as `text/html` instead, so the app's
+    /// client-side router can handle any of its URLs. Paths under one of
+    /// `exclude_prefixes`, such as an API, keep answering `404`. Prefixes
+    /// match whole segments: `/api` covers `/api` and `/api/users` but not
+    /// `/apiary`. The file is read for every request it answers.
+    ///
+    /// # Arguments
+    ///
+    /// * `index_path` - Path to the app's HTML entry point
+    /// * `exclude_prefixes` - Path prefixes that are not part of the app
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use rwf::Server;
+    /// use std::fs;
+    ///
+    /// let index = std::env::temp_dir().join(format!("rwf-spa-{}.html", std::process::id()));
+    /// fs::write(&index, "<div id=\"app\"></div>").unwrap();
+    ///
+    /// let index_path = index.to_str().unwrap().to_string();
+    /// let mut server = Server::build("127.0.0.1:0").unwrap();
+    /// server.spa_fallback(&index_path, &["/api"]);
+    /// server.route("/api/users", |_req, _session| "[]".to_string());
+    /// # let addr = rwf::test_support::serve(server);
+    /// # let get = |path: &str| rwf::test_support::get(addr, path);
+    ///
+    /// // Routes still answer, and unknown API paths are not found
+    /// assert!(get("/api/users").ends_with("[]"));
+    /// assert!(get("/api/posts").starts_with("HTTP/1.1 404"));
+    ///
+    /// // Any other path falls through to the app
+    /// let page = get("/settings/profile");
+    /// assert!(page.starts_with("HTTP/1.1 200"));
+    /// assert!(page.contains("Content-Type: text/html"));
+    /// assert!(page.ends_with("<div id=\"app\"></div>"));
+    /// assert!(get("/apiary").starts_with("HTTP/1.1 200"));
+    /// ```
+    pub fn spa_fallback(&mut self, index_path: &str, exclude_prefixes: &[&str]) {
+        self.config.spa_fallback = Some(SpaFallback {
+            index_path: index_path.to_string(),
+            exclude_prefixes: exclude_prefixes
+                .iter()
+                .map(|prefix| prefix.trim_end_matches('/').to_string())
+                .collect(),
+        });
+    }
+
+    /// Sets the `Content-Type` of responses that do not specify one.
+    ///
+    /// Applies to every response with a body and no `Content-Type` header,
+    /// such as the ones built from a handler's `String`. Responses that set
+    /// their own type, like static files, keep it. Defaults to
+    /// `text/html; charset=utf-8`.
+    ///
+    /// # Arguments
+    ///
+    /// * `content_type` - The media type to send, e.g. `text/plain; charset=utf-8`
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use rwf::{connection::response::Response, Server};
+    ///
+    /// let mut server = Server::build("127.0.0.1:0").unwrap();
+    /// server.default_content_type("text/plain; charset=utf-8");
+    /// server.route("/text", |_req, _session| "Hi".to_string());
+    /// server.route("/json", |_req, _session| {
+    ///     Response::new(200, "{}").with_header("Content-Type", "application/json")
+    /// });
+    /// # let addr = rwf::test_support::serve(server);
+    /// # let get = |path: &str| rwf::test_support::get(addr, path);
+    ///
+    /// assert!(get("/text").contains("Content-Type: text/plain; charset=utf-8\r\n"));
+    /// let json = get("/json");
+    /// assert!(json.contains("Content-Type: application/json\r\n"));
+    /// assert!(!json.contains("text/plain"));
+    /// ```
+    pub fn default_content_type(&mut self, content_type: &str) {
+        self.config.default_content_type = content_type.to_string();
+    }
+
+    /// Sets whether the server's own error responses are JSON for clients
+    /// preferring it.
+    ///
+    /// When enabled, requests whose `Accept` header lists a JSON type first
+    /// get the `404 Not Found` for unknown paths, the `405 Method Not
+    /// Allowed` for unregistered methods and the `500 Internal Server Error`
+    /// for panicking handlers as [`Response::json_error`] bodies,
+    /// e.g. `{"error":"Not found"}`. Other clients, such as browsers, keep
+    /// getting plain text. Errors returned by handlers are left as they are.
+    /// Disabled by default.
+    ///
+    /// # Arguments
+    ///
+    /// * `enabled` - Whether errors are rendered as JSON for JSON clients
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use rwf::Server;
+    ///
+    /// let mut server = Server::build("127.0.0.1:0").unwrap();
+    /// server.json_errors(true);
+    /// # let addr = rwf::test_support::serve(server);
+    /// # let get = |accept: &str| {
+    /// #     rwf::test_support::send(addr, &format!("GET /missing HTTP/1.1\r\nAccept: {accept}\r\nConnection: close\r\n\r\n"))
+    /// # };
+    ///
+    /// let json = get("application/json");
+    /// assert!(json.starts_with("HTTP/1.1 404"));
+    /// assert!(json.contains("Content-Type: application/json\r\n"));
+    /// assert!(json.ends_with(r#"{"error":"Not found"}"#));
+    ///
+    /// let html = get("text/html,*/*;q=0.8");
+    /// assert!(html.starts_with("HTTP/1.1 404") && html.ends_with("Resource not found"));
+    /// ```
+    pub fn json_errors(&mut self, enabled: bool) {
+        self.config.json_errors = enabled;
+    }
+
+    /// Sets whether `TRACE` requests reach routes instead of being refused.
+    ///
+    /// `TRACE` asks the server to echo the request back, which lets a
+    /// script read headers it is otherwise denied, such as `HttpOnly`
+    /// cookies (cross-site tracing). It is therefore refused with `405
+    /// Method Not Allowed` by default. When enabled, `TRACE` requests are
+    /// routed like any other method, to routes registered for
+    /// `Method::TRACE` or answering every method. `CONNECT`, only meaningful
+    /// to proxies, is always answered with `501 Not Implemented`.
+    ///
+    /// # Arguments
+    ///
+    /// * `enabled` - Whether `TRACE` requests are routed
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use rwf::{connection::method::Method, Server};
+    ///
+    /// let start = |allow_trace: bool| {
+    ///     let mut server = Server::build("127.0.0.1:0").unwrap();
+    ///     server.allow_trace(allow_trace);
+    ///     server.route_with(Method::TRACE, "/", "Echoes the method", |req, _session| {
+    ///         req.method.to_string()
+    ///     });
+    ///     server.route("/any", |_req, _session| "Any".to_string());
+    /// #   rwf::test_support::serve(server)
+    /// };
+    /// # let send = |addr, request: &str| {
+    /// #     rwf::test_support::send(addr, &format!("{request}\r\nConnection: close\r\n\r\n"))
+    /// # };
+    ///
+    /// let addr = start(false);
+    /// let trace = send(addr, "TRACE / HTTP/1.1");
+    /// assert!(trace.starts_with("HTTP/1.1 405") && trace.contains("Allow: GET, HEAD"));
+    /// assert!(send(addr, "TRACE /any HTTP/1.1").starts_with("HTTP/1.1 405"));
+    /// let connect = send(addr, "CONNECT example.com:443 HTTP/1.1");
+    /// assert!(connect.starts_with("HTTP/1.1 501 Not Implemented"));
+    /// assert!(!send(addr, "OPTIONS * HTTP/1.1").contains("TRACE"));
+    ///
+    /// let addr = start(true);
+    /// assert!(send(addr, "TRACE / HTTP/1.1").ends_with("TRACE"));
+    /// assert!(send(addr, "OPTIONS * HTTP/1.1").contains("Allow: GET, HEAD, POST, PUT, PATCH, DELETE, OPTIONS, TRACE\r\n"));
+    /// assert!(send(addr, "CONNECT example.com:443 HTTP/1.1").starts_with("HTTP/1.1 501"));
+    /// ```
+    pub fn allow_trace(&mut self, enabled: bool) {
+        self.config.allow_trace = enabled;
+    }
+
+    /// Sets the name of the threads serving connections.
+    ///
+    /// Every connection is served on its own thread, named after this
+    /// prefix and numbered in the order connections are accepted, e.g.
+    /// `rwf-worker-3`, so debuggers, profilers and panic messages tell them
+    /// apart. A handler that panics fails its own request only: the panic is
+    /// logged with the request's method and path, the client gets `500
+    /// Internal Server Error` and the connection and server keep running.
+    /// Defaults to `rwf-worker`.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The thread name prefix
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use rwf::Server;
+    /// use std::thread;
+    ///
+    /// let mut server = Server::build("127.0.0.1:0").unwrap();
+    /// server.thread_name("api");
+    /// server.route("/thread", |_req, _session| {
+    ///     thread::current().name().unwrap_or_default().to_string()
+    /// });
+    /// // Logs "Handler panicked on GET '/crash': database unreachable"
+    /// server.route("/crash", |_req, _session| -> String { panic!("database unreachable") });
+    /// # let addr = rwf::test_support::serve(server);
+    /// # let get = |path: &str| rwf::test_support::get(addr, path);
+    ///
+    /// let name = get("/thread");
+    /// let name = name.rsplit("\r\n\r\n").next().unwrap();
+    /// assert!(name.strip_prefix("api-").unwrap().parse::<usize>().is_ok());
+    ///
+    /// assert!(get("/crash").starts_with("HTTP/1.1 500"));
+    /// // The server keeps serving
+    /// assert!(get("/thread").starts_with("HTTP/1.1 200"));
+    /// ```
+    pub fn thread_name(&mut self, name: &str) {
+        self.config.thread_name = name.to_string();
+    }
+
+    /// Serves a single file at the given route.
+    ///
+    /// Unlike [`Server::static_dir`], the file is mapped explicitly, so its
+    /// name does not have to match the route and no directory is searched.
+    /// The file is read on every request and sent with the `Content-Type`
+    /// matching its extension. If it cannot be read, the request is answered
+    /// with `404 Not Found`.
+    ///
+    /// Like every static route, the file is served without looking up or
+    /// creating a session, and the response carries no session cookie even
+    /// for clients that have one, so CDNs and browsers can cache it.
+    ///
+    /// # Arguments
+    ///
+    /// * `route` - The URL path the file is served at
+    /// * `file_path` - Path to the file on disk
+    ///
+    /// # Example
+    ///
+    /// ```rust,ignore
+    /// server.static_file("/favicon.ico", "assets/favicon.ico");
+    /// server.static_file("/", "public/index.html");
+    /// ```
+    ///
+    /// A logged-in client gets its stylesheet without a `Set-Cookie`:
+    ///
+    /// ```rust
+    /// use rwf::Server;
+    ///
+    /// let css = std::env::temp_dir().join(format!("rwf-static-{}.css", std::process::id()));
+    /// std::fs::write(&css, "body { color: red; }").unwrap();
+    ///
+    /// let mut server = Server::build("127.0.0.1:0").unwrap();
+    /// server.static_file("/style.css", css.to_str().unwrap());
+    /// server.route("/login", |_req, session| {
+    ///     session.add("user".to_string(), "ferris".to_string());
+    ///     "Logged in".to_string()
     /// });
+    /// # let addr = rwf::test_support::serve(server);
+    /// # let get = |path: &str, cookie: &str| {
+    /// #     rwf::test_support::send(addr, &format!("GET {path} HTTP/1.1\r\nCookie: {cookie}\r\nConnection: close\r\n\r\n"))
+    /// # };
+    ///
+    /// let login = get("/login", "");
+    /// let cookie = login
+    ///     .lines()
+    ///     .find_map(|line| line.strip_prefix("Set-Cookie: "))
+    ///     .and_then(|value| value.split(';').next())
+    ///     .unwrap();
+    ///
+    /// let style = get("/style.css", cookie);
+    /// assert!(style.starts_with("HTTP/1.1 200 OK") && style.ends_with("body { color: red; }"));
+    /// assert!(!style.contains("Set-Cookie"));
+    /// // Routes using the session still refresh its cookie
+    /// assert!(get("/login", cookie).contains("Set-Cookie: session_id="));
     /// ```
-    pub fn route(&mut self, path: &str, f: fn(Request, &mut Session) -> String) {
-        let mut routes = RefCell::borrow_mut(&mut self.routes);
-        routes.add(path, f);
+    pub fn static_file(&mut self, route: &str, file_path: &str) {
+        let file_path = file_path.to_string();
+        let handler: Handler = Box::new(move |request, _| {
+            Response::file_for(&file_path, &request).unwrap_or_else(|_| response::error404())
+        });
+        self.routes_mut()
+            .add_route(Route::new(route.to_string(), handler).without_session());
     }
 
     /// Configures static file serving from the specified directory.
     ///
     /// Creates routes for all files in the directory, making them accessible
-    /// via HTTP requests.
+    /// via HTTP requests. Static routes never store data in the session, so
+    /// serving them never creates a session or sets a session cookie.
     ///
+    /// The files are listed once, when this method is called. Use
+    /// [`Server::serve_dir`] to resolve files per request instead.
     ///
     /// # Arguments
     ///
     /// * `path` - Path to the directory containing static files
+    ///
     /// **OBS**: The path must be from the root level of your project.
     ///
+    /// # Returns
+    ///
+    /// * `Result<(), ServerError>` - `Ok` once every file is routed, or a
+    ///   `ServerError::Config` if the directory cannot be read or holds a file
+    ///   whose name is not valid UTF-8. No routes are added on error.
+    ///
     /// # Example
     ///
     /// ```rust
-    /// server.static_dir("/static");
+    /// use rwf::{Server, ServerError};
+    ///
+    /// let mut server = Server::build("127.0.0.1:0").unwrap();
+    ///
+    /// let error = server.static_dir("does/not/exist").unwrap_err();
+    /// assert!(matches!(error, ServerError::Config(_)));
+    /// assert!(error.to_string().contains("does/not/exist"));
+    /// ```
+    ///
+    /// A file removed after its route was added is answered with `404 Not
+    /// Found`:
+    ///
+    /// ```rust
+    /// use rwf::Server;
+    /// use std::fs;
+    ///
+    /// let dir = std::env::temp_dir().join(format!("rwf-static-dir-{}", std::process::id()));
+    /// fs::create_dir_all(&dir).unwrap();
+    /// fs::write(dir.join("removed.txt"), "gone soon").unwrap();
+    /// let path = dir.to_str().unwrap().to_string();
+    /// let prefix = dir.file_name().unwrap().to_str().unwrap().to_string();
+    ///
+    /// let mut server = Server::build("127.0.0.1:0").unwrap();
+    /// server.static_dir(&path).unwrap();
+    /// # let addr = rwf::test_support::serve(server);
+    /// # let send = |request: &str| rwf::test_support::send(addr, request);
+    /// fs::remove_dir_all(&dir).unwrap();
+    ///
+    /// let response = send(&format!("GET /{prefix}/removed.txt HTTP/1.1\r\nConnection: close\r\n\r\n"));
+    /// assert!(response.starts_with("HTTP/1.1 404"));
+    /// ```
+    pub fn static_dir(&mut self, path: &str) -> Result<(), ServerError> {
+        let unreadable = |e: io::Error| {
+            ServerError::Config(format!("cannot read static directory '{path}': {e}"))
+        };
+        let prefix = path
+            .trim_end_matches('/')
+            .rsplit('/')
+            .next()
+            .unwrap_or_default();
+
+        let mut routes = Vec::new();
+        for entry in read_dir(path).map_err(unreadable)? {
+            let name = entry.map_err(unreadable)?.file_name();
+            let name = name.to_str().ok_or_else(|| {
+                ServerError::Config(format!(
+                    "static directory '{path}' contains a file name that is not valid UTF-8: {name:?}"
+                ))
+            })?;
+            routes.push(format!("/{prefix}/{name}"));
+        }
+
+        let mut table = self.routes_mut();
+        for route in routes {
+            table.add_route(Route::new(route, Box::new(static_fn)).without_session());
+        }
+        Ok(())
+    }
+
+    /// Configures static file serving like [`Server::static_dir`], panicking
+    /// on errors.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - Path to the directory containing static files
+    ///
+    /// # Example
+    ///
+    /// ```rust,ignore
+    /// server.static_dir_or_panic("templates/static");
     /// ```
     ///
     /// # Panics
     ///
-    /// Panics if the directory cannot be read or if file operations fail.
-    pub fn static_dir(&mut self, path: &str) {
-        let dir = read_dir(path).unwrap();
-        for entry in dir.map(|result| result.unwrap()) {
-            let path = format!(
-                "/{}/{}",
-                path.split("/").last().unwrap(),
-                entry.file_name().to_str().unwrap()
-            );
+    /// Panics with the `ServerError` message if the directory cannot be served.
+    pub fn static_dir_or_panic(&mut self, path: &str) {
+        if let Err(error) = self.static_dir(path) {
+            panic!("{error}");
+        }
+    }
+
+    /// Serves a whole directory tree from a single catch-all route.
+    ///
+    /// Requests for paths under `url_prefix` are resolved relative to
+    /// `fs_root` when they arrive, so files added later are served too and
+    /// files with the same name in different directories do not collide.
+    /// Files are served with their `Content-Type` and `Range` support, as by
+    /// [`Response::file_for`]. A request for a directory gets its index file,
+    /// as set by [`Server::index_file`] before calling this method, or a
+    /// listing of its contents if [`Server::listing`] is enabled. Missing
+    /// files get a `404 Not Found`, and paths resolving outside of `fs_root`,
+    /// e.g. through a symbolic link, get a `403 Forbidden`. The route answers
+    /// `GET` and `HEAD` requests and never touches the session.
+    ///
+    /// # Arguments
+    ///
+    /// * `url_prefix` - The path prefix files are served under, e.g. `/assets`
+    /// * `fs_root` - The directory the files are read from
+    ///
+    /// # Returns
+    ///
+    /// * `Result<(), ServerError>` - `Ok` once the route is registered, or a
+    ///   `ServerError::Config` if `fs_root` is not a readable directory
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use rwf::Server;
+    /// use std::fs;
+    ///
+    /// let base = std::env::temp_dir().join(format!("rwf-serve-dir-{}", std::process::id()));
+    /// let root = base.join("public");
+    /// fs::create_dir_all(root.join("css")).unwrap();
+    /// fs::write(root.join("index.html"), "Home").unwrap();
+    /// fs::write(root.join("css/site.css"), "body {}").unwrap();
+    /// fs::write(base.join("secret.txt"), "Secret").unwrap();
+    /// #[cfg(unix)]
+    /// let _ = std::os::unix::fs::symlink(base.join("secret.txt"), root.join("leak.txt"));
+    ///
+    /// let dir = root.to_str().unwrap().to_string();
+    /// let mut server = Server::build("127.0.0.1:0").unwrap();
+    /// server.serve_dir("/assets", &dir).unwrap();
+    /// assert!(server.serve_dir("/other", "does/not/exist").is_err());
+    /// # let addr = rwf::test_support::serve(server);
+    /// # let get = |path: &str| rwf::test_support::get(addr, path);
+    ///
+    /// let css = get("/assets/css/site.css");
+    /// assert!(css.contains("Content-Type: text/css") && css.ends_with("body {}"));
+    /// assert!(get("/assets/").ends_with("Home"));
+    /// assert!(get("/assets/missing.js").starts_with("HTTP/1.1 404"));
+    /// // Paths are normalized before routing, so `..` cannot leave the prefix
+    /// assert!(get("/assets/../secret.txt").starts_with("HTTP/1.1 404"));
+    /// #[cfg(unix)]
+    /// assert!(get("/assets/leak.txt").starts_with("HTTP/1.1 403"));
+    /// ```
+    pub fn serve_dir(&mut self, url_prefix: &str, fs_root: &str) -> Result<(), ServerError> {
+        let root = fs::canonicalize(fs_root)
+            .map_err(|e| ServerError::Config(format!("cannot serve directory '{fs_root}': {e}")))?;
+        if !root.is_dir() {
+            return Err(ServerError::Config(format!(
+                "cannot serve directory '{fs_root}': not a directory"
+            )));
+        }
+
+        let index_file = self.config.index_file.clone();
+        let listing = self.config.listing;
+        let handler: Handler =
+            Box::new(move |request, _| serve_from_dir(&root, &index_file, listing, &request));
+        let pattern = format!("{}/*path", url_prefix.trim_end_matches('/'));
+        self.routes_mut().add_route(
+            Route::new(pattern, handler)
+                .method(Method::GET)
+                .without_session(),
+        );
+        Ok(())
+    }
+
+    /// Registers the directory templates are rendered from at runtime.
+    ///
+    /// Handlers then call [`Request::render`] with the name of a template
+    /// inside the directory, so the template can be picked while the request
+    /// is handled and is not compiled into the binary. Values are
+    /// HTML-escaped, or inserted as given with [`Request::render_raw`]. Each
+    /// server has its own directory; registering another one replaces it.
+    ///
+    /// # Arguments
+    ///
+    /// * `dir` - The directory holding the templates
+    ///
+    /// # Returns
+    ///
+    /// * `Result<(), ServerError>` - `Ok` once the directory is registered, or
+    ///   a `ServerError::Config` if it is not a readable directory
+    ///
+    /// # Example
+    ///
+    /// ```rust,ignore
+    /// server.templates("templates")?;
+    /// server.route("/page/:name", |req, _session| -> Result<String, HttpError> {
+    ///     let name = format!("{}.html", req.param("name").unwrap());
+    ///     Ok(req.render(&name, &[("title", "Home")])?)
+    /// });
+    /// ```
+    ///
+    /// Servers in the same process keep their own directory:
+    ///
+    /// ```rust
+    /// use rwf::{connection::response::HttpError, Server};
+    /// use std::fs;
+    ///
+    /// let base = std::env::temp_dir().join(format!("rwf-two-template-dirs-{}", std::process::id()));
+    /// let mut addrs = Vec::new();
+    /// for site in ["blog", "shop"] {
+    ///     fs::create_dir_all(base.join(site)).unwrap();
+    ///     fs::write(base.join(site).join("home.html"), format!("{site} home")).unwrap();
+    ///
+    ///     let mut server = Server::build("127.0.0.1:0").unwrap();
+    ///     server.templates(base.join(site).to_str().unwrap()).unwrap();
+    ///     server.route("/", |req, _session| -> Result<String, HttpError> {
+    ///         Ok(req.render("home.html", &[])?)
+    ///     });
+    /// #   addrs.push(rwf::test_support::serve(server));
+    /// }
+    ///
+    /// # let get = |i: usize| rwf::test_support::get(addrs[i], "/");
+    /// assert!(get(0).ends_with("blog home"));
+    /// assert!(get(1).ends_with("shop home"));
+    /// ```
+    pub fn templates(&mut self, dir: &str) -> Result<(), ServerError> {
+        let templates = Templates::new(dir).map_err(|e| {
+            ServerError::Config(format!("cannot use template directory '{dir}': {e}"))
+        })?;
+        self.config.templates = Some(Arc::new(templates));
+        Ok(())
+    }
+
+    /// Gives access to the routing table while the server is being configured.
+    fn routes_mut(&mut self) -> RwLockWriteGuard<'_, Routes> {
+        self.routes.write().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// Counts a refused connection as pending until dropped, including when its
+/// thread fails to start.
+struct Refusal(Arc<AtomicUsize>);
+
+impl Drop for Refusal {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::AcqRel);
+    }
+}
+
+/// How often `Server::run_until` checks for new connections and its stop condition.
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// Time the accept loop waits after a fatal accept error before retrying.
+const ACCEPT_BACKOFF: Duration = Duration::from_millis(100);
+
+/// Decides how long to wait after a failed `accept` before trying again.
+///
+/// # Returns
+///
+/// * `None` - For errors caused by a single client, which do not affect
+///   accepting the next connection
+/// * `Some(Duration)` - For any other error, such as running out of file
+///   descriptors, which would repeat immediately if retried right away
+fn accept_backoff(error: &io::Error) -> Option<Duration> {
+    match error.kind() {
+        io::ErrorKind::ConnectionAborted
+        | io::ErrorKind::ConnectionReset
+        | io::ErrorKind::Interrupted
+        | io::ErrorKind::WouldBlock
+        | io::ErrorKind::TimedOut => None,
+        _ => Some(ACCEPT_BACKOFF),
+    }
+}
 
-            self.route(&path, static_fn);
+/// Serves the file a `Server::serve_dir` request points to.
+///
+/// # Arguments
+///
+/// * `root` - The canonicalized directory files are served from
+/// * `index_file` - The file served for directory paths
+/// * `listing` - Whether directories without an index file are listed
+/// * `request` - The request, whose `path` parameter holds the file path
+///   relative to `root`
+fn serve_from_dir(root: &Path, index_file: &str, listing: bool, request: &Request) -> Response {
+    let relative = request.param("path").unwrap_or_default();
+    // Links in listings are percent-encoded, so names are decoded segment
+    // by segment before they reach the file system
+    let Some(segments) = relative
+        .split('/')
+        .filter(|segment| !segment.is_empty())
+        .map(percent_decode)
+        .collect::<Option<Vec<String>>>()
+    else {
+        return response::error404();
+    };
+    let mut path = root.to_path_buf();
+    path.extend(&segments);
+    if path.is_dir() {
+        if listing && !path.join(index_file).is_file() {
+            return list_dir(root, &path, request);
         }
+        path.push(index_file);
+    }
+
+    let Ok(path) = path.canonicalize() else {
+        return response::error404();
+    };
+    if !path.starts_with(root) {
+        println!(
+            "Refused to serve '{}' from outside of the directory",
+            path.display()
+        );
+        return Response::new(403, "Forbidden");
     }
+    path.to_str()
+        .and_then(|path| Response::file_for(path, request).ok())
+        .unwrap_or_else(response::error404)
 }
 
-// Handles serving of static files.
+/// Builds the HTML listing of a directory served by `Server::serve_dir`.
 ///
 /// # Arguments
 ///
-/// * `r` - The incoming request
-/// * `_` - Unused session parameter
+/// * `root` - The canonicalized directory files are served from
+/// * `dir` - The listed directory, inside `root`
+/// * `request` - The request, whose path prefixes the links
 ///
 /// # Returns
 ///
-/// * `String` - The contents of the requested file
+/// * `Response` - The listing, a `403 Forbidden` if `dir` resolves outside of
+///   `root`, or a `404 Not Found` if it cannot be read
+fn list_dir(root: &Path, dir: &Path, request: &Request) -> Response {
+    let Ok(dir) = dir.canonicalize() else {
+        return response::error404();
+    };
+    if !dir.starts_with(root) {
+        return Response::new(403, "Forbidden");
+    }
+    let Ok(entries) = read_dir(&dir) else {
+        return response::error404();
+    };
+
+    // Directories first, each group sorted by name
+    let mut entries: Vec<(bool, String, u64)> = entries
+        .filter_map(|entry| {
+            let entry = entry.ok()?;
+            let path = entry.path().canonicalize().ok()?;
+            if !path.starts_with(root) {
+                return None;
+            }
+            let metadata = path.metadata().ok()?;
+            let name = entry.file_name().into_string().ok()?;
+            Some((!metadata.is_dir(), name, metadata.len()))
+        })
+        .collect();
+    entries.sort();
+
+    let base = request.resource.trim_end_matches('/');
+    let title = html_escape(&format!("{base}/"));
+    let mut items = String::new();
+    if dir != root {
+        let parent = base.rsplit_once('/').map_or("", |(parent, _)| parent);
+        items.push_str(&format!(
+            "<li><a href=\"{}/\">../</a></li>\n",
+            html_escape(parent)
+        ));
+    }
+    for (is_file, name, size) in entries {
+        let href = format!("{}/{}", html_escape(base), percent_encode(&name));
+        let name = html_escape(&name);
+        items.push_str(&if is_file {
+            format!("<li><a href=\"{href}\">{name}</a> {size} bytes</li>\n")
+        } else {
+            format!("<li><a href=\"{href}/\">{name}/</a></li>\n")
+        });
+    }
+
+    let body = format!(
+        "<!DOCTYPE html>\n<html>\n<head><title>Index of {title}</title></head>\n\
+         <body>\n<h1>Index of {title}</h1>\n<ul>\n{items}</ul>\n</body>\n</html>\n"
+    );
+    Response::new(200, body).with_header("Content-Type", "text/html; charset=utf-8")
+}
+
+/// Escapes the characters with a meaning in HTML text and attribute values.
+fn html_escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Percent-encodes every byte of a path segment but the unreserved characters.
+fn percent_encode(segment: &str) -> String {
+    segment
+        .bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                (byte as char).to_string()
+            }
+            byte => format!("%{byte:02X}"),
+        })
+        .collect()
+}
+
+/// Decodes the percent escapes of a path segment.
+///
+/// Returns `None` for invalid UTF-8 and for segments that would change the
+/// path's structure once decoded: containing a `/` or NUL byte, or being `.`
+/// or `..`.
+fn percent_decode(segment: &str) -> Option<String> {
+    let bytes = segment.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let escaped = (bytes[i] == b'%')
+            .then(|| segment.get(i + 1..i + 3))
+            .flatten()
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match escaped {
+            Some(byte) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            None => {
+                decoded.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+    let decoded = String::from_utf8(decoded).ok()?;
+    if decoded.contains(['/', '\0']) || matches!(decoded.as_str(), "." | "..") {
+        return None;
+    }
+    Some(decoded)
+}
+
+/// Handles serving of static files.
+///
+/// # Arguments
+///
+/// * `r` - The incoming request
+/// * `_` - Unused session parameter
 ///
-/// # Panics
+/// # Returns
 ///
-/// Panics if the file cannot be found or read.
-fn static_fn(r: Request, _: &mut Session) -> String {
-    let fname = r.resource.split("/").last().unwrap();
-    let fpath = find_file(fname, current_dir().unwrap()).unwrap();
-    read_to_string(fpath).unwrap()
+/// * `Response` - The contents of the requested file, with its
+///   `Content-Type`, a `404 Not Found` if the file no longer exists, or a
+///   `500 Internal Server Error` if it cannot be looked up or read
+fn static_fn(r: Request, _: &mut Session) -> Response {
+    let fname = r.path_segments().last().unwrap_or_default();
+    let fpath = match current_dir().and_then(|dir| find_file(fname, dir)) {
+        Ok(Some(fpath)) => fpath,
+        Ok(None) => return response::error404(),
+        Err(e) => {
+            println!("Failed to look up static file '{fname}': {e}");
+            return HttpError::internal_server_error().into();
+        }
+    };
+    match Response::file_for(&fpath, &r) {
+        Ok(response) => response,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => response::error404(),
+        Err(e) => {
+            println!("Failed to read '{fpath}': {e}");
+            HttpError::internal_server_error().into()
+        }
+    }
 }
 
 /// Recursively searches for a file in the directory structure.
@@ -192,27 +2999,26 @@ fn static_fn(r: Request, _: &mut Session) -> String {
 ///
 /// # Returns
 ///
-/// * `Option<String>` - The path to the found file, if it exists
-///
-/// # Panics
-///
-/// Panics if directory operations fail.
-fn find_file(file: &str, dir_path: PathBuf) -> Option<String> {
-    let dir = read_dir(dir_path).unwrap();
-    for entry in dir.map(|rst| rst.unwrap()) {
-        let name = entry.file_name();
+/// * `io::Result<Option<String>>` - The path to the found file, if it
+///   exists, or the error of a directory that cannot be read. Entries whose
+///   path is not valid UTF-8 are skipped.
+fn find_file(file: &str, dir_path: PathBuf) -> io::Result<Option<String>> {
+    for entry in read_dir(dir_path)? {
+        let entry = entry?;
         let path = entry.path();
 
-        if name.to_str() == Some(file) {
-            return Some(path.to_str().unwrap().to_string());
+        if entry.file_name().to_str() == Some(file) {
+            if let Some(path) = path.to_str() {
+                return Ok(Some(path.to_string()));
+            }
         }
 
         if path.is_dir() {
-            if let Some(found) = find_file(file, path) {
-                return Some(found);
+            if let Some(found) = find_file(file, path)? {
+                return Ok(Some(found));
             }
         }
     }
 
-    None
+    Ok(None)
 }
@@ -3,6 +3,8 @@
 /// # Examples
 ///
 /// ```
+/// use rwf::contains_any;
+///
 /// let my_string = "Hello, world!";
 ///
 /// // Check if the string contains any of the specified substrings
@@ -29,10 +31,7 @@
 macro_rules! contains_any {
     ($target:expr, $($pattern:literal),+) => {{
         let target_str: &str = $target;
-        let mut matches = Vec::new();
-        $(
-            matches.push(target_str.contains($pattern));
-        )+
+        let matches = [$(target_str.contains($pattern)),+];
         matches.contains(&true)
     }};
 }
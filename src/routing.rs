@@ -6,17 +6,34 @@
 //! This module implements a simple routing system that maps URL paths to handler
 //! functions. It prevents duplicate routes and provides easy access to route handlers.
 //!
+//! Paths match exactly by default. Matching can be relaxed to ignore case
+//! and to treat `/path` and `/path/` as the same route.
+//!
+//! A path segment starting with `:`, as in `/users/:id`, is a parameter: it
+//! matches any single non-empty segment, whose value is captured under the
+//! parameter name. Routes without parameters take precedence, so `/users/new`
+//! wins over `/users/:id`. A last segment starting with `*`, as in
+//! `/assets/*path`, is a wildcard capturing the rest of the path, possibly
+//! empty, and ranks below both.
+//!
+//! A table can hold separate tables for virtual hosts, see `Server::host`:
+//! requests are routed by their `Host` header, and requests for other hosts
+//! use the main table.
+//!
+//! Route tables can be built on their own and mounted into a server under a
+//! prefix with `Server::mount`, so route bundles can be shipped as libraries.
+//!
 //! ## Example
 //!
-//! ```rust
-//! use routing::Routes;
+//! ```rust,ignore
+//! use rwf::routing::Routes;
 //!
 //! let mut routes = Routes::new();
 //!
 //! // Add a route
-//! routes.add("/hello", |req, session| {
-//!     "Hello, World!".to_string()
-//! });
+//! routes.add("/hello", Box::new(|req, session| {
+//!     "Hello, World!".into()
+//! }));
 //!
 //! // Get a route
 //! if let Some(route) = routes.get_route("/hello") {
@@ -25,18 +42,55 @@
 //! }
 //! ```
 
-use crate::connection::{request::Request, session::Session};
+use crate::connection::{
+    method::Method,
+    request::Request,
+    response::{IntoResponse, Response},
+    session::Session,
+};
+use std::{
+    borrow::Cow,
+    collections::HashMap,
+    sync::{Arc, RwLockWriteGuard},
+};
+
+/// A boxed route handler, taking the request and its session and producing a response.
+///
+/// Handlers may be closures capturing state, as long as it can be shared
+/// across threads.
+pub type Handler = Box<dyn Fn(Request, &mut Session) -> Response + Send + Sync>;
 
 /// Represents a single route in the routing system.
 ///
 /// A route pairs a URL path with a handler function that processes requests
-/// to that path and generates responses.
-#[derive(Debug)]
+/// to that path and generates responses. It may be limited to one method and
+/// carry a description, which are reported by [`Routes::describe`].
 pub struct Route {
     /// The URL path this route responds to
     path: String,
     /// The handler function for this route
-    handler: fn(Request, &mut Session) -> String,
+    handler: Handler,
+    /// The only method this route answers, or `None` for every method
+    method: Option<Method>,
+    /// A human-readable description of the route
+    description: Option<String>,
+    /// Whether the handler may read or store session data
+    uses_session: bool,
+    /// Whether requests without an existing session are rejected
+    requires_session: bool,
+}
+
+/// A description of a registered route, as returned by [`Routes::describe`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RouteInfo {
+    /// The path the route is registered under
+    pub path: String,
+    /// The only method the route answers, or `None` for every method
+    pub method: Option<Method>,
+    /// The description given at registration, if any
+    pub description: Option<String>,
+    /// Whether the handler may use the session; `false` for static files
+    pub uses_session: bool,
 }
 
 impl Route {
@@ -53,14 +107,87 @@ impl Route {
     ///
     /// # Example
     ///
-    /// ```rust
+    /// ```rust,ignore
     /// let route = Route::new(
     ///     "/hello".to_string(),
-    ///     |req, session| "Hello, World!".to_string()
+    ///     Box::new(|req, session| "Hello, World!".into())
     /// );
     /// ```
-    pub fn new(path: String, handler: fn(Request, &mut Session) -> String) -> Self {
-        Self { path, handler }
+    pub fn new(path: String, handler: Handler) -> Self {
+        Self {
+            path,
+            handler,
+            method: None,
+            description: None,
+            uses_session: true,
+            requires_session: false,
+        }
+    }
+
+    /// Limits the route to requests with the given method.
+    pub fn method(mut self, method: Method) -> Self {
+        self.method = Some(method);
+        self
+    }
+
+    /// Attaches a human-readable description to the route.
+    pub fn description(mut self, description: &str) -> Self {
+        self.description = Some(description.to_string());
+        self
+    }
+
+    /// Marks the route as never touching the session, as static files do.
+    ///
+    /// Its requests are served before any session is looked up or created:
+    /// the handler gets an empty session that is thrown away, and the
+    /// response carries no session cookie, so shared caches can store it.
+    pub fn without_session(mut self) -> Self {
+        self.uses_session = false;
+        self.requires_session = false;
+        self
+    }
+
+    /// Marks the route as only reachable with an existing session.
+    ///
+    /// Requests without a correctly signed session cookie naming a stored
+    /// session never reach the handler: they get `401 Unauthorized`, or a
+    /// redirect to the login page set with `Server::login_path`.
+    pub fn require_session(mut self) -> Self {
+        self.uses_session = true;
+        self.requires_session = true;
+        self
+    }
+
+    /// Returns the route's path and metadata.
+    pub fn info(&self) -> RouteInfo {
+        RouteInfo {
+            path: self.path.clone(),
+            method: self.method.clone(),
+            description: self.description.clone(),
+            uses_session: self.uses_session,
+        }
+    }
+
+    /// Returns whether the handler may use the session, see [`Route::without_session`].
+    pub fn uses_session(&self) -> bool {
+        self.uses_session
+    }
+
+    /// Returns whether the route rejects requests without a session, see
+    /// [`Route::require_session`].
+    pub fn requires_session(&self) -> bool {
+        self.requires_session
+    }
+
+    /// Returns the path the route was registered under, such as `/users/:id`.
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    /// Returns the method the route is limited to, or `None` if it answers
+    /// every method, see [`Route::method`].
+    pub fn registered_method(&self) -> Option<&Method> {
+        self.method.as_ref()
     }
 
     /// Returns the handler function for this route.
@@ -71,14 +198,14 @@ impl Route {
     ///
     /// # Example
     ///
-    /// ```rust
+    /// ```rust,ignore
     /// if let Some(route) = routes.get_route("/hello") {
     ///     let handler = route.get_fn();
     ///     let response = handler(request, session);
     /// }
     /// ```
-    pub fn get_fn(&mut self) -> fn(Request, &mut Session) -> String {
-        self.handler
+    pub fn get_fn(&self) -> &Handler {
+        &self.handler
     }
 }
 
@@ -86,10 +213,138 @@ impl Route {
 ///
 /// `Routes` maintains a vector of `Route` instances and provides methods
 /// for adding new routes and finding existing ones.
-#[derive(Debug)]
 pub struct Routes {
     /// Vector storing all registered routes
     routes: Vec<Route>,
+    /// How request paths are compared with route paths
+    matching: Matching,
+    /// The routing tables of virtual hosts, keyed by lowercase host name
+    hosts: HashMap<String, Routes>,
+    /// Whether registering a duplicate route panics instead of logging a warning
+    strict: bool,
+}
+
+/// Options relaxing how paths are compared.
+#[derive(Debug, Clone, Copy, Default)]
+struct Matching {
+    /// Whether paths are compared ignoring ASCII case
+    case_insensitive: bool,
+    /// Whether a trailing `/` is ignored when comparing paths
+    ignore_trailing_slash: bool,
+}
+
+/// Registers routes scoped to one virtual host, as returned by `Server::host`.
+///
+/// Holds the server's routing table until it is dropped.
+pub struct HostRoutes<'a> {
+    /// The server's routing table
+    routes: RwLockWriteGuard<'a, Routes>,
+    /// The host the routes are registered for
+    host: String,
+}
+
+impl<'a> HostRoutes<'a> {
+    /// Scopes route registration on `routes` to `host`.
+    pub(crate) fn new(routes: RwLockWriteGuard<'a, Routes>, host: &str) -> Self {
+        Self {
+            routes,
+            host: host.to_string(),
+        }
+    }
+
+    /// Registers a handler for the host answering every method, as
+    /// `Server::route` does for the default host.
+    pub fn route<R, F>(&mut self, path: &str, f: F) -> &mut Self
+    where
+        R: IntoResponse + 'static,
+        F: Fn(Request, &mut Session) -> R + Send + Sync + 'static,
+    {
+        self.routes.host_mut(&self.host).route(path, f);
+        self
+    }
+
+    /// Registers a handler for the host answering only one method, as
+    /// `Server::route_with` does for the default host.
+    pub fn route_with<R, F>(
+        &mut self,
+        method: Method,
+        path: &str,
+        description: &str,
+        f: F,
+    ) -> &mut Self
+    where
+        R: IntoResponse + 'static,
+        F: Fn(Request, &mut Session) -> R + Send + Sync + 'static,
+    {
+        self.routes
+            .host_mut(&self.host)
+            .route_with(method, path, description, f);
+        self
+    }
+}
+
+/// A route matching a request path, with the path parameters it captured.
+pub struct RouteMatch<'a> {
+    /// The matching route
+    pub route: &'a Route,
+    /// The values of the route's `:name` segments, keyed by name
+    pub params: HashMap<String, String>,
+}
+
+impl Matching {
+    /// Matches a request path against a route path, which may hold `:name`
+    /// parameters.
+    ///
+    /// Returns the captured parameters, or `None` if the paths do not match.
+    fn matches(&self, pattern: &str, path: &str) -> Option<HashMap<String, String>> {
+        let (pattern, path) = (self.strip(pattern), self.strip(path));
+        let (mut patterns, mut segments) = (pattern.split('/'), path.split('/'));
+        let mut params = HashMap::new();
+
+        loop {
+            match (patterns.next(), segments.next()) {
+                (None, None) => return Some(params),
+                (Some(expected), segment) if expected.len() > 1 && expected.starts_with('*') => {
+                    let rest: Vec<&str> = segment.into_iter().chain(segments).collect();
+                    params.insert(expected[1..].to_string(), rest.join("/"));
+                    return Some(params);
+                }
+                (Some(expected), Some(segment)) => match expected.strip_prefix(':') {
+                    Some(name) if !name.is_empty() && !segment.is_empty() => {
+                        params.insert(name.to_string(), segment.to_string());
+                    }
+                    _ if self.case_insensitive && expected.eq_ignore_ascii_case(segment) => {}
+                    _ if expected == segment => {}
+                    _ => return None,
+                },
+                _ => return None,
+            }
+        }
+    }
+
+    /// Strips the trailing `/` ignored by the matching options.
+    fn strip<'a>(&self, path: &'a str) -> &'a str {
+        match path.strip_suffix('/') {
+            Some(stripped) if self.ignore_trailing_slash && !stripped.is_empty() => stripped,
+            _ => path,
+        }
+    }
+
+    /// Brings a path into the form it is compared in.
+    fn normalize<'a>(&self, path: &'a str) -> Cow<'a, str> {
+        let path = self.strip(path);
+        if self.case_insensitive {
+            Cow::Owned(path.to_ascii_lowercase())
+        } else {
+            Cow::Borrowed(path)
+        }
+    }
+}
+
+impl Default for Routes {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Routes {
@@ -99,61 +354,530 @@ impl Routes {
     ///
     /// A new `Routes` instance with no registered routes
     pub fn new() -> Self {
-        Self { routes: Vec::new() }
+        Self {
+            routes: Vec::new(),
+            matching: Matching::default(),
+            hosts: HashMap::new(),
+            strict: false,
+        }
+    }
+
+    /// Sets whether paths are matched ignoring ASCII case.
+    ///
+    /// # Arguments
+    ///
+    /// * `enabled` - `true` to match `/Hello` against a route registered as `/hello`
+    pub fn set_case_insensitive(&mut self, enabled: bool) {
+        self.matching.case_insensitive = enabled;
+        for routes in self.hosts.values_mut() {
+            routes.set_case_insensitive(enabled);
+        }
+    }
+
+    /// Sets whether a single trailing `/` is ignored when matching paths.
+    ///
+    /// The root path `/` is never stripped.
+    ///
+    /// # Arguments
+    ///
+    /// * `enabled` - `true` to match `/hello/` against a route registered as `/hello`
+    pub fn set_ignore_trailing_slash(&mut self, enabled: bool) {
+        self.matching.ignore_trailing_slash = enabled;
+        for routes in self.hosts.values_mut() {
+            routes.set_ignore_trailing_slash(enabled);
+        }
+    }
+
+    /// Sets whether registering a duplicate route panics instead of logging
+    /// a warning, see [`Routes::add_route`].
+    ///
+    /// # Arguments
+    ///
+    /// * `enabled` - `true` to panic on a duplicate (path, method) pair
+    pub fn set_strict(&mut self, enabled: bool) {
+        self.strict = enabled;
+        for routes in self.hosts.values_mut() {
+            routes.set_strict(enabled);
+        }
+    }
+
+    /// Returns the routing table of a virtual host, creating it if needed.
+    ///
+    /// Requests whose `Host` header names the host are routed through its
+    /// table only; requests for any other host use this table, which acts as
+    /// the default host. The host table shares this table's matching options.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The host name, matched ignoring ASCII case and any port
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use rwf::routing::Routes;
+    ///
+    /// let mut routes = Routes::new();
+    /// routes.route("/", |_req, _session| "Default".to_string());
+    /// routes.host_mut("Blog.Example.com").route("/", |_req, _session| "Blog".to_string());
+    ///
+    /// assert_eq!(routes.for_host(Some("blog.example.com")).describe().len(), 1);
+    /// assert!(std::ptr::eq(routes.for_host(Some("shop.example.com")), &routes));
+    /// assert!(std::ptr::eq(routes.for_host(None), &routes));
+    /// ```
+    pub fn host_mut(&mut self, name: &str) -> &mut Routes {
+        let (matching, strict) = (self.matching, self.strict);
+        let name = Request::host_name(name).unwrap_or_default();
+        self.hosts.entry(name).or_insert_with(|| Routes {
+            matching,
+            strict,
+            ..Routes::new()
+        })
+    }
+
+    /// Returns the routing table serving the given host: the host's own
+    /// table if one was created with [`Routes::host_mut`], or else this one.
+    ///
+    /// # Arguments
+    ///
+    /// * `host` - The request's host name, as in `Request::host`
+    pub fn for_host(&self, host: Option<&str>) -> &Routes {
+        host.and_then(|host| self.hosts.get(host)).unwrap_or(self)
     }
 
     /// Finds a route matching the specified path.
     ///
+    /// Both paths are normalized according to the matching options before
+    /// being compared.
+    ///
     /// # Arguments
     ///
     /// * `path` - The URL path to look up
     ///
     /// # Returns
     ///
-    /// * `Some(&mut Route)` if a matching route is found
+    /// * `Some(&Route)` if a matching route is found
     /// * `None` if no matching route exists
     ///
     /// # Example
     ///
-    /// ```rust
+    /// ```rust,ignore
     /// if let Some(route) = routes.get_route("/hello") {
     ///     // Use the route...
     /// } else {
     ///     // Handle 404...
     /// }
     /// ```
-    pub fn get_route(&mut self, path: &str) -> Option<&mut Route> {
-        for route in self.routes.iter_mut() {
-            if route.path == path {
-                return Some(route);
+    pub fn get_route(&self, path: &str) -> Option<&Route> {
+        self.find(path, None).map(|found| found.route)
+    }
+
+    /// Finds the route answering a request with the given path and method.
+    ///
+    /// A route registered for the method takes precedence over one answering
+    /// every method.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The URL path to look up
+    /// * `method` - The request method
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use rwf::{connection::method::Method, routing::Routes};
+    ///
+    /// let mut routes = Routes::new();
+    /// routes.route_with(Method::POST, "/users", "Creates a user", |_req, _session| "Created".to_string());
+    ///
+    /// assert!(routes.get_route_for("/users", Method::POST).is_some());
+    /// assert!(routes.get_route_for("/users", Method::GET).is_none());
+    /// ```
+    pub fn get_route_for(&self, path: &str, method: Method) -> Option<&Route> {
+        self.find(path, Some(method)).map(|found| found.route)
+    }
+
+    /// Finds the route answering a request, along with the path parameters
+    /// it captured.
+    ///
+    /// Routes are ranked as in [`Routes::get_route_for`], and routes without
+    /// parameters rank above routes with parameters.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The request path
+    /// * `method` - The request method
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use rwf::{connection::method::Method, routing::Routes};
+    ///
+    /// let mut routes = Routes::new();
+    /// routes.route("/users/:id/posts/:post", |_req, _session| "Post".to_string());
+    /// routes.route("/users/new", |_req, _session| "New user".to_string());
+    ///
+    /// let found = routes.match_route("/users/42/posts/7", Method::GET).unwrap();
+    /// assert_eq!(found.route.path(), "/users/:id/posts/:post");
+    /// assert_eq!(found.params["id"], "42");
+    /// assert_eq!(found.params["post"], "7");
+    ///
+    /// // Routes without parameters win
+    /// let found = routes.match_route("/users/new", Method::GET).unwrap();
+    /// assert_eq!(found.route.path(), "/users/new");
+    /// assert!(routes.match_route("/users//posts/7", Method::GET).is_none());
+    /// ```
+    pub fn match_route(&self, path: &str, method: Method) -> Option<RouteMatch<'_>> {
+        self.find(path, Some(method))
+    }
+
+    /// Lists the methods routes are registered for under a path.
+    ///
+    /// Used to answer `405 Method Not Allowed` when a path is known but no
+    /// route answers the request's method. Routes answering every method
+    /// are left out, as they match any request. The methods are listed in
+    /// the order of [`Method::ALL`], followed by extension methods such as
+    /// `PROPFIND` in registration order.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The request path
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use rwf::{connection::method::Method, routing::Routes};
+    ///
+    /// let mut routes = Routes::new();
+    /// routes.route_with(Method::POST, "/users", "Creates a user", |_req, _session| "Created".to_string());
+    /// routes.route_with(Method::GET, "/users", "Lists users", |_req, _session| "[]".to_string());
+    ///
+    /// assert_eq!(routes.allowed_methods("/users"), [Method::GET, Method::POST]);
+    /// assert!(routes.allowed_methods("/posts").is_empty());
+    /// ```
+    pub fn allowed_methods(&self, path: &str) -> Vec<Method> {
+        let registered: Vec<&Method> = self
+            .routes
+            .iter()
+            .filter(|route| self.matching.matches(&route.path, path).is_some())
+            .filter_map(|route| route.method.as_ref())
+            .collect();
+        let mut allowed: Vec<Method> = Method::ALL
+            .into_iter()
+            .filter(|method| registered.contains(&method))
+            .collect();
+        for method in registered {
+            if matches!(method, Method::Other(_)) && !allowed.contains(method) {
+                allowed.push(method.clone());
             }
         }
-        None
+        allowed
     }
 
-    /// Adds a new route to the routing table.
+    /// Finds the best route for a path, limited to routes answering `method`
+    /// unless it is `None`.
+    fn find(&self, path: &str, method: Option<Method>) -> Option<RouteMatch<'_>> {
+        let mut best: Option<((u8, bool), RouteMatch<'_>)> = None;
+        for route in &self.routes {
+            if method.is_some() && route.method.is_some() && route.method != method {
+                continue;
+            }
+            let Some(params) = self.matching.matches(&route.path, path) else {
+                continue;
+            };
+            // Lower ranks first: exact paths, then parameters, then wildcards,
+            // and routes bound to the method before routes answering any method
+            let kind = if route.path.contains("/*") {
+                2
+            } else {
+                u8::from(!params.is_empty())
+            };
+            let rank = (kind, route.method.is_none());
+            if best.as_ref().is_none_or(|(best_rank, _)| rank < *best_rank) {
+                best = Some((rank, RouteMatch { route, params }));
+            }
+        }
+        best.map(|(_, found)| found)
+    }
+
+    /// Lists every registered route with its metadata, in registration order.
+    ///
+    /// Useful for an admin or debug endpoint listing the API.
+    ///
+    /// # Example
     ///
-    /// If a route with the same path already exists, the function returns
-    /// without making any changes.
+    /// ```rust
+    /// use rwf::{connection::method::Method, routing::{RouteInfo, Routes}};
+    ///
+    /// let mut routes = Routes::new();
+    /// routes.route_with(Method::GET, "/users", "Lists users", |_req, _session| "[]".to_string());
+    /// routes.route("/health", |_req, _session| "ok".to_string());
+    ///
+    /// assert_eq!(
+    ///     routes.describe()[0],
+    ///     RouteInfo {
+    ///         path: "/users".to_string(),
+    ///         method: Some(Method::GET),
+    ///         description: Some("Lists users".to_string()),
+    ///         uses_session: true,
+    ///     }
+    /// );
+    /// assert_eq!(routes.describe()[1].method, None);
+    /// ```
+    pub fn describe(&self) -> Vec<RouteInfo> {
+        self.routes.iter().map(Route::info).collect()
+    }
+
+    /// Registers a handler for a path, like `Server::route`.
+    ///
+    /// The handler may return anything convertible into a `Response`. If a
+    /// route with the same path already exists, it is handled as by
+    /// [`Routes::add_route`].
     ///
     /// # Arguments
     ///
     /// * `path` - The URL path for the new route
-    /// * `f` - The handler function for the route
+    /// * `f` - The handler function or closure for the route
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use rwf::routing::Routes;
+    ///
+    /// let mut routes = Routes::new();
+    /// routes.route("/users", |_req, _session| "All users".to_string());
+    /// assert!(routes.get_route("/users").is_some());
+    /// ```
+    pub fn route<R, F>(&mut self, path: &str, f: F)
+    where
+        R: IntoResponse + 'static,
+        F: Fn(Request, &mut Session) -> R + Send + Sync + 'static,
+    {
+        self.add(
+            path,
+            Box::new(move |request, session| f(request, session).into_response()),
+        );
+    }
+
+    /// Registers a handler answering every method, only reachable with an
+    /// existing session, like `Server::protected_route`.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The URL path for the new route
+    /// * `f` - The handler function or closure for the route
+    pub fn protected_route<R, F>(&mut self, path: &str, f: F)
+    where
+        R: IntoResponse + 'static,
+        F: Fn(Request, &mut Session) -> R + Send + Sync + 'static,
+    {
+        let handler: Handler =
+            Box::new(move |request, session| f(request, session).into_response());
+        self.add_route(Route::new(path.to_string(), handler).require_session());
+    }
+
+    /// Registers a handler answering only one method, with a description.
+    ///
+    /// Several methods can be registered on the same path, each with its own
+    /// handler. A route registered with [`Routes::route`] answers the methods
+    /// left over.
+    ///
+    /// # Arguments
+    ///
+    /// * `method` - The method the route answers
+    /// * `path` - The URL path for the new route
+    /// * `description` - A human-readable description, reported by [`Routes::describe`]
+    /// * `f` - The handler function or closure for the route
+    pub fn route_with<R, F>(&mut self, method: Method, path: &str, description: &str, f: F)
+    where
+        R: IntoResponse + 'static,
+        F: Fn(Request, &mut Session) -> R + Send + Sync + 'static,
+    {
+        let handler: Handler =
+            Box::new(move |request, session| f(request, session).into_response());
+        self.add_route(
+            Route::new(path.to_string(), handler)
+                .method(method)
+                .description(description),
+        );
+    }
+
+    /// Registers one handler answering every method on each of several paths.
+    ///
+    /// Behaves like calling [`Routes::route`] once per path with the same
+    /// handler, which is shared rather than cloned. Paths already registered
+    /// are skipped.
+    ///
+    /// # Arguments
+    ///
+    /// * `paths` - The URL paths for the new routes
+    /// * `f` - The handler function or closure for every route
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use rwf::routing::Routes;
+    ///
+    /// let mut routes = Routes::new();
+    /// routes.route_many(&["/", "/index"], |_req, _session| "Home".to_string());
+    /// assert!(routes.get_route("/").is_some() && routes.get_route("/index").is_some());
+    /// ```
+    pub fn route_many<R, F>(&mut self, paths: &[&str], f: F)
+    where
+        R: IntoResponse + 'static,
+        F: Fn(Request, &mut Session) -> R + Send + Sync + 'static,
+    {
+        let f = Arc::new(f);
+        for path in paths {
+            self.add(path, shared_handler(&f));
+        }
+    }
+
+    /// Registers one handler answering each of several methods on a path.
+    ///
+    /// Behaves like registering the handler with [`Routes::route_with`] once
+    /// per method, without a description. Methods other than those listed
+    /// fall back to a route registered with [`Routes::route`] for the path.
+    ///
+    /// # Arguments
+    ///
+    /// * `methods` - The methods the routes answer
+    /// * `path` - The URL path for the new routes
+    /// * `f` - The handler function or closure for every route
     ///
     /// # Example
     ///
     /// ```rust
-    /// routes.add("/hello", |req, session| {
-    ///     "Hello, World!".to_string()
+    /// use rwf::{connection::method::Method, routing::Routes};
+    ///
+    /// let mut routes = Routes::new();
+    /// routes.route_methods(&[Method::PUT, Method::PATCH], "/users/:id", |_req, _session| {
+    ///     "Saved".to_string()
     /// });
+    /// assert!(routes.get_route_for("/users/:id", Method::PUT).is_some());
+    /// assert!(routes.get_route_for("/users/:id", Method::PATCH).is_some());
+    /// assert!(routes.get_route_for("/users/:id", Method::POST).is_none());
     /// ```
-    pub fn add(&mut self, path: &str, f: fn(Request, &mut Session) -> String) {
-        let route = self.get_route(path);
-        if let Some(_) = route {
+    pub fn route_methods<R, F>(&mut self, methods: &[Method], path: &str, f: F)
+    where
+        R: IntoResponse + 'static,
+        F: Fn(Request, &mut Session) -> R + Send + Sync + 'static,
+    {
+        let f = Arc::new(f);
+        for method in methods {
+            self.add_route(Route::new(path.to_string(), shared_handler(&f)).method(method.clone()));
+        }
+    }
+
+    /// Moves every route of `other` into this table, with `prefix` prepended
+    /// to its path.
+    ///
+    /// A route registered as `/` in `other` is mounted at the prefix itself.
+    /// Conflicts are checked with this table's matching options before
+    /// anything is added.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(())` - If every route was added
+    /// * `Err(Vec<String>)` - The prefixed paths already in this table, in
+    ///   which case no route is added
+    pub(crate) fn mount(&mut self, prefix: &str, other: Routes) -> Result<(), Vec<String>> {
+        let prefix = prefix.trim_end_matches('/');
+        let prefix = if prefix.starts_with('/') || prefix.is_empty() {
+            prefix.to_string()
+        } else {
+            format!("/{prefix}")
+        };
+
+        let mounted: Vec<Route> = other
+            .routes
+            .into_iter()
+            .map(|route| {
+                let path = match route.path.trim_start_matches('/') {
+                    "" if !prefix.is_empty() => prefix.clone(),
+                    rest => format!("{prefix}/{rest}"),
+                };
+                Route { path, ..route }
+            })
+            .collect();
+
+        let conflicts: Vec<String> = mounted
+            .iter()
+            .filter(|route| self.conflicts_with(route))
+            .map(|route| route.path.clone())
+            .collect();
+        if !conflicts.is_empty() {
+            return Err(conflicts);
+        }
+
+        self.routes.extend(mounted);
+        Ok(())
+    }
+
+    /// Adds a new route answering every method to the routing table.
+    ///
+    /// If such a route with the same path already exists, the function logs a
+    /// warning and returns without making any changes, or panics in strict
+    /// mode, see [`Routes::set_strict`].
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The URL path for the new route
+    /// * `f` - The handler function for the route
+    ///
+    /// # Example
+    ///
+    /// ```rust,ignore
+    /// routes.add("/hello", Box::new(|req, session| {
+    ///     "Hello, World!".into()
+    /// }));
+    /// ```
+    pub fn add(&mut self, path: &str, f: Handler) {
+        self.add_route(Route::new(path.to_string(), f));
+    }
+
+    /// Adds a route built with [`Route::new`] and its builder methods.
+    ///
+    /// If a route with the same path and method already exists, the function
+    /// logs a warning and returns without making any changes.
+    ///
+    /// # Arguments
+    ///
+    /// * `route` - The route to add
+    ///
+    /// # Panics
+    ///
+    /// Panics on a duplicate route if strict mode is enabled with
+    /// [`Routes::set_strict`].
+    pub fn add_route(&mut self, route: Route) {
+        if self.conflicts_with(&route) {
+            let name = match route.method {
+                Some(method) => format!("{method} {}", route.path),
+                None => route.path.clone(),
+            };
+            if self.strict {
+                panic!("Route {name} is already registered");
+            }
+            println!("Ignoring duplicate route {name}");
             return;
         }
-        let new_route = Route::new(path.to_string(), f);
-        self.routes.push(new_route);
+        self.routes.push(route);
     }
+
+    /// Checks whether a route with the same path and method is registered.
+    fn conflicts_with(&self, route: &Route) -> bool {
+        let path = self.matching.normalize(&route.path);
+        self.routes.iter().any(|existing| {
+            existing.method == route.method && self.matching.normalize(&existing.path) == path
+        })
+    }
+}
+
+/// Boxes a handler shared by several routes.
+fn shared_handler<R, F>(f: &Arc<F>) -> Handler
+where
+    R: IntoResponse + 'static,
+    F: Fn(Request, &mut Session) -> R + Send + Sync + 'static,
+{
+    let f = Arc::clone(f);
+    Box::new(move |request, session| f(request, session).into_response())
 }
@@ -0,0 +1,62 @@
+//! # Test Support Module
+//!
+//! Helpers shared by the documentation examples, so each example only shows
+//! the behavior it documents. Not part of the public API.
+
+use crate::Server;
+use std::{
+    io::{Read, Write},
+    net::{SocketAddr, TcpStream},
+    thread,
+};
+
+/// Runs a quiet server on a background thread.
+///
+/// # Arguments
+///
+/// * `server` - The configured server
+///
+/// # Returns
+///
+/// The address the server listens on
+pub fn serve(mut server: Server) -> SocketAddr {
+    server.quiet(true);
+    let addr = server.address().unwrap();
+    thread::spawn(move || server.run());
+    addr
+}
+
+/// Sends a raw request on a new connection.
+///
+/// # Arguments
+///
+/// * `addr` - The address of the server
+/// * `request` - The raw request, sent as is
+///
+/// # Returns
+///
+/// Everything the server sent until it closed the connection
+pub fn send(addr: SocketAddr, request: &str) -> String {
+    let mut stream = TcpStream::connect(addr).unwrap();
+    stream.write_all(request.as_bytes()).unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+    response
+}
+
+/// Sends a `GET` request closing the connection after the response.
+///
+/// # Arguments
+///
+/// * `addr` - The address of the server
+/// * `path` - The request target
+///
+/// # Returns
+///
+/// The raw response
+pub fn get(addr: SocketAddr, path: &str) -> String {
+    send(
+        addr,
+        &format!("GET {path} HTTP/1.1\r\nConnection: close\r\n\r\n"),
+    )
+}
@@ -0,0 +1,77 @@
+//! # TLS Module
+//!
+//! Loads certificates and private keys for HTTPS and wraps accepted TCP
+//! connections in rustls sessions. Only compiled with the `tls` feature.
+//!
+//! Certificates and keys are read from PEM files:
+//! - The certificate file holds one or more `CERTIFICATE` blocks, starting with
+//!   the server certificate followed by any intermediates.
+//! - The key file holds a single private key as `PRIVATE KEY` (PKCS#8),
+//!   `RSA PRIVATE KEY` (PKCS#1) or `EC PRIVATE KEY` (SEC1).
+
+use rustls::{
+    pki_types::{pem::PemObject, CertificateDer, PrivateKeyDer},
+    ServerConfig, ServerConnection, StreamOwned,
+};
+use std::{
+    io::{self, ErrorKind},
+    net::TcpStream,
+    sync::Arc,
+};
+
+/// A TCP connection wrapped in an established TLS session.
+pub type TlsStream = StreamOwned<ServerConnection, TcpStream>;
+
+/// Builds a rustls server configuration from PEM encoded certificate and key files.
+///
+/// # Arguments
+///
+/// * `cert_path` - Path to the PEM certificate chain
+/// * `key_path` - Path to the PEM private key
+///
+/// # Returns
+///
+/// * `io::Result<Arc<ServerConfig>>` - The shared configuration, or an
+///   `InvalidData` error if the files cannot be read or used
+pub fn load_config(cert_path: &str, key_path: &str) -> io::Result<Arc<ServerConfig>> {
+    let certs = CertificateDer::pem_file_iter(cert_path)
+        .and_then(|certs| certs.collect::<Result<Vec<_>, _>>())
+        .map_err(|e| {
+            invalid(format!(
+                "failed to read certificates from '{cert_path}': {e}"
+            ))
+        })?;
+
+    let key = PrivateKeyDer::from_pem_file(key_path)
+        .map_err(|e| invalid(format!("failed to read private key from '{key_path}': {e}")))?;
+
+    let config = ServerConfig::builder()
+        .with_no_client_auth()
+        .with_single_cert(certs, key)
+        .map_err(|e| invalid(format!("invalid certificate or key: {e}")))?;
+
+    Ok(Arc::new(config))
+}
+
+/// Performs the TLS handshake on an accepted TCP connection.
+///
+/// # Arguments
+///
+/// * `config` - The server configuration built by [`load_config`]
+/// * `tcp` - The accepted TCP connection
+///
+/// # Returns
+///
+/// * `io::Result<TlsStream>` - The encrypted stream, or the error that made
+///   the handshake fail
+pub fn accept(config: &Arc<ServerConfig>, mut tcp: TcpStream) -> io::Result<TlsStream> {
+    let mut conn = ServerConnection::new(Arc::clone(config)).map_err(invalid)?;
+    while conn.is_handshaking() {
+        conn.complete_io(&mut tcp)?;
+    }
+    Ok(StreamOwned::new(conn, tcp))
+}
+
+fn invalid(error: impl Into<Box<dyn std::error::Error + Send + Sync>>) -> io::Error {
+    io::Error::new(ErrorKind::InvalidData, error)
+}